@@ -0,0 +1,263 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use ff::Field;
+use ff::PrimeField;
+use sprs::CsMat;
+use crate::codegen::generate_from;
+use crate::codegen::generate_rev_from;
+use crate::codespec::CodeParams;
+use crate::codespec::CodeSpecification;
+use crate::serialize::DecodeError;
+use crate::serialize::Reader;
+use crate::serialize::read_field;
+use crate::serialize::write_field;
+use crate::serialize::write_len;
+
+const MAGIC: &[u8; 8] = b"BRKCODE\0";
+const CACHE_VERSION: u8 = 1;
+const CHECKSUM_LEN: usize = 32;
+
+type Codes<F> = (Vec<CsMat<F>>, Vec<CsMat<F>>);
+
+// everything the generated matrices depend on: the code specification, n, seed, field modulus and the reverse flag
+fn cache_key<F>(code: &CodeParams, n: usize, seed: u64, reverse: bool) -> Vec<u8>
+where
+    F: PrimeField,
+{
+    let mut key = Vec::new();
+    for x in [
+        code.alpha_num, code.alpha_den,
+        code.beta_num, code.beta_den,
+        code.r_num, code.r_den,
+        code.baselen,
+        n,
+    ] {
+        write_len(&mut key, x);
+    }
+    key.extend_from_slice(&seed.to_le_bytes());
+    // p - 1 identifies the modulus
+    write_field(&mut key, &(<F as Field>::zero() - <F as Field>::one()));
+    key.push(reverse as u8);
+    key
+}
+
+/// file in `dir` holding the code generated for the given key
+pub fn cache_path<F>(dir: &Path, code: &CodeParams, n: usize, seed: u64, reverse: bool) -> PathBuf
+where
+    F: PrimeField,
+{
+    let hash = blake3::hash(&cache_key::<F>(code, n, seed, reverse));
+    dir.join(format!("{}.code", &hash.to_hex()[..32]))
+}
+
+fn write_matrix<F>(out: &mut Vec<u8>, code: &CsMat<F>)
+where
+    F: PrimeField,
+{
+    // indices and offsets are stored as u32
+    assert!(code.rows().max(code.cols()).max(code.nnz()) <= u32::MAX as usize);
+    out.push(code.is_csc() as u8);
+    write_len(out, code.rows());
+    write_len(out, code.cols());
+    let indptr = code.proper_indptr();
+    write_len(out, indptr.len());
+    for &i in indptr.iter() {
+        out.extend_from_slice(&(i as u32).to_le_bytes());
+    }
+    write_len(out, code.nnz());
+    for &i in code.indices().iter() {
+        out.extend_from_slice(&(i as u32).to_le_bytes());
+    }
+    for x in code.data().iter() {
+        write_field(out, x);
+    }
+}
+
+fn read_u32s(reader: &mut Reader) -> Result<Vec<usize>, DecodeError> {
+    let len = reader.read_len(4)?;
+    let bytes = reader.read_bytes(4 * len)?;
+    Ok(bytes.chunks_exact(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize).collect())
+}
+
+fn read_matrix<F>(reader: &mut Reader) -> Result<CsMat<F>, DecodeError>
+where
+    F: PrimeField,
+{
+    let is_csc = reader.read_bytes(1)?[0] == 1;
+    let rows = reader.read_u64()? as usize;
+    let cols = reader.read_u64()? as usize;
+    let indptr = read_u32s(reader)?;
+    let indices = read_u32s(reader)?;
+    let data = (0..indices.len()).map(|_| read_field::<F>(reader)).collect::<Result<Vec<F>, _>>()?;
+    let code = if is_csc {
+        CsMat::try_new_csc((rows, cols), indptr, indices, data)
+    }else{
+        CsMat::try_new((rows, cols), indptr, indices, data)
+    };
+    code.map_err(|_| DecodeError::InvalidLength(rows as u64))
+}
+
+fn encode_codes<F>(key: &[u8], precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Vec<u8>
+where
+    F: PrimeField,
+{
+    let mut out = MAGIC.to_vec();
+    out.push(CACHE_VERSION);
+    write_len(&mut out, key.len());
+    out.extend_from_slice(key);
+    for codes in [precodes, postcodes] {
+        write_len(&mut out, codes.len());
+        for code in codes.iter() {
+            write_matrix(&mut out, code);
+        }
+    }
+    let checksum = blake3::hash(&out);
+    out.extend_from_slice(checksum.as_bytes());
+    out
+}
+
+// None if the bytes are not a valid cache file for `key`
+fn decode_codes<F>(key: &[u8], bytes: &[u8]) -> Option<Codes<F>>
+where
+    F: PrimeField,
+{
+    if bytes.len() < CHECKSUM_LEN {
+        return None;
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+    if blake3::hash(body).as_bytes() != checksum {
+        return None;
+    }
+
+    let mut reader = Reader::new(body);
+    if reader.read_bytes(MAGIC.len()).ok()? != MAGIC || reader.read_bytes(1).ok()?[0] != CACHE_VERSION {
+        return None;
+    }
+    let key_len = reader.read_len(1).ok()?;
+    if reader.read_bytes(key_len).ok()? != key {
+        return None;
+    }
+    let mut read_codes = || -> Result<Vec<CsMat<F>>, DecodeError> {
+        let len = reader.read_len(1)?;
+        (0..len).map(|_| read_matrix::<F>(&mut reader)).collect()
+    };
+    let precodes = read_codes().ok()?;
+    let postcodes = read_codes().ok()?;
+    if reader.remaining() != 0 || precodes.is_empty() || precodes.len() != postcodes.len() {
+        return None;
+    }
+    Some((precodes, postcodes))
+}
+
+// load the code from the cache, or generate it with `gen` and store it
+fn cached<F, G>(path: &Path, key: &[u8], gen: G) -> io::Result<Codes<F>>
+where
+    F: PrimeField,
+    G: FnOnce() -> Codes<F>,
+{
+    match fs::read(path) {
+        Ok(bytes) => {
+            // a stale or corrupted file is regenerated
+            if let Some(codes) = decode_codes::<F>(key, &bytes) {
+                return Ok(codes);
+            }
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let (precodes, postcodes) = gen();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // write to a temporary file first so concurrent readers never see a partial file
+    let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+    fs::write(&tmp, encode_codes(key, &precodes, &postcodes))?;
+    fs::rename(&tmp, path)?;
+    Ok((precodes, postcodes))
+}
+
+/// `generate`, loading the matrices from `dir` if they were generated before
+pub fn generate_cached<F, S>(dir: &Path, n: usize, seed: u64) -> io::Result<Codes<F>>
+where
+    F: PrimeField,
+    S: CodeSpecification,
+{
+    generate_cached_from::<F>(dir, &S::code_params(), n, seed)
+}
+
+/// `generate_rev`, loading the matrices from `dir` if they were generated before
+pub fn generate_rev_cached<F, S>(dir: &Path, n: usize, seed: u64) -> io::Result<Codes<F>>
+where
+    F: PrimeField,
+    S: CodeSpecification,
+{
+    generate_rev_cached_from::<F>(dir, &S::code_params(), n, seed)
+}
+
+/// `generate_from`, loading the matrices from `dir` if they were generated before
+pub fn generate_cached_from<F>(dir: &Path, code: &CodeParams, n: usize, seed: u64) -> io::Result<Codes<F>>
+where
+    F: PrimeField,
+{
+    let key = cache_key::<F>(code, n, seed, false);
+    cached(&cache_path::<F>(dir, code, n, seed, false), &key, || generate_from::<F>(code, n, seed))
+}
+
+/// `generate_rev_from`, loading the matrices from `dir` if they were generated before
+pub fn generate_rev_cached_from<F>(dir: &Path, code: &CodeParams, n: usize, seed: u64) -> io::Result<Codes<F>>
+where
+    F: PrimeField,
+{
+    let key = cache_key::<F>(code, n, seed, true);
+    cached(&cache_path::<F>(dir, code, n, seed, true), &key, || generate_rev_from::<F>(code, n, seed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate;
+    use crate::codegen::generate_rev;
+    use crate::codespec::Code6;
+    use crate::fieldspec::ft127::Ft127;
+    use crate::fieldspec::ft255::Ft255;
+
+    fn same<F>(a: &Codes<F>, b: &Codes<F>) -> bool
+    where
+        F: PrimeField,
+    {
+        let eq = |x: &[CsMat<F>], y: &[CsMat<F>]| x.len() == y.len() && x.iter().zip(y.iter()).all(|(x, y)| x == y);
+        eq(&a.0, &b.0) && eq(&a.1, &b.1)
+    }
+
+    #[test]
+    fn load_and_regenerate() {
+        let dir = std::env::temp_dir().join(format!("thesis-codecache-{}", std::process::id()));
+        let fresh = generate::<Ft255, Code6>(300, 7);
+        let written = generate_cached::<Ft255, Code6>(&dir, 300, 7).unwrap();
+        let loaded = generate_cached::<Ft255, Code6>(&dir, 300, 7).unwrap();
+        assert!(same(&fresh, &written) && same(&fresh, &loaded));
+
+        // the reverse code and other fields get their own files
+        let code = Code6::code_params();
+        let path = cache_path::<Ft255>(&dir, &code, 300, 7, false);
+        assert_ne!(path, cache_path::<Ft255>(&dir, &code, 300, 7, true));
+        assert_ne!(path, cache_path::<Ft127>(&dir, &code, 300, 7, false));
+        let rev = generate_rev_cached::<Ft255, Code6>(&dir, 300, 7).unwrap();
+        assert!(same(&generate_rev::<Ft255, Code6>(300, 7), &rev));
+
+        // a corrupted file fails the checksum and is replaced
+        let mut bytes = fs::read(&path).unwrap();
+        let mid = bytes.len() / 2;
+        bytes[mid] ^= 1;
+        fs::write(&path, &bytes).unwrap();
+        assert!(decode_codes::<Ft255>(&cache_key::<Ft255>(&code, 300, 7, false), &bytes).is_none());
+        let regenerated = generate_cached::<Ft255, Code6>(&dir, 300, 7).unwrap();
+        assert!(same(&fresh, &regenerated));
+        assert!(decode_codes::<Ft255>(&cache_key::<Ft255>(&code, 300, 7, false), &fs::read(&path).unwrap()).is_some());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -7,29 +7,32 @@ use itertools::iterate;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
 use rayon::prelude::*;
+use crate::codespec::CodeParams;
 use crate::codespec::CodeSpecification;
 
 
 const fn ceil_muldiv(n: usize, num: usize, den: usize) -> usize {
-    (n * num + den - 1) / den
+    (n * num).div_ceil(den)
 }
 
-// compute dimensions for all of the matrices used by this code
-fn get_dims<S: CodeSpecification>(
+// compute dimensions for all of the matrices used by this code, the outermost postcode fills the codeword up to code_len
+fn get_dims(
+    code: &CodeParams,
     n: usize,
+    code_len: usize,
     log2p: f64,
 ) -> (Vec<(usize, usize, usize)>, Vec<(usize, usize, usize)>) {
     use std::cmp::{max, min};
-    let baselen = S::baselen();
+    let baselen = code.baselen;
     assert!(n > baselen);
 
     // figure out dimensions for the precode and postcode matrices
     let pre_dims = {
-        let mut tmp: Vec<_> = iterate(n, |&ni| ceil_muldiv(ni, S::alpha_num(), S::alpha_den()))
+        let mut tmp: Vec<_> = iterate(n, |&ni| ceil_muldiv(ni, code.alpha_num, code.alpha_den))
             .take_while(|&ni| ni > baselen)
             .collect();
         if let Some(&ni) = tmp.last() {
-            let last = ceil_muldiv(ni, S::alpha_num(), S::alpha_den());
+            let last = ceil_muldiv(ni, code.alpha_num, code.alpha_den);
             assert!(last <= baselen);
             tmp.push(last);
         }
@@ -41,10 +44,10 @@ fn get_dims<S: CodeSpecification>(
                 let mi = nm[1];
                 let cn = min(
                     max(
-                        ceil_muldiv(ni, 32 * S::beta_num(), 25 * S::beta_den()),
-                        4 + ceil_muldiv(ni, S::beta_num(), S::beta_den()),
+                        ceil_muldiv(ni, 32 * code.beta_num, 25 * code.beta_den),
+                        4 + ceil_muldiv(ni, code.beta_num, code.beta_den),
                     ),
-                    ((110f64 / (ni as f64) + S::cnst_cn_1()) / S::cnst_cn_2()).ceil() as usize,
+                    ((110f64 / (ni as f64) + code.cnst_cn_1()) / code.cnst_cn_2()).ceil() as usize,
                 );
                 let cn = min(cn, mi); // can't generate more nonzero entries than there are columns
                 (ni, mi, cn)
@@ -54,14 +57,16 @@ fn get_dims<S: CodeSpecification>(
 
     let post_dims = pre_dims
         .iter()
-        .map(|&(ni, mi, _)| {
-            let niprime = ceil_muldiv(mi, S::r_num(), S::r_den());
-            let miprime = ceil_muldiv(ni, S::r_num(), S::r_den()) - ni - niprime;
-            let tmp1 = ceil_muldiv(ni, 2 * S::beta_num(), S::beta_den()); // 2 * beta * ni
-            let tmp2 = ceil_muldiv(ni, S::r_num(), S::r_den()) - ni + 110; // ni * (r - 1 + 110/ni)
+        .enumerate()
+        .map(|(i, &(ni, mi, _))| {
+            let niprime = ceil_muldiv(mi, code.r_num, code.r_den);
+            let ni_len = if i == 0 { code_len } else { ceil_muldiv(ni, code.r_num, code.r_den) };
+            let miprime = ni_len - ni - niprime;
+            let tmp1 = ceil_muldiv(ni, 2 * code.beta_num, code.beta_den); // 2 * beta * ni
+            let tmp2 = ceil_muldiv(ni, code.r_num, code.r_den) - ni + 110; // ni * (r - 1 + 110/ni)
             let dn = min(
                 tmp1 + (tmp2 as f64 / log2p).ceil() as usize,
-                ((110f64 / (ni as f64) + S::cnst_dn_1()) / S::cnst_dn_2()).ceil() as usize,
+                ((110f64 / (ni as f64) + code.cnst_dn_1()) / code.cnst_dn_2()).ceil() as usize,
             );
             let dn = min(dn, miprime); // can't generate more nonzero entries than there are columns
             (niprime, miprime, dn)
@@ -84,69 +89,77 @@ where
     let mut ptrs = Vec::<usize>::with_capacity(1 + n);
     ptrs.push(0); // ptrs always starts with 0
 
+    let mut rows = Vec::with_capacity(d);
+    let mut vals = Vec::with_capacity(d);
     for _ in 0..n {
-        // for each row, sample d random nonzero columns (without replacement)
-        let cols = {
-            /*
-            let mut nub = HashSet::new();
-            let mut tmp = (&mut rng)
-                .sample_iter(&dist)
+        gen_column(&dist, d, &mut rng, &mut rows, &mut vals);
+        idxs.extend_from_slice(&rows);
+        data.extend_from_slice(&vals);
+        ptrs.push(data.len());
+    }
+
+    CsMat::new_csc((m, n), ptrs, idxs, data)
+}
+
+/// sample the next column of a `gen_matrix` matrix with rows drawn from `dist`: d distinct rows in increasing order
+/// and their nonzero entries, consuming the same randomness as `gen_matrix` does for it
+pub fn gen_column<F, R>(dist: &Uniform<usize>, d: usize, mut rng: R, rows: &mut Vec<usize>, vals: &mut Vec<F>)
+where
+    F: PrimeField,
+    R: Rng,
+{
+    // for each column, sample d random nonzero rows (without replacement)
+    let cols = {
+        /*
+        let mut nub = HashSet::new();
+        let mut tmp = (&mut rng)
+            .sample_iter(&dist)
+            .filter(|&x| {
+                if nub.contains(&x) {
+                    false
+                } else {
+                    nub.insert(x);
+                    true
+                }
+            })
+            .take(d)
+            .collect::<Vec<_>>();
+        */
+        // for small d, the quadratic approach is almost certainly faster
+        let tmp = &mut *rows;
+        tmp.clear();
+        assert_eq!(
+            d,
+            (&mut rng)
+                .sample_iter(dist)
                 .filter(|&x| {
-                    if nub.contains(&x) {
+                    if tmp.contains(&x) {
                         false
                     } else {
-                        nub.insert(x);
+                        tmp.push(x);
                         true
                     }
                 })
                 .take(d)
-                .collect::<Vec<_>>();
-            */
-            // for small d, the quadratic approach is almost certainly faster
-            let mut tmp = Vec::with_capacity(d);
-            assert_eq!(
-                d,
-                (&mut rng)
-                    .sample_iter(&dist)
-                    .filter(|&x| {
-                        if tmp.contains(&x) {
-                            false
-                        } else {
-                            tmp.push(x);
-                            true
-                        }
-                    })
-                    .take(d)
-                    .count()
-            );
-            tmp.sort_unstable(); // need to sort to supply to new_csc below
+                .count()
+        );
+        tmp.sort_unstable(); // need to sort to supply to new_csc
+        tmp
+    };
+    assert_eq!(d, cols.len());
+
+    // sample random elements for each row
+    vals.clear();
+    for _ in 0..d {
+        let val = {
+            let mut tmp = F::random(&mut rng);
+            while bool::from(<F as Field>::is_zero(&tmp)) {
+                tmp = F::random(&mut rng);
+            }
             tmp
         };
-        assert_eq!(d, cols.len());
-
-        // sample random elements for each column
-        let mut last = m + 1;
-        for &col in &cols[..] {
-            // detect and skip repeats
-            if col == last {
-                continue;
-            }
-            last = col;
-
-            let val = {
-                let mut tmp = F::random(&mut rng);
-                while bool::from(<F as Field>::is_zero(&tmp)) {
-                    tmp = F::random(&mut rng);
-                }
-                tmp
-            };
-            idxs.push(col);
-            data.push(val);
-        }
-        ptrs.push(data.len());
+        vals.push(val);
     }
-
-    CsMat::new_csc((m, n), ptrs, idxs, data)
 }
 
 /// Generate a random code from a given seed
@@ -155,7 +168,57 @@ where
     F: PrimeField,
     S: CodeSpecification,
 {
-    let (pre_dims, post_dims) = get_dims::<S>(n, (F::NUM_BITS - 1) as f64);
+    generate_from::<F>(&S::code_params(), n, seed)
+}
+
+// the code for n <= baselen message symbols: the message followed by rs_len symbols of its Reed-Solomon encoding,
+// written as a single level with the identity as precode and an empty postcode
+fn generate_base<F>(n: usize, rs_len: usize) -> (Vec<CsMat<F>>, Vec<CsMat<F>>)
+where
+    F: PrimeField,
+{
+    let identity = CsMat::new_csc((n, n), (0..=n).collect(), (0..n).collect(), vec![<F as Field>::one(); n]);
+    let empty = CsMat::new_csc((0, rs_len), vec![0; rs_len + 1], Vec::new(), Vec::new());
+    (vec![identity], vec![empty])
+}
+
+/// Generate a random code for a runtime code specification from a given seed,
+/// messages of at most baselen symbols get the identity plus Reed-Solomon code of `generate_base`
+pub fn generate_from<F>(code: &CodeParams, n: usize, seed: u64) -> (Vec<CsMat<F>>, Vec<CsMat<F>>)
+where
+    F: PrimeField,
+{
+    generate_sized::<F>(code, n, generated_length(code, n), seed)
+}
+
+/// Codeword length of the code `generate_from` generates for n message symbols
+pub fn generated_length(code: &CodeParams, n: usize) -> usize {
+    assert!(n >= 1);
+    ceil_muldiv(n, code.r_num, code.r_den).max(n + 1)
+}
+
+/// Dimensions (input length, output length, nonzeros per column) of the precodes and postcodes `generate_from`
+/// samples for n message symbols, None for the identity plus Reed-Solomon code of messages of at most baselen symbols
+pub fn generated_dims<F>(code: &CodeParams, n: usize) -> Option<(Vec<(usize, usize, usize)>, Vec<(usize, usize, usize)>)>
+where
+    F: PrimeField,
+{
+    if n <= code.baselen {
+        return None;
+    }
+    Some(get_dims(code, n, generated_length(code, n), (F::NUM_BITS - 1) as f64))
+}
+
+// generate_from with codewords of exactly code_len symbols, about r * n
+fn generate_sized<F>(code: &CodeParams, n: usize, code_len: usize, seed: u64) -> (Vec<CsMat<F>>, Vec<CsMat<F>>)
+where
+    F: PrimeField,
+{
+    assert!(code_len > n);
+    if n <= code.baselen {
+        return generate_base::<F>(n, code_len - n);
+    }
+    let (pre_dims, post_dims) = get_dims(code, n, code_len, (F::NUM_BITS - 1) as f64);
     assert!(!pre_dims.is_empty());
 
     let mut precodes = Vec::with_capacity(pre_dims.len());
@@ -182,8 +245,17 @@ where
     F: PrimeField,
     S: CodeSpecification,
 {
-    let input_len = ((n as f64) / S::r()).floor() as usize;
-    let (mut precodes, mut postcodes) = generate::<F, S>(input_len, seed);
+    generate_rev_from::<F>(&S::code_params(), n, seed)
+}
+
+/// Generate a random reverse code for a runtime code specification from a given seed
+pub fn generate_rev_from<F>(code: &CodeParams, n: usize, seed: u64) -> (Vec<CsMat<F>>, Vec<CsMat<F>>)
+where
+    F: PrimeField,
+{
+    let input_len = (((n as f64) / code.r()).floor() as usize).max(1);
+    // r * input_len need not round to n, the outermost postcode takes up the difference
+    let (mut precodes, mut postcodes) = generate_sized::<F>(code, input_len, n, seed);
     // println!("x len: {}", precodes[0].cols());
     for precode in precodes.iter_mut() {
         precode.transpose_mut();
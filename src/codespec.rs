@@ -1,6 +1,10 @@
+use std::fmt;
+use std::fs;
+use std::io;
 use std::marker::Sync;
 use std::marker::PhantomData;
 use std::ops::Add;
+use std::path::Path;
 use typenum::Unsigned;
 
 pub fn binary_entropy(z: f64) -> f64 {
@@ -29,10 +33,22 @@ pub trait CodeSpecification {
     /// base-case code length
     type Blen: Unsigned;
 
+    /// the same code as a runtime `CodeParams`
+    fn code_params() -> CodeParams {
+        CodeParams {
+            alpha_num: Self::An::to_usize(),
+            alpha_den: Self::Ad::to_usize(),
+            beta_num: Self::Bn::to_usize(),
+            beta_den: Self::Bd::to_usize(),
+            r_num: Self::Rn::to_usize(),
+            r_den: Self::Rd::to_usize(),
+            baselen: Self::Blen::to_usize(),
+        }
+    }
+
     /// distance as f64 {
     fn dist() -> f64 {
-        (Self::Bn::to_usize() * Self::Rd::to_usize()) as f64
-            / (Self::Bd::to_usize() * Self::Rn::to_usize()) as f64
+        Self::code_params().dist()
     }
 
     /// alpha num as usize
@@ -72,48 +88,212 @@ pub trait CodeSpecification {
 
     /// alpha as f64
     fn alpha() -> f64 {
-        Self::An::to_usize() as f64 / Self::Ad::to_usize() as f64
+        Self::code_params().alpha()
     }
 
     /// beta as f64
     fn beta() -> f64 {
-        Self::Bn::to_usize() as f64 / Self::Bd::to_usize() as f64
+        Self::code_params().beta()
     }
 
     /// r as f64
     fn r() -> f64 {
-        Self::Rn::to_usize() as f64 / Self::Rd::to_usize() as f64
+        Self::code_params().r()
     }
 
     /// mu = r - 1 - r * alpha
     fn mu() -> f64 {
-        Self::r() - 1f64 - Self::r() * Self::alpha()
+        Self::code_params().mu()
     }
 
     /// nu = beta + alpha * beta + 0.03
     fn nu() -> f64 {
-        Self::beta() + Self::alpha() * Self::beta() + 0.03f64
+        Self::code_params().nu()
     }
 
     /// constant for cn calculation
     fn cnst_cn_1() -> f64 {
-        binary_entropy(Self::beta()) + Self::alpha() * binary_entropy(1.28f64 * Self::beta() / Self::alpha())
+        Self::code_params().cnst_cn_1()
     }
 
     /// constant for cn calculation
     fn cnst_cn_2() -> f64 {
-        Self::beta() * (Self::alpha() / (1.28f64 * Self::beta())).log2()
+        Self::code_params().cnst_cn_2()
     }
 
     /// constant for dn calculation
     fn cnst_dn_1() -> f64 {
-        Self::r() * Self::alpha() * binary_entropy(Self::beta() / Self::r())
-            + Self::mu() * binary_entropy(Self::nu() / Self::mu())
+        Self::code_params().cnst_dn_1()
     }
 
     /// constant for dn calculation
     fn cnst_dn_2() -> f64 {
-        Self::alpha() * Self::beta() * (Self::mu() / Self::nu()).log2()
+        Self::code_params().cnst_dn_2()
+    }
+}
+
+/// A code specification chosen at runtime, e.g. a new row of the Brakedown table read from a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeParams {
+    pub alpha_num: usize,
+    pub alpha_den: usize,
+    pub beta_num: usize,
+    pub beta_den: usize,
+    pub r_num: usize,
+    pub r_den: usize,
+    /// base-case code length
+    pub baselen: usize,
+}
+
+/// Reasons a code specification file is rejected
+#[derive(Debug)]
+pub enum CodeParamsError {
+    Io(io::Error),
+    /// the line is not `key = value` with a known key and a fraction as value
+    Syntax { line: usize },
+    Missing(&'static str),
+    /// the constants do not describe a code, e.g. beta / r >= 1
+    Invalid(&'static str),
+}
+
+impl fmt::Display for CodeParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeParamsError::Io(e) => write!(f, "{}", e),
+            CodeParamsError::Syntax { line } => write!(f, "syntax error on line {}", line),
+            CodeParamsError::Missing(key) => write!(f, "missing key {}", key),
+            CodeParamsError::Invalid(reason) => write!(f, "invalid code: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CodeParamsError {}
+
+impl CodeParams {
+    /// parse `key = value` lines with the keys alpha, beta, r and baselen, e.g.
+    ///
+    /// ```text
+    /// # line 6 from table
+    /// alpha = 119/500
+    /// beta = 241/2000
+    /// r = 43/25
+    /// baselen = 10
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, CodeParamsError> {
+        let (mut alpha, mut beta, mut r, mut baselen) = (None, None, None, None);
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let syntax = CodeParamsError::Syntax { line: i + 1 };
+            let (key, value) = match line.split_once('=') {
+                Some(kv) => kv,
+                None => return Err(syntax),
+            };
+            let (num, den) = match value.trim().split_once('/') {
+                Some((num, den)) => (num.trim().parse::<usize>(), den.trim().parse::<usize>()),
+                None => (value.trim().parse::<usize>(), Ok(1)),
+            };
+            let fraction = match (num, den) {
+                (Ok(num), Ok(den)) => (num, den),
+                _ => return Err(syntax),
+            };
+            match key.trim() {
+                "alpha" => alpha = Some(fraction),
+                "beta" => beta = Some(fraction),
+                "r" => r = Some(fraction),
+                "baselen" if fraction.1 == 1 => baselen = Some(fraction.0),
+                _ => return Err(syntax),
+            }
+        }
+
+        let (alpha_num, alpha_den) = alpha.ok_or(CodeParamsError::Missing("alpha"))?;
+        let (beta_num, beta_den) = beta.ok_or(CodeParamsError::Missing("beta"))?;
+        let (r_num, r_den) = r.ok_or(CodeParamsError::Missing("r"))?;
+        let baselen = baselen.ok_or(CodeParamsError::Missing("baselen"))?;
+        let code = CodeParams { alpha_num, alpha_den, beta_num, beta_den, r_num, r_den, baselen };
+        code.validate()?;
+        Ok(code)
+    }
+
+    /// read a file in the format of `parse`
+    pub fn load(path: &Path) -> Result<Self, CodeParamsError> {
+        Self::parse(&fs::read_to_string(path).map_err(CodeParamsError::Io)?)
+    }
+
+    /// check the constants describe a code, so that all the entropies below are defined
+    pub fn validate(&self) -> Result<(), CodeParamsError> {
+        if self.alpha_den == 0 || self.beta_den == 0 || self.r_den == 0 {
+            return Err(CodeParamsError::Invalid("zero denominator"));
+        }
+        if self.baselen == 0 {
+            return Err(CodeParamsError::Invalid("baselen must be positive"));
+        }
+        if !(self.alpha() > 0f64 && self.alpha() < 1f64) {
+            return Err(CodeParamsError::Invalid("alpha must be in (0, 1)"));
+        }
+        if !(self.beta() > 0f64 && 1.28f64 * self.beta() < self.alpha()) {
+            return Err(CodeParamsError::Invalid("beta must be in (0, alpha / 1.28)"));
+        }
+        if !(self.r() > 1f64 && self.beta() < self.r()) {
+            return Err(CodeParamsError::Invalid("r must be larger than 1 and beta"));
+        }
+        if !(self.nu() > 0f64 && self.nu() < self.mu()) {
+            return Err(CodeParamsError::Invalid("nu must be in (0, mu)"));
+        }
+        Ok(())
+    }
+
+    /// distance as f64
+    pub fn dist(&self) -> f64 {
+        (self.beta_num * self.r_den) as f64 / (self.beta_den * self.r_num) as f64
+    }
+
+    /// alpha as f64
+    pub fn alpha(&self) -> f64 {
+        self.alpha_num as f64 / self.alpha_den as f64
+    }
+
+    /// beta as f64
+    pub fn beta(&self) -> f64 {
+        self.beta_num as f64 / self.beta_den as f64
+    }
+
+    /// r as f64
+    pub fn r(&self) -> f64 {
+        self.r_num as f64 / self.r_den as f64
+    }
+
+    /// mu = r - 1 - r * alpha
+    pub fn mu(&self) -> f64 {
+        self.r() - 1f64 - self.r() * self.alpha()
+    }
+
+    /// nu = beta + alpha * beta + 0.03
+    pub fn nu(&self) -> f64 {
+        self.beta() + self.alpha() * self.beta() + 0.03f64
+    }
+
+    /// constant for cn calculation
+    pub fn cnst_cn_1(&self) -> f64 {
+        binary_entropy(self.beta()) + self.alpha() * binary_entropy(1.28f64 * self.beta() / self.alpha())
+    }
+
+    /// constant for cn calculation
+    pub fn cnst_cn_2(&self) -> f64 {
+        self.beta() * (self.alpha() / (1.28f64 * self.beta())).log2()
+    }
+
+    /// constant for dn calculation
+    pub fn cnst_dn_1(&self) -> f64 {
+        self.r() * self.alpha() * binary_entropy(self.beta() / self.r())
+            + self.mu() * binary_entropy(self.nu() / self.mu())
+    }
+
+    /// constant for dn calculation
+    pub fn cnst_dn_2(&self) -> f64 {
+        self.alpha() * self.beta() * (self.mu() / self.nu()).log2()
     }
 }
 
@@ -219,3 +399,21 @@ pub type Code6 = CodeSpec<
     typenum::U25,
     typenum::U10, // baselen = 20
 >;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_code_params() {
+        let text = "# line 6 from table\nalpha = 119/500\nbeta = 241/2000\nr = 43 / 25\nbaselen = 10 # base case\n";
+        let code = CodeParams::parse(text).unwrap();
+        assert_eq!(code, Code6::code_params());
+        assert_eq!(code.mu(), Code6::mu());
+        assert_eq!(code.cnst_cn_1(), Code6::cnst_cn_1());
+
+        assert!(matches!(CodeParams::parse("alpha 119/500"), Err(CodeParamsError::Syntax { line: 1 })));
+        assert!(matches!(CodeParams::parse("alpha = 119/500\nbeta = 241/2000\nr = 43/25"), Err(CodeParamsError::Missing("baselen"))));
+        assert!(matches!(CodeParams::parse("alpha = 119/500\nbeta = 1/5\nr = 43/25\nbaselen = 10"), Err(CodeParamsError::Invalid(_))));
+    }
+}
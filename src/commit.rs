@@ -1,378 +1,868 @@
-use std::collections::HashMap;
-use std::time::Instant;
-use std::sync::RwLock;
-use rand::Rng;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::sync::Arc;
 use ff::Field;
 use ff::PrimeField;
-use ndarray::Array;
-use ndarray::Axis;
-use ndarray::Dim;
+use ndarray::ArrayD;
+use ndarray::ArrayViewD;
 use ndarray::parallel::prelude::*;
 use num_traits::Num;
 use sprs::MulAcc;
 use digest::Digest;
 use digest::Output;
 use sprs::CsMat;
+use rand::Rng;
+use crate::codespec::CodeParams;
 use crate::codespec::CodeSpecification;
-use crate::codegen::generate;
-use crate::encode::encode;
-use crate::helper::next_pow_2;
-use crate::helper::linear_combination_2_1;
-use crate::helper::linear_combination_3_2;
-use crate::helper::linear_combination_4_3;
+use crate::helper::encode_tensor;
+use crate::extension::ExtensionField;
+use crate::helper::combine_row;
+use crate::helper::inner_product;
+use crate::linear_code::CodeFamily;
+use crate::linear_code::ExpanderCode;
+use crate::linear_code::LinearCode;
+use crate::helper::linear_combination;
+use crate::helper::tensor_row;
 use crate::merkle::check_merkle_path;
-use crate::merkle::merkle_tree_commit_2d;
-use crate::merkle::merkle_tree_commit_3d;
-use crate::merkle::merkle_tree_commit_4d;
+use crate::merkle::hash_row;
+use crate::merkle::leaf_index;
+use crate::merkle::merkle_path;
+use crate::merkle::merkle_tree_commit;
+use crate::pcs::EvaluationCommitment;
+use crate::pcs::PolynomialCommitment;
+use crate::pcs::VerifyError;
+use crate::pcs::bench;
+use crate::transcript::Transcript;
 
-pub fn check_linear_combination_2_1<F>(
-    msg_len: usize, 
+// check that the r-combination of an opened row of M(k) matches the encoded row of M(k+1) at position i,
+// where msg_len and code_len are those of the axis M(k+1) is encoded along; the row of M(k) holds symbols of
+// `degree` coefficients and the row of M(k+1) is over E, encoded as a block of E::DEGREE codewords with
+// enc(block, width, scratch)
+pub fn check_linear_combination<F, E, Enc>(
+    msg_len: usize,
     code_len: usize,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    m_1d: &Array<F, Dim<[usize; 1]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
+    degree: usize,
+    row_hi: &[F],
+    row_lo: &[F],
+    r: &[E],
+    enc: &Enc,
+    i: usize,
 ) -> bool
 where
-    F: PrimeField + Num + MulAcc,
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    Enc: Fn(&mut [F], usize, &mut Vec<F>),
 {
-    assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-    assert_eq!(m_1d.shape(), &[msg_len]);
-    assert_eq!(r.len(), msg_len);
+    let d = E::DEGREE;
+    assert_eq!(row_hi.len(), r.len() * degree);
+    assert_eq!(row_lo.len(), msg_len * d);
 
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i1 in 0..msg_len {
-        msg.push(m_1d[[i1]]);
-    }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, precodes, postcodes);
-    let mut s = <F as Field>::zero();
-    for i2 in 0..msg_len {
-        s = s.add(r[i2].mul(m_2d[[i1, i2]]));
-    }
-    return s == msg[i1];
+    let mut msg = row_lo.to_vec();
+    msg.resize(code_len * d, <F as Field>::zero());
+    enc(&mut msg, d, &mut Vec::new());
+    return combine_row(row_hi, degree, r) == E::from_coeffs(&msg[(i * d)..((i + 1) * d)]);
 }
 
-pub fn check_linear_combination_3_2<F>(
-    msg_len: usize, 
+// check the padded linear combination between an opened row of M(k) and the rows of M(k+1), M(k+1)_pad at position i,
+// where msg_len and code_len are those of the axis M(k+1) is encoded along; the row of M(k) holds symbols of
+// `degree` coefficients and the rows of M(k+1), M(k+1)_pad are over E, see `check_linear_combination`
+pub fn check_linear_combination_simple_zk<F, E, Enc>(
+    msg_len: usize,
     code_len: usize,
-    m_3d: &Array<F, Dim<[usize; 3]>>,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
-    i2: usize,
+    degree: usize,
+    row_hi: &[F],
+    row_lo: &[F],
+    row_lo_pad: &[F],
+    r: &[E],
+    enc: &Enc,
+    i: usize,
 ) -> bool
 where
-    F: PrimeField + Num + MulAcc,
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    Enc: Fn(&mut [F], usize, &mut Vec<F>),
+{
+    let d = E::DEGREE;
+    assert_eq!(row_hi.len(), r.len() * degree);
+    assert_eq!(row_lo.len(), msg_len * d);
+    assert_eq!(row_lo_pad.len(), code_len * d);
+
+    let mut msg = Vec::<F>::with_capacity(code_len * d);
+    for i1 in 0..(msg_len * d) {
+        msg.push(row_lo[i1].sub(row_lo_pad[i1]));
+    }
+    msg.resize(code_len * d, <F as Field>::zero());
+    enc(&mut msg, d, &mut Vec::new());
+    let pad = E::from_coeffs(&row_lo_pad[(i * d)..((i + 1) * d)]);
+    return combine_row(row_hi, degree, r) == E::from_coeffs(&msg[(i * d)..((i + 1) * d)]).add(pad);
+}
+
+/// Public parameters of a tensor commitment
+pub struct TensorParams<F>
+where
+    F: PrimeField,
+{
+    /// tensor dimension t
+    pub dim: usize,
+    /// message length m_a of every axis a
+    pub msg_lens: Vec<usize>,
+    /// codeword length N_a of every axis a
+    pub code_lens: Vec<usize>,
+    /// seed the codes were generated from
+    pub seed: u64,
+    /// number of queried columns
+    pub test_no: usize,
+    /// code applied along every axis a, axes of equal message length share one code;
+    /// the last axis is never encoded, its code only fixes N(t-1)
+    pub codes: Vec<Arc<dyn LinearCode<F>>>,
+}
+
+impl<F> TensorParams<F>
+where
+    F: PrimeField,
 {
-    assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-    assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-    assert_eq!(r.len(), msg_len);
+    pub fn new<C>(dim: usize, msg_len: usize, seed: u64, test_no: usize) -> Self
+    where
+        F: Num + MulAcc,
+        C: CodeSpecification,
+    {
+        Self::with_code(&C::code_params(), dim, msg_len, seed, test_no)
+    }
+
+    /// parameters for a code specification chosen at runtime
+    pub fn with_code(code: &CodeParams, dim: usize, msg_len: usize, seed: u64, test_no: usize) -> Self
+    where
+        F: Num + MulAcc,
+    {
+        Self::with_shape(code, &vec![msg_len; dim], seed, test_no)
+    }
 
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i2 in 0..msg_len {
-        msg.push(m_2d[[i1, i2]]);
+    /// parameters for a m0 * ... * m(t-1) tensor, with a code generated for every distinct message length
+    pub fn with_shape(code: &CodeParams, msg_lens: &[usize], seed: u64, test_no: usize) -> Self
+    where
+        F: Num + MulAcc,
+    {
+        // the expansion only applies to the Reed-Solomon and RAA families
+        Self::with_families(code, msg_lens, &vec![CodeFamily::Expander; msg_lens.len()], 2, seed, test_no)
     }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, &precodes, &postcodes);
-    let mut s = <F as Field>::zero();
-    for i3 in 0..msg_len {
-        s = s.add(r[i3].mul(m_3d[[i1, i2, i3]]));
+
+    /// parameters for a m0 * ... * m(t-1) tensor with a code of families[a] along every axis a,
+    /// see `CodeFamily::code`; axes of equal message length and family share their code
+    pub fn with_families(code: &CodeParams, msg_lens: &[usize], families: &[CodeFamily], expansion: usize, seed: u64, test_no: usize) -> Self
+    where
+        F: Num + MulAcc,
+    {
+        let generate = |family: CodeFamily, msg_len: usize| Ok::<_, Infallible>(family.code(code, msg_len, expansion, seed));
+        match Self::with_family_codes(msg_lens, families, seed, test_no, generate) {
+            Ok(params) => params,
+            Err(never) => match never {},
+        }
+    }
+
+    /// `with_families` with every distinct code built by code_of(family, msg_len), e.g. to load the expander codes
+    /// from `codecache`
+    pub fn with_family_codes<E, G>(msg_lens: &[usize], families: &[CodeFamily], seed: u64, test_no: usize, mut code_of: G) -> Result<Self, E>
+    where
+        G: FnMut(CodeFamily, usize) -> Result<Arc<dyn LinearCode<F>>, E>,
+    {
+        assert_eq!(msg_lens.len(), families.len());
+        let axes = msg_lens.iter().copied().zip(families.iter().copied()).collect::<Vec<_>>();
+        let mut codes = Vec::<Arc<dyn LinearCode<F>>>::with_capacity(axes.len());
+        for (a, &(msg_len, family)) in axes.iter().enumerate() {
+            let code = match axes[..a].iter().position(|&prev| prev == (msg_len, family)) {
+                Some(prev) => codes[prev].clone(),
+                None => code_of(family, msg_len)?,
+            };
+            codes.push(code);
+        }
+        Ok(Self::from_axis_codes(seed, test_no, codes))
+    }
+
+    /// parameters for a code generated from `seed` elsewhere, e.g. loaded by `codecache::generate_cached`
+    pub fn from_codes(dim: usize, msg_len: usize, seed: u64, test_no: usize, precodes: Vec<CsMat<F>>, postcodes: Vec<CsMat<F>>) -> Self
+    where
+        F: Num + MulAcc,
+    {
+        assert_eq!(precodes[0].cols(), msg_len);
+        Self::from_linear_code(dim, seed, test_no, Arc::new(ExpanderCode::new(precodes, postcodes)))
+    }
+
+    /// parameters for any systematic linear code, e.g. to compare code families under the same commitment;
+    /// rows are read back from the first msg_len symbols of their codewords, see `LinearCode::is_systematic`
+    pub fn from_linear_code(dim: usize, seed: u64, test_no: usize, code: Arc<dyn LinearCode<F>>) -> Self {
+        Self::from_axis_codes(seed, test_no, vec![code; dim])
+    }
+
+    /// parameters with a separate systematic code for every axis, the tensor has shape
+    /// codes[0].message_len() * ... * codes[t-1].message_len(); panics on a code that is not systematic
+    pub fn from_axis_codes(seed: u64, test_no: usize, codes: Vec<Arc<dyn LinearCode<F>>>) -> Self {
+        let dim = codes.len();
+        assert!(dim >= 2);
+        assert!(codes.iter().all(|code| code.is_systematic()), "tensor codes have to be systematic, see LinearCode::is_systematic");
+        let msg_lens = codes.iter().map(|code| code.message_len()).collect();
+        let code_lens = codes.iter().map(|code| code.codeword_len()).collect();
+        TensorParams { dim, msg_lens, code_lens, seed, test_no, codes }
+    }
+
+    /// number of coefficients m0 * ... * m(t-1)
+    pub fn coef_no(&self) -> usize {
+        self.msg_lens.iter().product()
+    }
+
+    /// start a transcript for the protocol named `label`, bound to these parameters
+    pub fn transcript<D>(&self, label: &[u8]) -> Transcript<D>
+    where
+        D: Digest,
+    {
+        let mut transcript = Transcript::<D>::new(label);
+        transcript.absorb_usize(self.dim);
+        for (&msg_len, &code_len) in self.msg_lens.iter().zip(self.code_lens.iter()) {
+            transcript.absorb_usize(msg_len);
+            transcript.absorb_usize(code_len);
+        }
+        transcript.absorb_bytes(&self.seed.to_le_bytes());
+        transcript.absorb_usize(self.test_no);
+        transcript
     }
-    return s == msg[i2];
 }
 
-pub fn check_linear_combination_4_3<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_4d: &Array<F, Dim<[usize; 4]>>,
-    m_3d: &Array<F, Dim<[usize; 3]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
-    i2: usize,
-    i3: usize,
-) -> bool
+/// Prover state of a tensor commitment
+pub struct TensorState<F, D>
 where
-    F: PrimeField + Num + MulAcc,
+    D: Digest,
+{
+    /// M0: N0 * ... * N(t-2) * m(t-1), encoded along every axis but the last
+    pub m0: ArrayD<F>,
+    pub hashes_m0: Vec<Output<D>>,
+}
+
+/// Opened rows for one queried position (i1, ..., i(t-1))
+pub struct TensorQuery<F, D>
+where
+    D: Digest,
+{
+    /// rows of M0 at (i1, ..., i(t-1)), of M1 at (i1, ..., i(t-2)), ..., of M(t-2) at (i1)
+    pub rows: Vec<Vec<F>>,
+    /// authentication paths of the rows
+    pub paths: Vec<Vec<Output<D>>>,
+}
+
+/// Opening of a tensor commitment: everything the prover sends after the commitment
+pub struct TensorProof<F, D>
+where
+    D: Digest,
+{
+    /// merkle roots of M1, ..., M(t-2)
+    pub roots: Vec<Output<D>>,
+    /// M(t-1): m0 symbols over the extension the challenges are drawn from, E::DEGREE coefficients each
+    pub m_last: Vec<F>,
+    /// opened rows at the positions squeezed from the transcript
+    pub queries: Vec<TensorQuery<F, D>>,
+}
+
+// squeeze test_no positions (i1, ..., i(t-1)) from the transcript, ia below code_lens[a]
+pub fn squeeze_positions<D>(transcript: &mut Transcript<D>, code_lens: &[usize], test_no: usize) -> Vec<Vec<usize>>
+where
+    D: Digest,
+{
+    let mut rng = transcript.squeeze_rng();
+    (0..test_no)
+        .map(|_| code_lens.iter().map(|&code_len| rng.gen_range(0..code_len)).collect())
+        .collect()
+}
+
+// fold M0 along its last axis with the vector over E returned by next(transcript, k) at every level k:
+// M(k+1) = sum_l next(k)[l] * M(k)[..., l], committing to M1, ..., M(t-2) and absorbing M(t-1);
+// the rows of M1, ..., M(t-1) hold the E::DEGREE coefficients of every symbol, see `linear_combination`
+fn fold_tensor<F, E, D, R>(
+    params: &TensorParams<F>,
+    m0: &ArrayD<F>,
+    transcript: &mut Transcript<D>,
+    next: R,
+) -> (Vec<ArrayD<F>>, Vec<Vec<Output<D>>>)
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    R: FnMut(&mut Transcript<D>, usize) -> Vec<E>,
 {
-    assert_eq!(m_4d.shape(), &[code_len, code_len, code_len, msg_len]);
-    assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-    assert_eq!(r.len(), msg_len);
+    fold_tensors(params, &[(m0, &params.msg_lens)], transcript, next).pop().unwrap()
+}
+
+// fold_tensor for several tensors (M0, lens) with the same vectors, keeping the first lens[a] entries of axis a of
+// every folded tensor; the tensors of a level are committed or absorbed in turn before the next vector is drawn
+pub fn fold_tensors<F, E, D, R>(
+    params: &TensorParams<F>,
+    tensors: &[(&ArrayD<F>, &[usize])],
+    transcript: &mut Transcript<D>,
+    mut next: R,
+) -> Vec<(Vec<ArrayD<F>>, Vec<Vec<Output<D>>>)>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    R: FnMut(&mut Transcript<D>, usize) -> Vec<E>,
+{
+    let dim = params.dim;
+
+    // M1: N0 * ... * N(t-3) * lens[t-2], ..., M(t-1): lens[0]
+    let mut folds = tensors
+        .iter()
+        .map(|_| (Vec::<ArrayD<F>>::with_capacity(dim - 1), Vec::<Vec<Output<D>>>::with_capacity(dim - 2)))
+        .collect::<Vec<_>>();
+    for k in 0..(dim - 1) {
+        let a = dim - 2 - k;
+        let vk = next(transcript, k);
+        for ((m0, lens), (m, hashes)) in tensors.iter().zip(folds.iter_mut()) {
+            let mk = {
+                let (prev, degree) = if k == 0 { (m0.view(), 1) } else { (m[k - 1].view(), E::DEGREE) };
+                linear_combination::<F, E>(&prev, degree, &vk, lens[a])
+            };
+            if k < dim - 2 {
+                let hashes_mk = merkle_tree_commit::<F, D>(lens[a] * E::DEGREE, &mk.view());
+                transcript.absorb_digest(&hashes_mk[0]);
+                hashes.push(hashes_mk);
+            }else{
+                transcript.absorb_fields(mk.as_slice().unwrap());
+            }
+            m.push(mk);
+        }
+    }
+    folds
+}
+
+// replay fold_tensor on the verifier side and return the vectors of every level
+fn replay_fold<F, E, D, R>(
+    params: &TensorParams<F>,
+    roots: &[Output<D>],
+    m_last: &[F],
+    transcript: &mut Transcript<D>,
+    next: R,
+) -> Vec<Vec<E>>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    D: Digest,
+    R: FnMut(&mut Transcript<D>, usize) -> Vec<E>,
+{
+    replay_folds(params, &[(roots, m_last)], transcript, next)
+}
 
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i3 in 0..msg_len {
-        msg.push(m_3d[[i1, i2, i3]]);
+// replay fold_tensors from the (roots, M(t-1)) of every folded tensor
+pub fn replay_folds<F, E, D, R>(
+    params: &TensorParams<F>,
+    tensors: &[(&[Output<D>], &[F])],
+    transcript: &mut Transcript<D>,
+    mut next: R,
+) -> Vec<Vec<E>>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    D: Digest,
+    R: FnMut(&mut Transcript<D>, usize) -> Vec<E>,
+{
+    let dim = params.dim;
+    let mut v = Vec::<Vec<E>>::with_capacity(dim - 1);
+    for k in 0..(dim - 1) {
+        v.push(next(transcript, k));
+        for (roots, m_last) in tensors.iter() {
+            if k < dim - 2 {
+                transcript.absorb_digest(&roots[k]);
+            }else{
+                transcript.absorb_fields(m_last);
+            }
+        }
     }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, &precodes, &postcodes);
-    let mut s = <F as Field>::zero();
-    for i4 in 0..msg_len {
-        s = s.add(r[i4].mul(m_4d[[i1, i2, i3, i4]]));
+    return v;
+}
+
+// length of the rows of level k of a tensor folded by fold_tensors with lens: M0 has rows of the last axis,
+// M(k) rows of lens[t-1-k] symbols of `degree` coefficients
+fn row_len(msg_lens: &[usize], lens: &[usize], degree: usize, k: usize) -> usize {
+    if k == 0 {
+        msg_lens[msg_lens.len() - 1]
+    } else {
+        lens[lens.len() - 1 - k] * degree
     }
-    return s == msg[i3];
 }
 
-pub fn commit_2_dim<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
-    seed: u64,
-    test_no: usize,
-)
+// open the rows of the given tensors at (i1, ..., i(t-1)), (i1, ..., i(t-2)), ... starting at level `first`,
+// where the tensors were folded with lens and the rows of every level but M0 hold symbols of `degree` coefficients
+pub fn open_rows<F, D>(
+    params: &TensorParams<F>,
+    levels: &[(ArrayViewD<F>, &[Output<D>])],
+    lens: &[usize],
+    first: usize,
+    degree: usize,
+    ii: &[usize],
+) -> TensorQuery<F, D>
 where
-    F: PrimeField + Num + MulAcc,
-    C: CodeSpecification,
+    F: PrimeField,
     D: Digest,
 {
-    let mut rng = rand::thread_rng();
+    let dim = params.dim;
+    let mut rows = Vec::<Vec<F>>::with_capacity(levels.len());
+    let mut paths = Vec::<Vec<Output<D>>>::with_capacity(levels.len());
+    for (k, (mk, hashes_mk)) in levels.iter().enumerate() {
+        let ik = &ii[..(dim - 1 - first - k)];
+        let code_lens = &params.code_lens[..ik.len()];
+        rows.push(tensor_row(mk, ik, row_len(&params.msg_lens, lens, degree, first + k)));
+        paths.push(merkle_path::<D>(hashes_mk, code_lens.iter().product(), leaf_index(code_lens, ik)));
+    }
+    TensorQuery { rows, paths }
+}
 
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
+// check the opened rows of M0, ..., M(t-2) at (i1, ..., i(t-1)) against the roots, and every
+// combination with v(k) against the row of M(k+1) encoded with enc(axis, block, width, scratch);
+// with the opened (rows, paths, roots, M(t-1)) of a pad folded over full codewords, every row of M(k+1) is
+// checked against the padded combination, see `check_linear_combination_simple_zk`
+fn check_rows<F, E, D, Enc>(
+    params: &TensorParams<F>,
+    ii: &[usize],
+    rows: &[&[F]],
+    paths: &[&[Output<D>]],
+    roots: &[&Output<D>],
+    m_last: &[F],
+    pad: Option<(&[&[F]], &[&[Output<D>]], &[&Output<D>], &[F])>,
+    v: &[Vec<E>],
+    enc: &Enc,
+    index: usize,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    Enc: Fn(usize, &mut [F], usize, &mut Vec<F>),
+{
+    let (dim, msg_lens) = (params.dim, &params.msg_lens);
+    let mismatched = |rows: &[&[F]], paths: &[&[Output<D>]], lens: &[usize]| {
+        rows.len() != dim - 1 || paths.len() != dim - 1 || rows.iter().enumerate().any(|(k, row)| row.len() != row_len(msg_lens, lens, E::DEGREE, k))
+    };
+    if mismatched(rows, paths, msg_lens) || pad.is_some_and(|(pad_rows, pad_paths, _, _)| mismatched(pad_rows, pad_paths, &params.code_lens)) {
+        return Err(VerifyError::ShapeMismatch);
+    }
+    for k in 0..(dim - 1) {
+        let ik = &ii[..(dim - 1 - k)];
+        let leaf = leaf_index(&params.code_lens[..ik.len()], ik);
+        let row_lo = if k + 2 < dim { rows[k + 1] } else { m_last };
+        // verify the merkle paths for M(k) and its pad
+        if !check_merkle_path::<D>(hash_row::<F, D>(rows[k]), leaf, paths[k], roots[k])
+            || pad.is_some_and(|(pad_rows, pad_paths, pad_roots, _)| !check_merkle_path::<D>(hash_row::<F, D>(pad_rows[k]), leaf, pad_paths[k], pad_roots[k]))
+        {
+            return Err(VerifyError::MerklePathMismatch { index });
+        }
+        // M(k+1) is encoded along axis a
+        let a = dim - 2 - k;
+        let degree = if k == 0 { 1 } else { E::DEGREE };
+        let enc_a = |block: &mut [F], width: usize, scratch: &mut Vec<F>| enc(a, block, width, scratch);
+        let matches = match pad {
+            None => check_linear_combination::<F, E, _>(msg_lens[a], params.code_lens[a], degree, rows[k], row_lo, &v[k], &enc_a, ii[a]),
+            Some((pad_rows, _, _, m_last_pad)) => {
+                let row_lo_pad = if k + 2 < dim { pad_rows[k + 1] } else { m_last_pad };
+                check_linear_combination_simple_zk::<F, E, _>(msg_lens[a], params.code_lens[a], degree, rows[k], row_lo, row_lo_pad, &v[k], &enc_a, ii[a])
+            }
+        };
+        if !matches {
+            return Err(VerifyError::LinearCombinationMismatch { axis: dim - 1 - k, index });
+        }
+    }
+    Ok(())
+}
 
-    // M0: N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, msg_len));
-    // generate random coefficient: m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
+// fold M0 with linear combinations r1, ..., r(t-1) over E, commit to the intermediate tensors and open test_no
+// positions; the transcript must already contain the root of M0
+pub fn open_tensor<F, E, D>(
+    params: &TensorParams<F>,
+    m0: &ArrayD<F>,
+    hashes_m0: &[Output<D>],
+    transcript: &mut Transcript<D>,
+) -> TensorProof<F, D>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    open_tensors::<F, E, D>(params, &[(m0, hashes_m0, &params.msg_lens)], transcript).pop().unwrap()
+}
 
-    let start_time = Instant::now();
+// open_tensor for M0 hiding the encoded coefficients behind the random M0_pad, which is folded over full codewords;
+// the transcript must already contain the roots of M0 and M0_pad
+pub fn open_padded_tensor<F, E, D>(
+    params: &TensorParams<F>,
+    m0: &ArrayD<F>,
+    hashes_m0: &[Output<D>],
+    m0_pad: &ArrayD<F>,
+    hashes_m0_pad: &[Output<D>],
+    transcript: &mut Transcript<D>,
+) -> (TensorProof<F, D>, TensorProof<F, D>)
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    let mut proofs = open_tensors::<F, E, D>(params, &[(m0, hashes_m0, &params.msg_lens), (m0_pad, hashes_m0_pad, &params.code_lens)], transcript);
+    let pad = proofs.pop().unwrap();
+    (proofs.pop().unwrap(), pad)
+}
+
+// fold every (M0, hashes, lens) with the same linear combinations and open all of them at the same positions
+fn open_tensors<F, E, D>(
+    params: &TensorParams<F>,
+    tensors: &[(&ArrayD<F>, &[Output<D>], &[usize])],
+    transcript: &mut Transcript<D>,
+) -> Vec<TensorProof<F, D>>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    let (dim, test_no) = (params.dim, params.test_no);
+
+    // M1, ..., M(t-1), each committed before the next r is squeezed
+    let folds = fold_tensors(
+        params,
+        &tensors.iter().map(|&(m0, _, lens)| (m0, lens)).collect::<Vec<_>>(),
+        transcript,
+        |transcript, k| transcript.squeeze_extension::<F, E>(params.msg_lens[dim - 1 - k]),
+    );
+
+    // sample idx
+    let idx = squeeze_positions(transcript, &params.code_lens[..(dim - 1)], test_no);
+
+    // open the rows of M0, ..., M(t-2) at every queried position
+    tensors
+        .iter()
+        .zip(folds)
+        .map(|(&(m0, hashes_m0, lens), (mut m, hashes))| {
+            let mut levels = vec![(m0.view(), hashes_m0)];
+            levels.extend(m.iter().zip(hashes.iter()).map(|(mk, hk)| (mk.view(), hk.as_slice())));
+            let queries = idx
+                .par_iter()
+                .map(|ii| open_rows(params, &levels, lens, 0, E::DEGREE, ii))
+                .collect();
+            drop(levels);
 
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
+            let roots = hashes.iter().map(|h| h[0].clone()).collect();
+            let m_last = m.pop().unwrap().into_raw_vec();
+            TensorProof { roots, m_last, queries }
+        })
+        .collect()
+}
 
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(1))
-        .into_par_iter()
+// check a proof for M0 committed under root, using enc(axis, block, width, scratch) to encode blocks of rows as
+// in `LinearCode::encode_batch`; the transcript must already contain the root of M0
+pub fn verify_tensor<F, E, D, Enc>(
+    params: &TensorParams<F>,
+    root: &Output<D>,
+    proof: &TensorProof<F, D>,
+    enc: &Enc,
+    transcript: &mut Transcript<D>,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    Enc: Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync,
+{
+    verify_tensors::<F, E, D, Enc>(params, root, proof, None, enc, transcript)
+}
+
+// check the proofs of open_padded_tensor for M0, M0_pad committed under root, pad_root;
+// the transcript must already contain both roots
+pub fn verify_padded_tensor<F, E, D, Enc>(
+    params: &TensorParams<F>,
+    root: &Output<D>,
+    proof: &TensorProof<F, D>,
+    pad_root: &Output<D>,
+    pad: &TensorProof<F, D>,
+    enc: &Enc,
+    transcript: &mut Transcript<D>,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    Enc: Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync,
+{
+    verify_tensors::<F, E, D, Enc>(params, root, proof, Some((pad_root, pad)), enc, transcript)
+}
+
+fn verify_tensors<F, E, D, Enc>(
+    params: &TensorParams<F>,
+    root: &Output<D>,
+    proof: &TensorProof<F, D>,
+    pad: Option<(&Output<D>, &TensorProof<F, D>)>,
+    enc: &Enc,
+    transcript: &mut Transcript<D>,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    Enc: Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync,
+{
+    let (dim, test_no) = (params.dim, params.test_no);
+    let mismatched = |proof: &TensorProof<F, D>, lens: &[usize]| {
+        proof.roots.len() != dim - 2 || proof.m_last.len() != lens[0] * E::DEGREE || proof.queries.len() != test_no
+    };
+    if mismatched(proof, &params.msg_lens) || pad.is_some_and(|(_, pad)| mismatched(pad, &params.code_lens)) {
+        return Err(VerifyError::ShapeMismatch);
+    }
+
+    // replay the transcript to recover r1, ..., r(t-1) and idx
+    let mut folded = vec![(proof.roots.as_slice(), proof.m_last.as_slice())];
+    folded.extend(pad.map(|(_, pad)| (pad.roots.as_slice(), pad.m_last.as_slice())));
+    let r = replay_folds(params, &folded, transcript, |transcript, k| transcript.squeeze_extension::<F, E>(params.msg_lens[dim - 1 - k]));
+    let idx = squeeze_positions(transcript, &params.code_lens[..(dim - 1)], test_no);
+
+    // verifier has access to r, M(t-1) and the roots of M0, ..., M(t-2), and the same for the pad
+    let mut roots = vec![root];
+    roots.extend(proof.roots.iter());
+    let pad_roots = pad.map(|(pad_root, pad)| std::iter::once(pad_root).chain(pad.roots.iter()).collect::<Vec<_>>());
+
+    idx
+        .par_iter()
+        .zip(proof.queries.par_iter())
         .enumerate()
-        .for_each(|(_i2, mut x)| {
-            let mut msg = x.to_vec();
-            msg.resize(code_len, <F as Field>::zero());
-            encode(&mut msg, &precodes, &postcodes);
-            for i1 in 0..code_len {
-                x[i1] = msg[i1];
+        .try_for_each(|(index, (ii, query))| {
+            let rows = query.rows.iter().map(|row| row.as_slice()).collect::<Vec<_>>();
+            let paths = query.paths.iter().map(|path| path.as_slice()).collect::<Vec<_>>();
+            match (pad, &pad_roots) {
+                (Some((_, pad)), Some(pad_roots)) => {
+                    let pad_query = &pad.queries[index];
+                    let pad_rows = pad_query.rows.iter().map(|row| row.as_slice()).collect::<Vec<_>>();
+                    let pad_paths = pad_query.paths.iter().map(|path| path.as_slice()).collect::<Vec<_>>();
+                    let pad = Some((pad_rows.as_slice(), pad_paths.as_slice(), pad_roots.as_slice(), pad.m_last.as_slice()));
+                    check_rows::<F, E, D, Enc>(params, ii, &rows, &paths, &roots, &proof.m_last, pad, &r, enc, index)
+                }
+                _ => check_rows::<F, E, D, Enc>(params, ii, &rows, &paths, &roots, &proof.m_last, None, &r, enc, index),
             }
-        });
+        })
+}
+
+/// Evaluation proof for p(z) = y, where z is given by its tensor query vectors q0, ..., q(t-1)
+pub struct TensorEvalProof<F, D>
+where
+    D: Digest,
+{
+    /// proximity test of the committed tensor
+    pub proximity: TensorProof<F, D>,
+    /// merkle roots of E1, ..., E(t-2), M0 folded with q(t-1), ..., q1
+    pub roots: Vec<Output<D>>,
+    /// E(t-1): m0
+    pub e_last: Vec<F>,
+    /// rows of E1, ..., E(t-2) at the positions of the proximity test
+    pub queries: Vec<TensorQuery<F, D>>,
+}
 
-    // M1: m
-    let m1 = linear_combination_2_1::<F>(msg_len, code_len, &m0, &r1, msg_len);
+// the transcript of an evaluation proof starts with the root of M0 and the query vectors
+fn eval_transcript<F, D>(
+    params: &TensorParams<F>,
+    root: &Output<D>,
+    q: &[Vec<F>],
+) -> Transcript<D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    let mut transcript = params.transcript::<D>(b"tensor eval");
+    transcript.absorb_digest(root);
+    for qk in q.iter() {
+        transcript.absorb_fields(qk);
+    }
+    transcript
+}
 
-    // commit to m0
-    let hashes_m0 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m0);
+// prove y = <q0 * ... * q(t-1), coefs>: besides the proximity test with challenges over E, M0 is folded with
+// q(t-1), ..., q1 into E1, ..., E(t-1) over F and both folds are checked at the same positions
+pub fn open_tensor_eval<F, E, D>(
+    params: &TensorParams<F>,
+    m0: &ArrayD<F>,
+    hashes_m0: &[Output<D>],
+    q: &[Vec<F>],
+) -> (F, TensorEvalProof<F, D>)
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    let (dim, test_no) = (params.dim, params.test_no);
+    assert_eq!(q.len(), dim);
+    assert!(q.iter().zip(params.msg_lens.iter()).all(|(qk, &msg_len)| qk.len() == msg_len));
+    let mut transcript = eval_transcript::<F, D>(params, &hashes_m0[0], q);
 
-    let committed_time = Instant::now();
+    // E1, ..., E(t-1), committed before r1 is squeezed
+    let (mut e, e_hashes) = fold_tensor(params, m0, &mut transcript, |_, k| q[dim - 1 - k].clone());
+    // M1, ..., M(t-1)
+    let (mut m, hashes) = fold_tensor(params, m0, &mut transcript, |transcript, k| transcript.squeeze_extension::<F, E>(params.msg_lens[dim - 1 - k]));
 
-    // verifier has access to r1, m1, m0.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
     // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-
-        assert!(
-            check_linear_combination_2_1::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &r1, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-
-    });
-
-    let m0_map_rwlock = RwLock::new(m0_map); 
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m0[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-        // println!("test {} passed", i+1);
-    });
-
-    let verified_time = Instant::now();
-
-    println!("t:2 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
+    let idx = squeeze_positions(&mut transcript, &params.code_lens[..(dim - 1)], test_no);
+
+    // open the rows of M0, ..., M(t-2) and of E1, ..., E(t-2) at every queried position
+    let mut levels = vec![(m0.view(), hashes_m0)];
+    levels.extend(m.iter().zip(hashes.iter()).map(|(mk, hk)| (mk.view(), hk.as_slice())));
+    let e_levels = e.iter().zip(e_hashes.iter()).map(|(ek, hk)| (ek.view(), hk.as_slice())).collect::<Vec<_>>();
+    let (queries, e_queries) = idx
+        .par_iter()
+        .map(|ii| (open_rows(params, &levels, &params.msg_lens, 0, E::DEGREE, ii), open_rows(params, &e_levels, &params.msg_lens, 1, 1, ii)))
+        .unzip();
+    drop(levels);
+    drop(e_levels);
+
+    let m_last = m.pop().unwrap().into_raw_vec();
+    let e_last = e.pop().unwrap().into_raw_vec();
+    let y = inner_product(&q[0], &e_last);
+    let proximity = TensorProof { roots: hashes.iter().map(|h| h[0].clone()).collect(), m_last, queries };
+    let roots = e_hashes.iter().map(|h| h[0].clone()).collect();
+    (y, TensorEvalProof { proximity, roots, e_last, queries: e_queries })
 }
 
-pub fn commit_3_dim<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
-    seed: u64,
-    test_no: usize,
-)
+// check an evaluation proof for M0 committed under root, using enc(axis, block, width, scratch) to encode rows
+pub fn verify_tensor_eval<F, E, D, Enc>(
+    params: &TensorParams<F>,
+    root: &Output<D>,
+    q: &[Vec<F>],
+    y: &F,
+    proof: &TensorEvalProof<F, D>,
+    enc: &Enc,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num,
+    E: ExtensionField<F>,
+    D: Digest,
+    Enc: Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync,
+{
+    let (dim, test_no) = (params.dim, params.test_no);
+    let prox = &proof.proximity;
+    if q.len() != dim
+        || q.iter().zip(params.msg_lens.iter()).any(|(qk, &msg_len)| qk.len() != msg_len)
+        || prox.roots.len() != dim - 2
+        || prox.m_last.len() != params.msg_lens[0] * E::DEGREE
+        || prox.queries.len() != test_no
+        || proof.roots.len() != dim - 2
+        || proof.e_last.len() != params.msg_lens[0]
+        || proof.queries.len() != test_no
+    {
+        return Err(VerifyError::ShapeMismatch);
+    }
+
+    // y = <q0, E(t-1)>
+    if inner_product(&q[0], &proof.e_last) != *y {
+        return Err(VerifyError::EvaluationMismatch);
+    }
+
+    // replay the transcript to recover r1, ..., r(t-1) and idx
+    let mut transcript = eval_transcript::<F, D>(params, root, q);
+    let v = replay_fold(params, &proof.roots, &proof.e_last, &mut transcript, |_, k| q[dim - 1 - k].clone());
+    let r = replay_fold(params, &prox.roots, &prox.m_last, &mut transcript, |transcript, k| transcript.squeeze_extension::<F, E>(params.msg_lens[dim - 1 - k]));
+    let idx = squeeze_positions(&mut transcript, &params.code_lens[..(dim - 1)], test_no);
+
+    // verifier has access to r, q, M(t-1), E(t-1) and the roots of M0, ..., M(t-2) and E1, ..., E(t-2)
+    let mut roots = vec![root];
+    roots.extend(prox.roots.iter());
+    let mut e_roots = vec![root];
+    e_roots.extend(proof.roots.iter());
+
+    idx
+        .par_iter()
+        .zip(prox.queries.par_iter().zip(proof.queries.par_iter()))
+        .enumerate()
+        .try_for_each(|(index, (ii, (query, e_query)))| {
+            if query.rows.is_empty() || query.paths.is_empty() {
+                return Err(VerifyError::ShapeMismatch);
+            }
+            let rows = query.rows.iter().map(|row| row.as_slice()).collect::<Vec<_>>();
+            let paths = query.paths.iter().map(|path| path.as_slice()).collect::<Vec<_>>();
+            // the fold with q starts from the same opened row of M0
+            let mut e_rows = vec![rows[0]];
+            e_rows.extend(e_query.rows.iter().map(|row| row.as_slice()));
+            let mut e_paths = vec![paths[0]];
+            e_paths.extend(e_query.paths.iter().map(|path| path.as_slice()));
+            check_rows::<F, E, D, Enc>(params, ii, &rows, &paths, &roots, &prox.m_last, None, &r, enc, index)?;
+            check_rows::<F, F, D, Enc>(params, ii, &e_rows, &e_paths, &e_roots, &proof.e_last, None, &v, enc, index)
+        })
+}
+
+/// Tensor commitment over a Brakedown code, with the random linear combinations drawn from the extension E of F
+pub struct TensorCommitment<F, C, D, E = F> {
+    _p: PhantomData<(F, C, D, E)>,
+}
+
+impl<F, C, D, E> PolynomialCommitment<F> for TensorCommitment<F, C, D, E>
 where
     F: PrimeField + Num + MulAcc,
     C: CodeSpecification,
     D: Digest,
+    E: ExtensionField<F>,
 {
-    let mut rng = rand::thread_rng();
-
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-    // M0: N * N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, code_len, msg_len));
-    // generate random coefficient: m * m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-
-    let start_time = Instant::now();
-
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-    let mut r2 = Vec::<F>::new();
-    r2.resize_with(msg_len, || F::random(&mut rng));
-
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i3, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i2, mut x)| {
-                    let mut msg = x.to_vec();
-                    msg.resize(code_len, <F as Field>::zero());
-                    encode(&mut msg, &precodes, &postcodes);
-                    for i1 in 0..code_len {
-                        x[i1] = msg[i1];
-                    }
-                });
-        });
-    // encode for axis 1
-    m0
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i3, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(0))
-                .enumerate()
-                .for_each(|(_i1, mut x)| {
-                    let mut msg = x.to_vec();
-                    msg.resize(code_len, <F as Field>::zero());
-                    encode(&mut msg, &precodes, &postcodes);
-                    for i2 in msg_len..code_len {
-                        x[i2] = msg[i2];
-                    }
-                });
-        });
-
-    // M1: N * m
-    let m1 = linear_combination_3_2::<F>(msg_len, code_len, &m0, &r1, msg_len);
-    // M2: m
-    let m2 = linear_combination_2_1::<F>(msg_len, code_len, &m1, &r2, msg_len);
-
-
-    // commit to m0
-    let hashes_m0 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m0);
-    // commit to m1
-    let hashes_m1 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m1);
-
-    let committed_time = Instant::now();
-
-    // verifier has access to r1, r2, m2, m0.root, m1.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    let mut m1_map = HashMap::<usize, Output<D>>::new();
-    m1_map.insert(0, hashes_m1[0].clone());
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_2 = Vec::<usize>::new();
-    idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-        assert!(
-            check_linear_combination_3_2::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &r1, 
-                &precodes, &postcodes, 
-                i1, i2
-            )
-        );
-        assert!(
-            check_linear_combination_2_1::<F>(
-                msg_len, code_len, 
-                &m1, &m2, &r2, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-    });
-    
-    let m0_map_rwlock = RwLock::new(m0_map);
-    let m1_map_rwlock = RwLock::new(m1_map);
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m0[[i1, i2, i3]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        let item_no = code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-    
-        // verify the merkle path for m1
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m1[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-        // println!("test {} passed", i+1);
-    });
-
-    let verified_time = Instant::now();
-
-    println!("t:3 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
+    type Params = TensorParams<F>;
+    type Commitment = Output<D>;
+    type ProverState = TensorState<F, D>;
+    type Proof = TensorProof<F, D>;
+
+    fn setup(dim: usize, msg_len: usize, seed: u64, test_no: usize) -> TensorParams<F> {
+        TensorParams::new::<C>(dim, msg_len, seed, test_no)
+    }
+
+    fn setup_with_shape(code: &CodeParams, msg_lens: &[usize], seed: u64, test_no: usize) -> TensorParams<F> {
+        TensorParams::with_shape(code, msg_lens, seed, test_no)
+    }
+
+    fn shape(params: &TensorParams<F>) -> Vec<usize> {
+        params.msg_lens.clone()
+    }
+
+    fn commit(params: &TensorParams<F>, coefs: &ArrayD<F>) -> (Output<D>, TensorState<F, D>) {
+        assert_eq!(coefs.ndim(), params.dim);
+
+        let enc = |a: usize, block: &mut [F], width: usize, scratch: &mut Vec<F>| params.codes[a].encode_batch(block, width, scratch);
+        let m0 = encode_tensor(&params.msg_lens, &params.code_lens, coefs, &enc);
+
+        // commit to m0
+        let hashes_m0 = merkle_tree_commit::<F, D>(params.msg_lens[params.dim - 1], &m0.view());
+        (hashes_m0[0].clone(), TensorState { m0, hashes_m0 })
+    }
+
+    fn open(params: &TensorParams<F>, state: &TensorState<F, D>) -> TensorProof<F, D> {
+        let mut transcript = params.transcript::<D>(b"tensor");
+        transcript.absorb_digest(&state.hashes_m0[0]);
+        open_tensor::<F, E, D>(params, &state.m0, &state.hashes_m0, &mut transcript)
+    }
+
+    fn verify(params: &TensorParams<F>, commitment: &Output<D>, proof: &TensorProof<F, D>) -> Result<(), VerifyError> {
+        let enc = |a: usize, block: &mut [F], width: usize, scratch: &mut Vec<F>| params.codes[a].encode_batch(block, width, scratch);
+        let mut transcript = params.transcript::<D>(b"tensor");
+        transcript.absorb_digest(commitment);
+        verify_tensor::<F, E, D, _>(params, commitment, proof, &enc, &mut transcript)
+    }
 }
 
-pub fn commit_4_dim<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
+impl<F, C, D, E> EvaluationCommitment<F> for TensorCommitment<F, C, D, E>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+    D: Digest,
+    E: ExtensionField<F>,
+{
+    type EvalProof = TensorEvalProof<F, D>;
+
+    fn open_eval(params: &TensorParams<F>, state: &TensorState<F, D>, q: &[Vec<F>]) -> (F, TensorEvalProof<F, D>) {
+        open_tensor_eval::<F, E, D>(params, &state.m0, &state.hashes_m0, q)
+    }
+
+    fn verify_eval(params: &TensorParams<F>, commitment: &Output<D>, q: &[Vec<F>], y: &F, proof: &TensorEvalProof<F, D>) -> Result<(), VerifyError> {
+        let enc = |a: usize, block: &mut [F], width: usize, scratch: &mut Vec<F>| params.codes[a].encode_batch(block, width, scratch);
+        verify_tensor_eval::<F, E, D, _>(params, commitment, q, y, proof, &enc)
+    }
+}
+
+/// commit to random coefficients of a t-dimensional tensor, open, verify and print the timings
+pub fn commit_t_dim<F, C, D>(
+    dim: usize,
+    coef_no: usize,
+    msg_len: usize,
+    code_len: usize,
     seed: u64,
     test_no: usize,
 )
@@ -381,211 +871,199 @@ where
     C: CodeSpecification,
     D: Digest,
 {
-    let mut rng = rand::thread_rng();
-
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-    // M0: N * N * N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, code_len, code_len, msg_len));
-    // generate random coefficient: m * m * m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-
-    let start_time = Instant::now();
-
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-    let mut r2 = Vec::<F>::new();
-    r2.resize_with(msg_len, || F::random(&mut rng));
-    let mut r3 = Vec::<F>::new();
-    r3.resize_with(msg_len, || F::random(&mut rng));
-
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(2))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i3, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(1))
-                        .enumerate()
-                        .filter(|(i, _)| i < &(msg_len))
-                        .for_each(|(_i2, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i1 in 0..code_len {
-                                x[i1] = msg[i1];
-                            }
-                        });
-                });
-        });
-    // encode for axis 1
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(2))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i3, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(0))
-                        .enumerate()
-                        .for_each(|(_i1, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i2 in msg_len..code_len {
-                                x[i2] = msg[i2];
-                            }
-                        });
-                });
-        });
-    // encode for axis 2
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .for_each(|(_i2, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(0))
-                        .enumerate()
-                        .for_each(|(_i1, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i3 in msg_len..code_len {
-                                x[i3] = msg[i3];
-                            }
-                        });
-                });
-        });
-
-    // M1: N * N * m
-    let m1 = linear_combination_4_3::<F>(msg_len, code_len, &m0, &r1, msg_len);
-    // M2: N * m
-    let m2 = linear_combination_3_2::<F>(msg_len, code_len, &m1, &r2, msg_len);
-    // M3: m
-    let m3 = linear_combination_2_1::<F>(msg_len, code_len, &m2, &r3, msg_len);
-
-
-    // commit to m0
-    let hashes_m0 = merkle_tree_commit_4d::<F, D>(msg_len, code_len, &m0);
-    // commit to m1
-    let hashes_m1 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m1);
-    // commit to m2
-    let hashes_m2 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m2);
-
-    let committed_time = Instant::now();
-
-    // verifier has access to r1, r2, r3, m3, m0.root, m1.root, m2.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    let mut m1_map = HashMap::<usize, Output<D>>::new();
-    m1_map.insert(0, hashes_m1[0].clone());
-    let mut m2_map = HashMap::<usize, Output<D>>::new();
-    m2_map.insert(0, hashes_m2[0].clone());
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_2 = Vec::<usize>::new();
-    idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_3 = Vec::<usize>::new();
-    idx_3.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-        let i3 = idx_3[i];
-
-        assert!(
-            check_linear_combination_4_3::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &r1, 
-                &precodes, &postcodes, 
-                i1, i2, i3
-            )
-        );
-        assert!(
-            check_linear_combination_3_2::<F>(
-                msg_len, code_len, 
-                &m1, &m2, &r2, 
-                &precodes, &postcodes, 
-                i1, i2
-            )
-        );
-        assert!(
-            check_linear_combination_2_1::<F>(
-                msg_len, code_len, 
-                &m2, &m3, &r3, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-    });
-    
-    let m0_map_rwlock = RwLock::new(m0_map);
-    let m1_map_rwlock = RwLock::new(m1_map);
-    let m2_map_rwlock = RwLock::new(m2_map);
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-        let i3 = idx_3[i];
-
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i4 in 0..msg_len {
-            digest.update(m0[[i1, i2, i3, i4]].to_repr());
+    let params = TensorCommitment::<F, C, D>::setup(dim, msg_len, seed, test_no);
+    assert_eq!(params.code_lens, vec![code_len; dim]);
+    let header = format!("t:{} coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", dim, coef_no, msg_len, code_len, test_no);
+    bench::<F, TensorCommitment<F, C, D>>(&params, coef_no, &header);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use crate::codespec::Code6;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext2;
+    use crate::fieldspec::ft64::Ft64;
+    use crate::fieldspec::ft64::Ft64Ext4;
+    use crate::linear_code::ReedSolomonCode;
+    use crate::linear_code::Systematic;
+    use crate::pcs::multilinear_query;
+    use crate::pcs::random_coefs;
+    use crate::pcs::univariate_query;
+    use crate::simple_zk::SimpleZkTensorCommitment;
+
+    #[test]
+    #[should_panic(expected = "systematic")]
+    fn rejects_non_systematic_codes() {
+        let code: Arc<dyn LinearCode<Ft32>> = Arc::new(ReedSolomonCode::new(16, 32));
+        TensorParams::from_linear_code(2, 0, 4, code);
+    }
+
+    // the message in front of a square code has distance 2 however the code is chosen
+    #[test]
+    #[should_panic(expected = "systematic")]
+    fn rejects_weak_systematic_codes() {
+        let code: Arc<dyn LinearCode<Ft32>> = Arc::new(Systematic::new(ReedSolomonCode::new(16, 16)));
+        TensorParams::from_linear_code(2, 0, 4, code);
+    }
+
+    // a m0 * ... * m(t-1) tensor with a code per distinct message length, padded at the end
+    fn rectangular<P>(msg_lens: &[usize])
+    where
+        P: PolynomialCommitment<Ft32, Params = TensorParams<Ft32>>,
+    {
+        let params = P::setup_with_shape(&Code6::code_params(), msg_lens, 0, 4);
+        assert_eq!(P::shape(&params), msg_lens);
+        for (a, code) in params.codes.iter().enumerate() {
+            assert_eq!(code.message_len(), msg_lens[a]);
+            assert_eq!(code.codeword_len(), params.code_lens[a]);
+            for b in 0..a {
+                assert_eq!(Arc::ptr_eq(code, &params.codes[b]), msg_lens[a] == msg_lens[b]);
+            }
         }
-        let cur_hash = digest.finalize();
-        let item_no = code_len * code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + i3 * code_len * code_len + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-
-        // verify the merkle path for m1
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m1[[i1, i2, i3]].to_repr());
+        let (commitment, state) = P::commit_coefs(&params, &random_coefs(params.coef_no() - msg_lens[0] + 1));
+        let proof = P::open(&params, &state);
+        assert_eq!(P::verify(&params, &commitment, &proof), Ok(()));
+
+        // the reversed shape holds as many coefficients under other parameters
+        let reversed = msg_lens.iter().rev().copied().collect::<Vec<_>>();
+        let other = P::setup_with_shape(&Code6::code_params(), &reversed, 0, 4);
+        assert!(P::verify(&other, &commitment, &proof).is_err());
+    }
+
+    #[test]
+    fn rectangular_shapes() {
+        for shape in [&[40, 9, 23][..], &[7, 40, 12], &[64, 8], &[5, 16, 16, 3]] {
+            rectangular::<TensorCommitment<Ft32, Code6, Blake3>>(shape);
+            rectangular::<SimpleZkTensorCommitment<Ft32, Code6, Blake3>>(shape);
         }
-        let cur_hash = digest.finalize();
-        let item_no = code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-    
-        // verify the merkle path for m2
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m2[[i1, i2]].to_repr());
+    }
+
+    // a hybrid of Brakedown and Ligero: Reed-Solomon and RAA codes on the later axes
+    fn mixed<P>()
+    where
+        P: PolynomialCommitment<Ft32, Params = TensorParams<Ft32>>,
+    {
+        let msg_lens = [24, 16, 8, 16];
+        let families = [CodeFamily::Expander, CodeFamily::ReedSolomon, CodeFamily::Raa, CodeFamily::ReedSolomon];
+        let params = TensorParams::<Ft32>::with_families(&Code6::code_params(), &msg_lens, &families, 3, 0, 4);
+        assert_eq!(params.code_lens[1..], [48, 24, 48]);
+        assert!(Arc::ptr_eq(&params.codes[1], &params.codes[3]));
+        let (commitment, state) = P::commit_coefs(&params, &random_coefs(params.coef_no()));
+        let proof = P::open(&params, &state);
+        assert_eq!(P::verify(&params, &commitment, &proof), Ok(()));
+
+        // the same codes passed one per axis
+        let direct = TensorParams::from_axis_codes(0, 4, params.codes.clone());
+        assert_eq!(P::verify(&direct, &commitment, &proof), Ok(()));
+
+        // the lazy expander code is the expander code, the expander code on every axis is not
+        let lazy = [CodeFamily::Lazy, CodeFamily::ReedSolomon, CodeFamily::Raa, CodeFamily::ReedSolomon];
+        let lazy = TensorParams::<Ft32>::with_families(&Code6::code_params(), &msg_lens, &lazy, 3, 0, 4);
+        assert_eq!(P::verify(&lazy, &commitment, &proof), Ok(()));
+        let expander = P::setup_with_shape(&Code6::code_params(), &msg_lens, 0, 4);
+        assert!(P::verify(&expander, &commitment, &proof).is_err());
+    }
+
+    #[test]
+    fn code_families() {
+        mixed::<TensorCommitment<Ft32, Code6, Blake3>>();
+        mixed::<TensorCommitment<Ft32, Code6, Blake3, Ft32Ext2>>();
+        mixed::<SimpleZkTensorCommitment<Ft32, Code6, Blake3>>();
+        mixed::<SimpleZkTensorCommitment<Ft32, Code6, Blake3, Ft32Ext2>>();
+    }
+
+    // commit, open and verify a t-dimensional tensor through the trait, then flip one folded coefficient
+    fn t_dim<P>(dim: usize, msg_len: usize)
+    where
+        P: PolynomialCommitment<Ft32, Params = TensorParams<Ft32>>,
+    {
+        let params = P::setup(dim, msg_len, 0, 4);
+        assert_eq!(P::shape(&params), vec![msg_len; dim]);
+        let (commitment, state) = P::commit_coefs(&params, &random_coefs(params.coef_no() - 1));
+        let proof = P::open(&params, &state);
+        assert_eq!(P::verify(&params, &commitment, &proof), Ok(()));
+
+        let (other, _) = P::commit_coefs(&params, &random_coefs(params.coef_no()));
+        assert!(P::verify(&params, &other, &proof).is_err());
+    }
+
+    #[test]
+    fn dimensions() {
+        for (dim, msg_len) in [(2, 32), (3, 16), (4, 8), (5, 6)] {
+            t_dim::<TensorCommitment<Ft32, Code6, Blake3>>(dim, msg_len);
+            t_dim::<TensorCommitment<Ft32, Code6, Blake3, Ft32Ext2>>(dim, msg_len);
+            t_dim::<SimpleZkTensorCommitment<Ft32, Code6, Blake3>>(dim, msg_len);
+            t_dim::<SimpleZkTensorCommitment<Ft32, Code6, Blake3, Ft32Ext2>>(dim, msg_len);
+        }
+    }
+
+    // prove y = p(z) for q given by query(z), check y against the direct evaluation and reject tampered proofs
+    fn eval<F, E>(shape: &[usize], q: &[Vec<F>], y_direct: F, coefs: &[F])
+    where
+        F: PrimeField + Num + MulAcc,
+        E: ExtensionField<F>,
+    {
+        type P<F, E> = TensorCommitment<F, Code6, Blake3, E>;
+        let params = P::<F, E>::setup_with_shape(&Code6::code_params(), shape, 0, 4);
+        let (commitment, state) = P::<F, E>::commit_coefs(&params, coefs);
+        let (y, proof) = P::<F, E>::open_eval(&params, &state, q);
+        assert_eq!(y, y_direct);
+        assert_eq!(P::<F, E>::verify_eval(&params, &commitment, q, &y, &proof), Ok(()));
+
+        // tampered proofs are rejected with the matching error
+        let one = <F as Field>::one();
+        assert_eq!(P::<F, E>::verify_eval(&params, &commitment, q, &(y + one), &proof), Err(VerifyError::EvaluationMismatch));
+        let mut tampered = open_tensor_eval::<F, E, Blake3>(&params, &state.m0, &state.hashes_m0, q).1;
+        tampered.proximity.queries[1].rows[0][0] += one;
+        assert_eq!(P::<F, E>::verify_eval(&params, &commitment, q, &y, &tampered), Err(VerifyError::MerklePathMismatch { index: 1 }));
+        let mut tampered = open_tensor_eval::<F, E, Blake3>(&params, &state.m0, &state.hashes_m0, q).1;
+        tampered.queries.pop();
+        assert_eq!(P::<F, E>::verify_eval(&params, &commitment, q, &y, &tampered), Err(VerifyError::ShapeMismatch));
+        let mut shorter = q.to_vec();
+        shorter[0].pop();
+        assert_eq!(P::<F, E>::verify_eval(&params, &commitment, &shorter, &y, &proof), Err(VerifyError::ShapeMismatch));
+    }
+
+    fn univariate_eval<F, E>(shape: &[usize])
+    where
+        F: PrimeField + Num + MulAcc,
+        E: ExtensionField<F>,
+    {
+        // leave some padding in the last axis
+        let coef_no = shape.iter().product::<usize>() - 3;
+        let coefs = random_coefs::<F>(coef_no);
+        let x = F::random(rand::thread_rng());
+        let y = coefs.iter().rev().fold(<F as Field>::zero(), |acc, c| acc * x + c);
+        eval::<F, E>(shape, &univariate_query(x, shape), y, &coefs);
+    }
+
+    fn multilinear_eval<F, E>(shape: &[usize])
+    where
+        F: PrimeField + Num + MulAcc,
+        E: ExtensionField<F>,
+    {
+        let coef_no = shape.iter().product::<usize>();
+        let coefs = random_coefs::<F>(coef_no);
+        let z = random_coefs::<F>(coef_no.trailing_zeros() as usize);
+        // fix the variables one by one, the lowest bit of the coefficient index first
+        let mut folded = coefs.clone();
+        for zb in z.iter() {
+            folded = folded.chunks(2).map(|c| c[0] * (<F as Field>::one() - zb) + c[1] * zb).collect();
         }
-        let cur_hash = digest.finalize();
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m2_map_rwlock, &hashes_m2));
-        // println!("test {} passed", i+1);
-    });
-
-    let verified_time = Instant::now();
-
-    println!("t:4 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
-}
\ No newline at end of file
+        eval::<F, E>(shape, &multilinear_query(&z, shape), folded[0], &coefs);
+    }
+
+    #[test]
+    fn evaluations() {
+        univariate_eval::<Ft32, Ft32>(&[16, 16, 16]);
+        univariate_eval::<Ft32, Ft32>(&[30, 8, 17, 5]);
+        univariate_eval::<Ft32, Ft32Ext2>(&[30, 8, 17, 5]);
+        univariate_eval::<Ft64, Ft64Ext4>(&[16, 16]);
+        multilinear_eval::<Ft32, Ft32>(&[16, 16, 16]);
+        multilinear_eval::<Ft32, Ft32>(&[32, 4, 8, 2]);
+        multilinear_eval::<Ft32, Ft32Ext2>(&[8, 64]);
+        multilinear_eval::<Ft64, Ft64Ext4>(&[16, 8, 4]);
+    }
+}
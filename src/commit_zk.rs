@@ -1,129 +1,134 @@
-use std::collections::HashMap;
-use std::time::Instant;
-use std::sync::RwLock;
-use rand::Rng;
-use ff::Field;
+use std::marker::PhantomData;
 use ff::PrimeField;
-use ndarray::Array;
-use ndarray::Axis;
-use ndarray::Dim;
-use ndarray::parallel::prelude::*;
+use ndarray::ArrayD;
 use num_traits::Num;
 use sprs::MulAcc;
 use digest::Digest;
 use digest::Output;
 use sprs::CsMat;
+use crate::codespec::CodeParams;
 use crate::codespec::CodeSpecification;
-use crate::codegen::generate;
-use crate::codegen::generate_rev;
-use crate::encode::encode;
+use crate::codegen::generate_rev_from;
+use crate::commit::TensorParams;
+use crate::commit::TensorProof;
+use crate::commit::open_tensor;
+use crate::commit::verify_tensor;
 use crate::encode::encode_zk;
-use crate::helper::next_pow_2;
-use crate::helper::linear_combination_2_1;
-use crate::helper::linear_combination_3_2;
-use crate::helper::linear_combination_4_3;
-use crate::merkle::check_merkle_path;
-use crate::merkle::merkle_tree_commit_2d;
-use crate::merkle::merkle_tree_commit_3d;
-use crate::merkle::merkle_tree_commit_4d;
+use crate::extension::ExtensionField;
+use crate::helper::encode_tensor;
+use crate::linear_code::encode_columns;
+use crate::merkle::merkle_tree_commit;
+use crate::pcs::PolynomialCommitment;
+use crate::pcs::VerifyError;
+use crate::pcs::bench;
+
+/// degree of the random graph used for redistribution, the reverse code has length ZK_DEGREE * N0
+pub const ZK_DEGREE: usize = 3; // degree_bound(1.0/rate, 256, code_len)
+
+/// Public parameters of a zk tensor commitment
+pub struct ZkTensorParams<F>
+where
+    F: PrimeField,
+{
+    pub base: TensorParams<F>,
+    /// degree of the random graph used for redistribution
+    pub degree: usize,
+    pub precodes_rev: Vec<CsMat<F>>,
+    pub postcodes_rev: Vec<CsMat<F>>,
+}
 
-pub fn check_linear_combination_2_1_zk<F, C>(
-    msg_len: usize, 
-    code_len: usize,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    m_1d: &Array<F, Dim<[usize; 1]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    precodes_rev: &Vec<CsMat<F>>,
-    postcodes_rev: &Vec<CsMat<F>>,
-    degree: usize,
-    i1: usize,
-) -> bool
+/// Prover state of a zk tensor commitment
+pub struct ZkState<F, D>
+where
+    D: Digest,
+{
+    /// M0: N0 * m1
+    pub m0: ArrayD<F>,
+    pub hashes_m0: Vec<Output<D>>,
+}
+
+/// Tensor commitment using the planned zero-knowledge encoding `encode_zk`, only t = 2 is supported;
+/// the random linear combination is drawn from the extension E of F.
+///
+/// This is not zero-knowledge: `encode_zk` still commits to the plain codewords of axis 0, so the
+/// commitment and the opened columns are those of `TensorCommitment` and hide nothing.
+pub struct ZkTensorCommitment<F, C, D, E = F> {
+    _p: PhantomData<(F, C, D, E)>,
+}
+
+impl<F, C, D, E> PolynomialCommitment<F> for ZkTensorCommitment<F, C, D, E>
 where
     F: PrimeField + Num + MulAcc,
     C: CodeSpecification,
+    D: Digest,
+    E: ExtensionField<F>,
 {
-    assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-    assert_eq!(m_1d.shape(), &[msg_len]);
-    assert_eq!(r.len(), msg_len);
+    type Params = ZkTensorParams<F>;
+    type Commitment = Output<D>;
+    type ProverState = ZkState<F, D>;
+    type Proof = TensorProof<F, D>;
 
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i1 in 0..msg_len {
-        msg.push(m_1d[[i1]]);
+    fn setup(dim: usize, msg_len: usize, seed: u64, test_no: usize) -> ZkTensorParams<F> {
+        Self::setup_with_code(&C::code_params(), dim, msg_len, seed, test_no)
     }
-    msg.resize(code_len, <F as Field>::zero());
-    encode_zk::<F, C>(&mut msg, precodes, postcodes, precodes_rev, postcodes_rev, degree);
-    let mut s = <F as Field>::zero();
-    for i2 in 0..msg_len {
-        s = s.add(r[i2].mul(m_2d[[i1, i2]]));
+
+    fn setup_with_shape(code: &CodeParams, msg_lens: &[usize], seed: u64, test_no: usize) -> ZkTensorParams<F> {
+        assert_eq!(msg_lens.len(), 2);
+
+        // generate codes
+        let base = TensorParams::with_shape(code, msg_lens, seed, test_no);
+        let (precodes_rev, postcodes_rev) = generate_rev_from::<F>(code, ZK_DEGREE * base.code_lens[0], seed);
+        ZkTensorParams { base, degree: ZK_DEGREE, precodes_rev, postcodes_rev }
     }
-    return s == msg[i1];
-}
 
-// pub fn check_linear_combination_3_2<F>(
-//     msg_len: usize, 
-//     code_len: usize,
-//     m_3d: &Array<F, Dim<[usize; 3]>>,
-//     m_2d: &Array<F, Dim<[usize; 2]>>,
-//     r: &Vec<F>,
-//     precodes: &Vec<CsMat<F>>,
-//     postcodes: &Vec<CsMat<F>>,
-//     i1: usize,
-//     i2: usize,
-// ) -> bool
-// where
-//     F: PrimeField + Num + MulAcc,
-// {
-//     assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-//     assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-//     assert_eq!(r.len(), msg_len);
+    fn shape(params: &ZkTensorParams<F>) -> Vec<usize> {
+        params.base.msg_lens.clone()
+    }
 
-//     let mut msg = Vec::<F>::with_capacity(code_len);
-//     for i2 in 0..msg_len {
-//         msg.push(m_2d[[i1, i2]]);
-//     }
-//     msg.resize(code_len, <F as Field>::zero());
-//     encode(&mut msg, &precodes, &postcodes);
-//     let mut s = <F as Field>::zero();
-//     for i3 in 0..msg_len {
-//         s = s.add(r[i3].mul(m_3d[[i1, i2, i3]]));
-//     }
-//     return s == msg[i2];
-// }
+    fn commit(params: &ZkTensorParams<F>, coefs: &ArrayD<F>) -> (Output<D>, ZkState<F, D>) {
+        let base = &params.base;
+        assert_eq!(coefs.ndim(), 2);
 
-// pub fn check_linear_combination_4_3<F>(
-//     msg_len: usize, 
-//     code_len: usize,
-//     m_4d: &Array<F, Dim<[usize; 4]>>,
-//     m_3d: &Array<F, Dim<[usize; 3]>>,
-//     r: &Vec<F>,
-//     precodes: &Vec<CsMat<F>>,
-//     postcodes: &Vec<CsMat<F>>,
-//     i1: usize,
-//     i2: usize,
-//     i3: usize,
-// ) -> bool
-// where
-//     F: PrimeField + Num + MulAcc,
-// {
-//     assert_eq!(m_4d.shape(), &[code_len, code_len, code_len, msg_len]);
-//     assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-//     assert_eq!(r.len(), msg_len);
+        let m0 = encode_tensor(&base.msg_lens, &base.code_lens, coefs, &Self::encoder(params));
 
-//     let mut msg = Vec::<F>::with_capacity(code_len);
-//     for i3 in 0..msg_len {
-//         msg.push(m_3d[[i1, i2, i3]]);
-//     }
-//     msg.resize(code_len, <F as Field>::zero());
-//     encode(&mut msg, &precodes, &postcodes);
-//     let mut s = <F as Field>::zero();
-//     for i4 in 0..msg_len {
-//         s = s.add(r[i4].mul(m_4d[[i1, i2, i3, i4]]));
-//     }
-//     return s == msg[i3];
-// }
+        // commit to m0
+        let hashes_m0 = merkle_tree_commit::<F, D>(base.msg_lens[1], &m0.view());
+        (hashes_m0[0].clone(), ZkState { m0, hashes_m0 })
+    }
 
+    fn open(params: &ZkTensorParams<F>, state: &ZkState<F, D>) -> TensorProof<F, D> {
+        let mut transcript = params.base.transcript::<D>(b"tensor zk");
+        transcript.absorb_usize(params.degree);
+        transcript.absorb_digest(&state.hashes_m0[0]);
+        open_tensor::<F, E, D>(&params.base, &state.m0, &state.hashes_m0, &mut transcript)
+    }
+
+    fn verify(params: &ZkTensorParams<F>, commitment: &Output<D>, proof: &TensorProof<F, D>) -> Result<(), VerifyError> {
+        let base = &params.base;
+        let mut transcript = base.transcript::<D>(b"tensor zk");
+        transcript.absorb_usize(params.degree);
+        transcript.absorb_digest(commitment);
+        verify_tensor::<F, E, D, _>(base, commitment, proof, &Self::encoder(params), &mut transcript)
+    }
+}
+
+impl<F, C, D, E> ZkTensorCommitment<F, C, D, E>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+    D: Digest,
+    E: ExtensionField<F>,
+{
+    // encode blocks of codewords of axis 0 with `encode_zk`, only axis 0 is encoded
+    fn encoder(params: &ZkTensorParams<F>) -> impl Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync + '_ {
+        let base = &params.base;
+        move |_: usize, block: &mut [F], width: usize, column: &mut Vec<F>| {
+            encode_columns(block, width, base.code_lens[0], column, |msg| {
+                encode_zk(msg, &*base.codes[0], &params.precodes_rev, &params.postcodes_rev, params.degree)
+            })
+        }
+    }
+}
 
 pub fn commit_2_dim_zk<F, C, D>(
     coef_no: usize, 
@@ -137,467 +142,32 @@ where
     C: CodeSpecification,
     D: Digest,
 {
-    let mut rng = rand::thread_rng();
-
-    // generate codes
-    let degree = 3;
-    // let degree = degree_bound(1.0/rate, 256, code_len);
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-    let (precodes_rev, postcodes_rev) = generate_rev::<F, C>(degree*code_len, seed);
-
-    // M0: N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, msg_len));
-    // generate random coefficient: m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-
-    let start_time = Instant::now();
-
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(1))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i2, mut x)| {
-            let mut msg = x.to_vec();
-            msg.resize(code_len, <F as Field>::zero());
-            encode_zk::<F, C>(&mut msg, &precodes, &postcodes, &precodes_rev, &postcodes_rev, degree);
-            for i1 in 0..code_len {
-                x[i1] = msg[i1];
-            }
-        });
-
-    return;
-
-    // M1: m
-    let m1 = linear_combination_2_1::<F>(msg_len, code_len, &m0, &r1, msg_len);
-
-    // commit to m0
-    let hashes_m0 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m0);
-
-    let committed_time = Instant::now();
-
-    // verifier has access to r1, m1, m0.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-
-        assert!(
-            check_linear_combination_2_1_zk::<F, C>(
-                msg_len, code_len, 
-                &m0, &m1, &r1, 
-                &precodes, &postcodes, &precodes_rev, &postcodes_rev, degree,
-                i1
-            )
-        );
-
-    });
-
-    let m0_map_rwlock = RwLock::new(m0_map); 
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m0[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-        // println!("test {} passed", i+1);
-    });
-
-    let verified_time = Instant::now();
-
-    println!("zk t:2 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
+    let params = ZkTensorCommitment::<F, C, D>::setup(2, msg_len, seed, test_no);
+    assert_eq!(params.base.code_lens, vec![code_len; 2]);
+    let header = format!("zk t:2 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
+    bench::<F, ZkTensorCommitment<F, C, D>>(&params, coef_no, &header);
 }
 
-// pub fn commit_3_dim<F, C, D>(
-//     coef_no: usize, 
-//     msg_len: usize, 
-//     code_len: usize, 
-//     seed: u64,
-//     test_no: usize,
-// )
-// where
-//     F: PrimeField + Num + MulAcc,
-//     C: CodeSpecification,
-//     D: Digest,
-// {
-//     let mut rng = rand::thread_rng();
-
-//     // generate codes
-//     let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-//     // M0: N * N * m
-//     let mut m0 = Array::<F, _>::zeros((code_len, code_len, msg_len));
-//     // generate random coefficient: m * m * m
-//     m0.par_iter_mut().for_each(|x| {
-//         let mut rng = rand::thread_rng();
-//         *x = F::random(&mut rng);
-//     });
-
-//     let start_time = Instant::now();
-
-//     // random linear combination
-//     let mut r1 = Vec::<F>::new();
-//     r1.resize_with(msg_len, || F::random(&mut rng));
-//     let mut r2 = Vec::<F>::new();
-//     r2.resize_with(msg_len, || F::random(&mut rng));
-
-//     // encode for axis 0
-//     m0
-//         .axis_iter_mut(Axis(2))
-//         .into_par_iter()
-//         .enumerate()
-//         .for_each(|(i3, mut xx)| {
-//             xx
-//                 .axis_iter_mut(Axis(1))
-//                 .enumerate()
-//                 .filter(|(i, _)| i < &(msg_len))
-//                 .for_each(|(i2, mut x)| {
-//                     let mut msg = x.to_vec();
-//                     msg.resize(code_len, <F as Field>::zero());
-//                     encode(&mut msg, &precodes, &postcodes);
-//                     for i1 in 0..code_len {
-//                         x[i1] = msg[i1];
-//                     }
-//                 });
-//         });
-//     // encode for axis 1
-//     m0
-//         .axis_iter_mut(Axis(2))
-//         .into_par_iter()
-//         .enumerate()
-//         .for_each(|(i3, mut xx)| {
-//             xx
-//                 .axis_iter_mut(Axis(0))
-//                 .enumerate()
-//                 .for_each(|(i1, mut x)| {
-//                     let mut msg = x.to_vec();
-//                     msg.resize(code_len, <F as Field>::zero());
-//                     encode(&mut msg, &precodes, &postcodes);
-//                     for i2 in msg_len..code_len {
-//                         x[i2] = msg[i2];
-//                     }
-//                 });
-//         });
-
-//     // M1: N * m
-//     let m1 = linear_combination_3_2::<F>(msg_len, code_len, &m0, &r1, msg_len);
-//     // M2: m
-//     let m2 = linear_combination_2_1::<F>(msg_len, code_len, &m1, &r2, msg_len);
-
-
-//     // commit to m0
-//     let hashes_m0 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m0);
-//     // commit to m1
-//     let hashes_m1 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m1);
-
-//     let committed_time = Instant::now();
-
-//     // verifier has access to r1, r2, m2, m0.root, m1.root
-//     let mut m0_map = HashMap::<usize, Output<D>>::new();
-//     m0_map.insert(0, hashes_m0[0].clone());
-//     let mut m1_map = HashMap::<usize, Output<D>>::new();
-//     m1_map.insert(0, hashes_m1[0].clone());
-//     // sample idx
-//     let mut idx_1 = Vec::<usize>::new();
-//     idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-//     let mut idx_2 = Vec::<usize>::new();
-//     idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-//     (0..test_no).into_par_iter().for_each(|i| {
-//         let i1 = idx_1[i];
-//         let i2 = idx_2[i];
-//         assert!(
-//             check_linear_combination_3_2::<F>(
-//                 msg_len, code_len, 
-//                 &m0, &m1, &r1, 
-//                 &precodes, &postcodes, 
-//                 i1, i2
-//             )
-//         );
-//         assert!(
-//             check_linear_combination_2_1::<F>(
-//                 msg_len, code_len, 
-//                 &m1, &m2, &r2, 
-//                 &precodes, &postcodes, 
-//                 i1
-//             )
-//         );
-//     });
-    
-//     let m0_map_rwlock = RwLock::new(m0_map);
-//     let m1_map_rwlock = RwLock::new(m1_map);
-//     (0..test_no).into_par_iter().for_each(|i| {
-//         let i1 = idx_1[i];
-//         let i2 = idx_2[i];
-
-//         // verify the merkle path for m0
-//         let mut digest = D::new();
-//         for i3 in 0..msg_len {
-//             digest.update(m0[[i1, i2, i3]].to_repr());
-//         }
-//         let cur_hash = digest.finalize();
-//         let item_no = code_len * code_len;
-//         let np2 = next_pow_2(item_no);
-//         let idx = i1 + i2 * code_len + np2 - 1;
-//         assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-    
-//         // verify the merkle path for m1
-//         let mut digest = D::new();
-//         for i2 in 0..msg_len {
-//             digest.update(m1[[i1, i2]].to_repr());
-//         }
-//         let cur_hash = digest.finalize();
-//         let item_no = code_len;
-//         let np2 = next_pow_2(item_no);
-//         let idx = i1 + np2 - 1;
-//         assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-//         // println!("test {} passed", i+1);
-//     });
-
-//     let verified_time = Instant::now();
-
-//     println!("t:3 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-//     println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-//     println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-//     println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
-// }
-
-// pub fn commit_4_dim<F, C, D>(
-//     coef_no: usize, 
-//     msg_len: usize, 
-//     code_len: usize, 
-//     seed: u64,
-//     test_no: usize,
-// )
-// where
-//     F: PrimeField + Num + MulAcc,
-//     C: CodeSpecification,
-//     D: Digest,
-// {
-//     let mut rng = rand::thread_rng();
-
-//     // generate codes
-//     let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-//     // M0: N * N * N * m
-//     let mut m0 = Array::<F, _>::zeros((code_len, code_len, code_len, msg_len));
-//     // generate random coefficient: m * m * m * m
-//     m0.par_iter_mut().for_each(|x| {
-//         let mut rng = rand::thread_rng();
-//         *x = F::random(&mut rng);
-//     });
-
-//     let start_time = Instant::now();
-
-//     // random linear combination
-//     let mut r1 = Vec::<F>::new();
-//     r1.resize_with(msg_len, || F::random(&mut rng));
-//     let mut r2 = Vec::<F>::new();
-//     r2.resize_with(msg_len, || F::random(&mut rng));
-//     let mut r3 = Vec::<F>::new();
-//     r3.resize_with(msg_len, || F::random(&mut rng));
-
-//     // encode for axis 0
-//     m0
-//         .axis_iter_mut(Axis(3))
-//         .into_par_iter()
-//         .enumerate()
-//         .for_each(|(i4, mut xxx)| {
-//             xxx
-//                 .axis_iter_mut(Axis(2))
-//                 .enumerate()
-//                 .filter(|(i, _)| i < &(msg_len))
-//                 .for_each(|(i3, mut xx)| {
-//                     xx
-//                         .axis_iter_mut(Axis(1))
-//                         .enumerate()
-//                         .filter(|(i, _)| i < &(msg_len))
-//                         .for_each(|(i2, mut x)| {
-//                             let mut msg = x.to_vec();
-//                             msg.resize(code_len, <F as Field>::zero());
-//                             encode(&mut msg, &precodes, &postcodes);
-//                             for i1 in 0..code_len {
-//                                 x[i1] = msg[i1];
-//                             }
-//                         });
-//                 });
-//         });
-//     // encode for axis 1
-//     m0
-//         .axis_iter_mut(Axis(3))
-//         .into_par_iter()
-//         .enumerate()
-//         .for_each(|(i4, mut xxx)| {
-//             xxx
-//                 .axis_iter_mut(Axis(2))
-//                 .enumerate()
-//                 .filter(|(i, _)| i < &(msg_len))
-//                 .for_each(|(i3, mut xx)| {
-//                     xx
-//                         .axis_iter_mut(Axis(0))
-//                         .enumerate()
-//                         .for_each(|(i1, mut x)| {
-//                             let mut msg = x.to_vec();
-//                             msg.resize(code_len, <F as Field>::zero());
-//                             encode(&mut msg, &precodes, &postcodes);
-//                             for i2 in msg_len..code_len {
-//                                 x[i2] = msg[i2];
-//                             }
-//                         });
-//                 });
-//         });
-//     // encode for axis 2
-//     m0
-//         .axis_iter_mut(Axis(3))
-//         .into_par_iter()
-//         .enumerate()
-//         .for_each(|(i4, mut xxx)| {
-//             xxx
-//                 .axis_iter_mut(Axis(1))
-//                 .enumerate()
-//                 .for_each(|(i2, mut xx)| {
-//                     xx
-//                         .axis_iter_mut(Axis(0))
-//                         .enumerate()
-//                         .for_each(|(i1, mut x)| {
-//                             let mut msg = x.to_vec();
-//                             msg.resize(code_len, <F as Field>::zero());
-//                             encode(&mut msg, &precodes, &postcodes);
-//                             for i3 in msg_len..code_len {
-//                                 x[i3] = msg[i3];
-//                             }
-//                         });
-//                 });
-//         });
-
-//     // M1: N * N * m
-//     let m1 = linear_combination_4_3::<F>(msg_len, code_len, &m0, &r1, msg_len);
-//     // M2: N * m
-//     let m2 = linear_combination_3_2::<F>(msg_len, code_len, &m1, &r2, msg_len);
-//     // M3: m
-//     let m3 = linear_combination_2_1::<F>(msg_len, code_len, &m2, &r3, msg_len);
-
-
-//     // commit to m0
-//     let hashes_m0 = merkle_tree_commit_4d::<F, D>(msg_len, code_len, &m0);
-//     // commit to m1
-//     let hashes_m1 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m1);
-//     // commit to m2
-//     let hashes_m2 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m2);
-
-//     let committed_time = Instant::now();
-
-//     // verifier has access to r1, r2, r3, m3, m0.root, m1.root, m2.root
-//     let mut m0_map = HashMap::<usize, Output<D>>::new();
-//     m0_map.insert(0, hashes_m0[0].clone());
-//     let mut m1_map = HashMap::<usize, Output<D>>::new();
-//     m1_map.insert(0, hashes_m1[0].clone());
-//     let mut m2_map = HashMap::<usize, Output<D>>::new();
-//     m2_map.insert(0, hashes_m2[0].clone());
-//     // sample idx
-//     let mut idx_1 = Vec::<usize>::new();
-//     idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-//     let mut idx_2 = Vec::<usize>::new();
-//     idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-//     let mut idx_3 = Vec::<usize>::new();
-//     idx_3.resize_with(test_no, || rng.gen_range(0..code_len));
-//     (0..test_no).into_par_iter().for_each(|i| {
-//         let i1 = idx_1[i];
-//         let i2 = idx_2[i];
-//         let i3 = idx_3[i];
-
-//         assert!(
-//             check_linear_combination_4_3::<F>(
-//                 msg_len, code_len, 
-//                 &m0, &m1, &r1, 
-//                 &precodes, &postcodes, 
-//                 i1, i2, i3
-//             )
-//         );
-//         assert!(
-//             check_linear_combination_3_2::<F>(
-//                 msg_len, code_len, 
-//                 &m1, &m2, &r2, 
-//                 &precodes, &postcodes, 
-//                 i1, i2
-//             )
-//         );
-//         assert!(
-//             check_linear_combination_2_1::<F>(
-//                 msg_len, code_len, 
-//                 &m2, &m3, &r3, 
-//                 &precodes, &postcodes, 
-//                 i1
-//             )
-//         );
-//     });
-    
-//     let m0_map_rwlock = RwLock::new(m0_map);
-//     let m1_map_rwlock = RwLock::new(m1_map);
-//     let m2_map_rwlock = RwLock::new(m2_map);
-//     (0..test_no).into_par_iter().for_each(|i| {
-//         let i1 = idx_1[i];
-//         let i2 = idx_2[i];
-//         let i3 = idx_3[i];
-
-//         // verify the merkle path for m0
-//         let mut digest = D::new();
-//         for i4 in 0..msg_len {
-//             digest.update(m0[[i1, i2, i3, i4]].to_repr());
-//         }
-//         let cur_hash = digest.finalize();
-//         let item_no = code_len * code_len * code_len;
-//         let np2 = next_pow_2(item_no);
-//         let idx = i1 + i2 * code_len + i3 * code_len * code_len + np2 - 1;
-//         assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-
-//         // verify the merkle path for m1
-//         let mut digest = D::new();
-//         for i3 in 0..msg_len {
-//             digest.update(m1[[i1, i2, i3]].to_repr());
-//         }
-//         let cur_hash = digest.finalize();
-//         let item_no = code_len * code_len;
-//         let np2 = next_pow_2(item_no);
-//         let idx = i1 + i2 * code_len + np2 - 1;
-//         assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-    
-//         // verify the merkle path for m2
-//         let mut digest = D::new();
-//         for i2 in 0..msg_len {
-//             digest.update(m2[[i1, i2]].to_repr());
-//         }
-//         let cur_hash = digest.finalize();
-//         let item_no = code_len;
-//         let np2 = next_pow_2(item_no);
-//         let idx = i1 + np2 - 1;
-//         assert!(check_merkle_path::<D>(cur_hash, idx, &m2_map_rwlock, &hashes_m2));
-//         // println!("test {} passed", i+1);
-//     });
-
-//     let verified_time = Instant::now();
-
-//     println!("t:4 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-//     println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-//     println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-//     println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
-// }
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use crate::codespec::Code6;
+    use crate::commit::TensorCommitment;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::pcs::coefs_to_tensor;
+    use crate::pcs::random_coefs;
+
+    // nothing is hidden yet: the commitment is the plain one and the same on every run
+    #[test]
+    fn commits_to_plain_codewords() {
+        type Zk = ZkTensorCommitment<Ft32, Code6, Blake3>;
+        type Plain = TensorCommitment<Ft32, Code6, Blake3>;
+        let params = Zk::setup(2, 32, 0, 4);
+        let coefs = coefs_to_tensor(&random_coefs::<Ft32>(32 * 32), &[32, 32]);
+        let (commitment, state) = Zk::commit(&params, &coefs);
+        assert_eq!(Zk::commit(&params, &coefs).0, commitment);
+        assert_eq!(Plain::commit(&params.base, &coefs).0, commitment);
+        assert_eq!(Zk::verify(&params, &commitment, &Zk::open(&params, &state)), Ok(()));
+    }
+}
@@ -0,0 +1,417 @@
+use std::fmt;
+use ff::Field;
+use ff::PrimeField;
+use sprs::CsMat;
+use crate::encode::codeword_length;
+use crate::encode::reed_solomon_points;
+
+/// Reasons a received word cannot be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodingError {
+    /// the word does not have the codeword length of the code
+    LengthMismatch { expected: usize, actual: usize },
+    /// more than one codeword agrees with the known symbols, too many were erased
+    Ambiguous,
+    /// no codeword agrees with the known symbols
+    Inconsistent,
+    /// no codeword was found within the allowed number of errors
+    TooManyErrors,
+}
+
+impl fmt::Display for DecodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodingError::LengthMismatch { expected, actual } => {
+                write!(f, "word has length {}, the codewords have length {}", actual, expected)
+            }
+            DecodingError::Ambiguous => write!(f, "too many erasures to determine the codeword"),
+            DecodingError::Inconsistent => write!(f, "no codeword agrees with the known symbols"),
+            DecodingError::TooManyErrors => write!(f, "no codeword within the error bound"),
+        }
+    }
+}
+
+impl std::error::Error for DecodingError {}
+
+// parity checks of the code: linear combinations of codeword symbols that vanish exactly on the codewords,
+// stored row by row like a CSR matrix
+struct Checks<F> {
+    ptrs: Vec<usize>,
+    vars: Vec<usize>,
+    coefs: Vec<F>,
+}
+
+impl<F> Checks<F>
+where
+    F: PrimeField,
+{
+    fn len(&self) -> usize {
+        self.ptrs.len() - 1
+    }
+
+    fn terms(&self, c: usize) -> impl Iterator<Item = (usize, F)> + '_ {
+        let range = self.ptrs[c]..self.ptrs[c + 1];
+        self.vars[range.clone()].iter().cloned().zip(self.coefs[range].iter().cloned())
+    }
+
+    fn push<I>(&mut self, terms: I)
+    where
+        I: IntoIterator<Item = (usize, F)>,
+    {
+        for (var, coef) in terms {
+            self.vars.push(var);
+            self.coefs.push(coef);
+        }
+        self.ptrs.push(self.vars.len());
+    }
+
+    // one check per row of code: code * word[in_start..] = word[out_start..]
+    fn push_matrix(&mut self, code: &CsMat<F>, in_start: usize, out_start: usize) {
+        for (j, row) in sparse_rows(code).into_iter().enumerate() {
+            let output = (out_start + j, -<F as Field>::one());
+            self.push(row.into_iter().map(|(col, v)| (in_start + col, v)).chain(Some(output)));
+        }
+    }
+
+    fn eval(&self, c: usize, word: &[F]) -> F {
+        self.terms(c).fold(<F as Field>::zero(), |acc, (var, coef)| acc + coef * word[var])
+    }
+
+    // the checks every symbol appears in
+    fn by_var(&self, n: usize) -> Vec<Vec<usize>> {
+        let mut var_checks = vec![Vec::new(); n];
+        for c in 0..self.len() {
+            for (var, _) in self.terms(c) {
+                var_checks[var].push(c);
+            }
+        }
+        var_checks
+    }
+}
+
+// the nonzero entries of every row of code, independent of its storage
+fn sparse_rows<F>(code: &CsMat<F>) -> Vec<Vec<(usize, F)>>
+where
+    F: PrimeField,
+{
+    let mut rows = vec![Vec::new(); code.rows()];
+    for (outer, vec) in code.outer_iterator().enumerate() {
+        for (inner, &v) in vec.iter() {
+            let (row, col) = if code.is_csc() { (inner, outer) } else { (outer, inner) };
+            rows[row].push((col, v));
+        }
+    }
+    rows
+}
+
+// follows the layout written by `encode`: x, the precode outputs, the Reed-Solomon output and the postcode outputs
+// from the last level to the first
+fn parity_checks<F>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Checks<F>
+where
+    F: PrimeField,
+{
+    let levels = precodes.len();
+    let mut checks = Checks { ptrs: vec![0], vars: Vec::new(), coefs: Vec::new() };
+
+    // the input of precode i starts at starts[i]
+    let mut starts = vec![0usize];
+    for precode in precodes.iter().take(levels - 1) {
+        let start = *starts.last().unwrap();
+        checks.push_matrix(precode, start, start + precode.cols());
+        starts.push(start + precode.cols());
+    }
+
+    // the output of the last precode is not part of the codeword, so its rows are folded into the
+    // Reed-Solomon checks: rs[t] = sum_j x_t^j (precode * z)[j]
+    let precode = precodes.last().unwrap();
+    let z_start = starts[levels - 1];
+    let rs_start = z_start + precode.cols();
+    let rs_len = postcodes.last().unwrap().cols();
+    let rows = sparse_rows(precode);
+    for (t, x) in reed_solomon_points::<F>(precode.rows(), rs_len).into_iter().enumerate() {
+        let mut coefs = vec![<F as Field>::zero(); precode.cols()];
+        let mut xj = <F as Field>::one();
+        for row in rows.iter() {
+            for &(col, v) in row.iter() {
+                coefs[col] += xj * v;
+            }
+            xj *= x;
+        }
+        let output = (rs_start + t, -<F as Field>::one());
+        checks.push(
+            coefs
+                .into_iter()
+                .enumerate()
+                .filter(|(_, coef)| !bool::from(coef.is_zero()))
+                .map(|(col, coef)| (z_start + col, coef))
+                .chain(Some(output)),
+        );
+    }
+
+    // postcode i reads the codeword of level i + 1, which runs from its input (the Reed-Solomon output for
+    // the last level) up to the postcode outputs written so far
+    let mut out_start = rs_start + rs_len;
+    for (i, postcode) in postcodes.iter().enumerate().rev() {
+        let in_start = if i == levels - 1 { rs_start } else { starts[i + 1] };
+        assert_eq!(out_start - in_start, postcode.cols());
+        checks.push_matrix(postcode, in_start, out_start);
+        out_start += postcode.rows();
+    }
+    assert_eq!(out_start, codeword_length(precodes, postcodes));
+
+    checks
+}
+
+// fill in the unknown symbols of word from the checks: first peel off checks with a single unknown symbol,
+// then run Gaussian elimination on whatever is left
+fn solve<F>(checks: &Checks<F>, var_checks: &[Vec<usize>], word: &mut [F], known: &mut [bool]) -> Result<(), DecodingError>
+where
+    F: PrimeField,
+{
+    let mut unknown = (0..checks.len())
+        .map(|c| checks.terms(c).filter(|&(var, _)| !known[var]).count())
+        .collect::<Vec<_>>();
+    let mut queue = (0..checks.len()).filter(|&c| unknown[c] == 1).collect::<Vec<_>>();
+    while let Some(c) = queue.pop() {
+        // the unknown symbol may have been solved by another check in the meantime
+        if unknown[c] != 1 {
+            continue;
+        }
+        let mut sum = <F as Field>::zero();
+        let mut unknown_term = None;
+        for (var, coef) in checks.terms(c) {
+            if known[var] {
+                sum += coef * word[var];
+            }else{
+                unknown_term = Some((var, coef));
+            }
+        }
+        let (var, coef) = unknown_term.unwrap();
+        word[var] = -sum * coef.invert().unwrap();
+        known[var] = true;
+        for &d in var_checks[var].iter() {
+            unknown[d] -= 1;
+            if unknown[d] == 1 {
+                queue.push(d);
+            }
+        }
+    }
+
+    let vars = (0..word.len()).filter(|&var| !known[var]).collect::<Vec<_>>();
+    if vars.is_empty() {
+        return Ok(());
+    }
+    let mut column = vec![usize::MAX; word.len()];
+    for (i, &var) in vars.iter().enumerate() {
+        column[var] = i;
+    }
+
+    // rows in echelon form: every row is zero in the pivot columns of the rows before it
+    let mut pivots: Vec<(usize, Vec<F>, F)> = Vec::new();
+    for c in (0..checks.len()).filter(|&c| unknown[c] > 0) {
+        if pivots.len() == vars.len() {
+            break;
+        }
+        let mut row = vec![<F as Field>::zero(); vars.len()];
+        let mut rhs = <F as Field>::zero();
+        for (var, coef) in checks.terms(c) {
+            if known[var] {
+                rhs -= coef * word[var];
+            }else{
+                row[column[var]] += coef;
+            }
+        }
+        for (p, pivot_row, pivot_rhs) in pivots.iter() {
+            let factor = row[*p];
+            if bool::from(factor.is_zero()) {
+                continue;
+            }
+            for (x, y) in row.iter_mut().zip(pivot_row.iter()) {
+                *x -= factor * y;
+            }
+            rhs -= factor * pivot_rhs;
+        }
+        match row.iter().position(|x| !bool::from(x.is_zero())) {
+            Some(p) => {
+                let inv = row[p].invert().unwrap();
+                for x in row.iter_mut() {
+                    *x *= inv;
+                }
+                pivots.push((p, row, rhs * inv));
+            }
+            None if !bool::from(rhs.is_zero()) => return Err(DecodingError::Inconsistent),
+            None => {}
+        }
+    }
+    if pivots.len() < vars.len() {
+        return Err(DecodingError::Ambiguous);
+    }
+
+    // back substitution, each row only depends on the pivots found after it
+    let mut values = vec![<F as Field>::zero(); vars.len()];
+    for (p, row, rhs) in pivots.iter().rev() {
+        let mut value = *rhs;
+        for (i, x) in row.iter().enumerate() {
+            if i != *p && !bool::from(x.is_zero()) {
+                value -= *x * values[i];
+            }
+        }
+        values[*p] = value;
+    }
+    for (&var, value) in vars.iter().zip(values) {
+        word[var] = value;
+        known[var] = true;
+    }
+    Ok(())
+}
+
+fn is_codeword<F>(checks: &Checks<F>, word: &[F]) -> bool
+where
+    F: PrimeField,
+{
+    (0..checks.len()).all(|c| bool::from(checks.eval(c, word).is_zero()))
+}
+
+/// Recover the codeword of the code (precodes, postcodes) from the symbols that were not erased (None).
+/// The message is the first precodes[0].cols() symbols of the result.
+///
+/// Erasures are peeled off through the sparse precode and postcode rows, whatever remains is solved by
+/// Gaussian elimination, so decoding succeeds whenever the known symbols determine the codeword.
+pub fn decode_erasures<F>(received: &[Option<F>], precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Result<Vec<F>, DecodingError>
+where
+    F: PrimeField,
+{
+    let code_len = codeword_length(precodes, postcodes);
+    if received.len() != code_len {
+        return Err(DecodingError::LengthMismatch { expected: code_len, actual: received.len() });
+    }
+
+    let checks = parity_checks(precodes, postcodes);
+    let var_checks = checks.by_var(code_len);
+    let mut word = received.iter().map(|x| x.unwrap_or_else(<F as Field>::zero)).collect::<Vec<_>>();
+    let mut known = received.iter().map(Option::is_some).collect::<Vec<_>>();
+    solve(&checks, &var_checks, &mut word, &mut known)?;
+    if !is_codeword(&checks, &word) {
+        return Err(DecodingError::Inconsistent);
+    }
+    Ok(word)
+}
+
+/// Find a codeword of the code (precodes, postcodes) that differs from received in at most max_errors symbols.
+///
+/// Symbols whose parity checks fail are erased and recovered with `decode_erasures`, first the symbols all of
+/// whose checks fail, then those where at least half of them do. Errors that cancel in some check can hide
+/// from this, so decoding may fail even if such a codeword exists, but a returned codeword is always within
+/// max_errors of received and is the unique one if max_errors is below half the minimum distance.
+pub fn decode_errors<F>(received: &[F], max_errors: usize, precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Result<Vec<F>, DecodingError>
+where
+    F: PrimeField,
+{
+    let code_len = codeword_length(precodes, postcodes);
+    if received.len() != code_len {
+        return Err(DecodingError::LengthMismatch { expected: code_len, actual: received.len() });
+    }
+
+    let checks = parity_checks(precodes, postcodes);
+    let failing = (0..checks.len())
+        .map(|c| !bool::from(checks.eval(c, received).is_zero()))
+        .collect::<Vec<_>>();
+    if !failing.contains(&true) {
+        return Ok(received.to_vec());
+    }
+
+    let var_checks = checks.by_var(code_len);
+    for (num, den) in [(1, 1), (1, 2)] {
+        // erase a symbol if at least num/den of its checks fail
+        let mut known = var_checks
+            .iter()
+            .map(|cs| {
+                let failed = cs.iter().filter(|&&c| failing[c]).count();
+                failed == 0 || failed * den < num * cs.len()
+            })
+            .collect::<Vec<_>>();
+        let mut word = received.to_vec();
+        if solve(&checks, &var_checks, &mut word, &mut known).is_err() || !is_codeword(&checks, &word) {
+            continue;
+        }
+        let errors = word.iter().zip(received.iter()).filter(|(x, y)| x != y).count();
+        if errors <= max_errors {
+            return Ok(word);
+        }
+    }
+    Err(DecodingError::TooManyErrors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+    use rand::SeedableRng;
+    use rand::seq::index::sample;
+    use rand_chacha::ChaCha20Rng;
+    use crate::codegen::generate;
+    use crate::codespec::Code6;
+    use crate::encode::encode;
+    use crate::fieldspec::ft255::Ft255;
+
+    type Codes = (Vec<CsMat<Ft255>>, Vec<CsMat<Ft255>>);
+
+    fn codeword(msg_len: usize, rng: &mut ChaCha20Rng) -> (Codes, Vec<Ft255>) {
+        let (precodes, postcodes) = generate::<Ft255, Code6>(msg_len, 0);
+        let mut word = vec![<Ft255 as Field>::zero(); codeword_length(&precodes, &postcodes)];
+        for x in word[..msg_len].iter_mut() {
+            *x = Ft255::random(&mut *rng);
+        }
+        encode(&mut word, &precodes, &postcodes);
+        ((precodes, postcodes), word)
+    }
+
+    #[test]
+    fn erasures() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        let ((precodes, postcodes), word) = codeword(300, &mut rng);
+        let code_len = word.len();
+
+        // random erasures beyond the minimum distance
+        for erased in [0, code_len / 20, code_len / 10] {
+            let mut received = word.iter().map(|&x| Some(x)).collect::<Vec<_>>();
+            for i in sample(&mut rng, code_len, erased) {
+                received[i] = None;
+            }
+            assert_eq!(decode_erasures(&received, &precodes, &postcodes), Ok(word.clone()));
+        }
+
+        // the message only enters the codeword through the first precode, which shrinks it
+        let mut received = word.iter().map(|&x| Some(x)).collect::<Vec<_>>();
+        for x in received[..300].iter_mut() {
+            *x = None;
+        }
+        assert_eq!(decode_erasures(&received, &precodes, &postcodes), Err(DecodingError::Ambiguous));
+
+        let mut received = word.iter().map(|&x| Some(x)).collect::<Vec<_>>();
+        received[code_len - 1] = Some(word[code_len - 1] + <Ft255 as Field>::one());
+        assert_eq!(decode_erasures(&received, &precodes, &postcodes), Err(DecodingError::Inconsistent));
+        assert_eq!(
+            decode_erasures(&received[1..], &precodes, &postcodes),
+            Err(DecodingError::LengthMismatch { expected: code_len, actual: code_len - 1 })
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let ((precodes, postcodes), word) = codeword(300, &mut rng);
+        assert_eq!(decode_errors(&word, 0, &precodes, &postcodes), Ok(word.clone()));
+
+        for error_no in [1, 5, 10] {
+            let mut received = word.clone();
+            for i in sample(&mut rng, word.len(), error_no) {
+                received[i] += Ft255::from(rng.gen_range(1..u64::MAX));
+            }
+            assert_eq!(decode_errors(&received, 10, &precodes, &postcodes), Ok(word.clone()));
+            if error_no > 1 {
+                assert_eq!(decode_errors(&received, error_no - 1, &precodes, &postcodes), Err(DecodingError::TooManyErrors));
+            }
+        }
+    }
+}
@@ -0,0 +1,389 @@
+use std::ops::Range;
+use ff::Field;
+use ff::PrimeField;
+use num_traits::Num;
+use rand::Rng;
+use rand::seq::SliceRandom;
+use rand::seq::index::sample;
+use sprs::CsMat;
+use sprs::MulAcc;
+use crate::codegen::generate_from;
+use crate::codespec::CodeParams;
+use crate::encode::codeword_length;
+use crate::encode::encode;
+use crate::linear_code::LinearCode;
+
+/// largest message support tried by the low-weight message search
+pub const MAX_SUPPORT: usize = 8;
+/// largest msg_len for which `estimate_distance` also runs Prange's information set search,
+/// each of its trials solves a msg_len x msg_len system
+pub const PRANGE_MAX_MSG_LEN: usize = 256;
+
+/// Lightest nonzero codeword found in a code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DistanceEstimate {
+    pub msg_len: usize,
+    pub code_len: usize,
+    /// weight of the lightest codeword, an upper bound on the minimum distance
+    pub min_weight: usize,
+    /// whether the search was exhaustive, so that min_weight is the minimum distance
+    pub exact: bool,
+    /// number of codewords looked at
+    pub codewords: usize,
+}
+
+impl DistanceEstimate {
+    /// min_weight relative to the codeword length
+    pub fn relative(&self) -> f64 {
+        self.min_weight as f64 / self.code_len as f64
+    }
+}
+
+fn unit<F>(i: usize, len: usize) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let mut x = vec![<F as Field>::zero(); len];
+    x[i] = <F as Field>::one();
+    x
+}
+
+fn encode_msg<F>(msg: &[F], precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Vec<F>
+where
+    F: PrimeField + Num + MulAcc,
+{
+    let mut word = vec![<F as Field>::zero(); codeword_length(precodes, postcodes)];
+    word[..msg.len()].copy_from_slice(msg);
+    encode(&mut word, precodes, postcodes);
+    word
+}
+
+fn weight<F>(word: &[F]) -> usize
+where
+    F: PrimeField,
+{
+    word.iter().filter(|x| !bool::from(<F as Field>::is_zero(x))).count()
+}
+
+// sum_i coefs[i] * columns[i]
+fn combine<F>(columns: &[Vec<F>], coefs: &[F]) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let mut word = vec![<F as Field>::zero(); columns[0].len()];
+    for (column, &coef) in columns.iter().zip(coefs.iter()) {
+        if bool::from(<F as Field>::is_zero(&coef)) {
+            continue;
+        }
+        for (x, &y) in word.iter_mut().zip(column.iter()) {
+            *x += coef * y;
+        }
+    }
+    word
+}
+
+// linearly independent rows in echelon form: every row is zero in the pivot columns of the rows before it
+#[derive(Clone)]
+struct Echelon<F> {
+    // pivot column, row and inverse of the pivot
+    rows: Vec<(usize, Vec<F>, F)>,
+}
+
+impl<F> Echelon<F>
+where
+    F: PrimeField,
+{
+    fn new() -> Self {
+        Echelon { rows: Vec::new() }
+    }
+
+    // add row unless it depends on the rows so far
+    fn push(&mut self, mut row: Vec<F>) {
+        for (c, pivot_row, inv) in self.rows.iter() {
+            let factor = row[*c];
+            if bool::from(<F as Field>::is_zero(&factor)) {
+                continue;
+            }
+            let factor = factor * inv;
+            for (x, &y) in row.iter_mut().zip(pivot_row.iter()) {
+                *x -= factor * y;
+            }
+        }
+        if let Some(c) = row.iter().position(|x| !bool::from(<F as Field>::is_zero(x))) {
+            let inv = row[c].invert().unwrap();
+            self.rows.push((c, row, inv));
+        }
+    }
+
+    // a nonzero x with row * x = 0 for all rows, None if they have full rank
+    fn null_vector(&self, cols: usize) -> Option<Vec<F>> {
+        let mut pivot = vec![false; cols];
+        for (c, _, _) in self.rows.iter() {
+            pivot[*c] = true;
+        }
+        let free = pivot.iter().position(|&p| !p)?;
+        let mut x = vec![<F as Field>::zero(); cols];
+        x[free] = <F as Field>::one();
+        // each row only depends on its pivot, the pivots of the rows after it and the free columns
+        for (c, row, inv) in self.rows.iter().rev() {
+            let sum = row
+                .iter()
+                .zip(x.iter())
+                .fold(<F as Field>::zero(), |acc, (&a, &b)| acc + a * b);
+            x[*c] = -sum * inv;
+        }
+        Some(x)
+    }
+}
+
+fn null_vector<F>(rows: Vec<Vec<F>>, cols: usize) -> Option<Vec<F>>
+where
+    F: PrimeField,
+{
+    let mut echelon = Echelon::new();
+    for row in rows {
+        echelon.push(row);
+    }
+    echelon.null_vector(cols)
+}
+
+// n choose r, None if it overflows
+fn binomial(n: usize, r: usize) -> Option<usize> {
+    let mut result = 1usize;
+    for i in 0..r {
+        // result is (n choose i), so the division is exact
+        result = result.checked_mul(n - i)? / (i + 1);
+    }
+    Some(result)
+}
+
+// go through the sets of `left` more zero positions from start.., with the rows of the positions so far in echelon
+fn search_zeros<F>(columns: &[Vec<F>], echelon: &Echelon<F>, start: usize, left: usize, min_weight: &mut usize, codewords: &mut usize)
+where
+    F: PrimeField,
+{
+    if left == 0 {
+        if let Some(msg) = echelon.null_vector(columns.len()) {
+            *min_weight = (*min_weight).min(weight(&combine(columns, &msg)));
+            *codewords += 1;
+        }
+        return;
+    }
+    let code_len = columns[0].len();
+    for j in start..=(code_len - left) {
+        let mut next = echelon.clone();
+        next.push(columns.iter().map(|column| column[j]).collect());
+        search_zeros(columns, &next, j + 1, left - 1, min_weight, codewords);
+    }
+}
+
+/// Minimum distance of the code (precodes, postcodes), None if the search would look at more than max_subsets codewords.
+///
+/// A lightest codeword vanishes on msg_len - 1 independent positions, which determine it up to a scalar,
+/// so it is found by going through all sets of msg_len - 1 positions.
+pub fn exact_distance<F>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>], max_subsets: usize) -> Option<DistanceEstimate>
+where
+    F: PrimeField + Num + MulAcc,
+{
+    let msg_len = precodes[0].cols();
+    let code_len = codeword_length(precodes, postcodes);
+    if binomial(code_len, msg_len - 1)? > max_subsets {
+        return None;
+    }
+
+    let columns = (0..msg_len).map(|i| encode_msg(&unit::<F>(i, msg_len), precodes, postcodes)).collect::<Vec<_>>();
+    Some(exact_from_columns(&columns))
+}
+
+/// `exact_distance` of any linear code, e.g. to hold a code to the Singleton bound code_len - msg_len + 1
+pub fn exact_code_distance<F>(code: &dyn LinearCode<F>, max_subsets: usize) -> Option<DistanceEstimate>
+where
+    F: PrimeField,
+{
+    let (msg_len, code_len) = (code.message_len(), code.codeword_len());
+    if binomial(code_len, msg_len - 1)? > max_subsets {
+        return None;
+    }
+
+    let columns = (0..msg_len)
+        .map(|i| {
+            let mut word = unit::<F>(i, code_len);
+            code.encode(&mut word);
+            word
+        })
+        .collect::<Vec<_>>();
+    Some(exact_from_columns(&columns))
+}
+
+// minimum weight of the code spanned by the encodings of the unit messages
+fn exact_from_columns<F>(columns: &[Vec<F>]) -> DistanceEstimate
+where
+    F: PrimeField,
+{
+    let (msg_len, code_len) = (columns.len(), columns[0].len());
+    let mut min_weight = code_len;
+    let mut codewords = 0;
+    search_zeros(columns, &Echelon::new(), 0, msg_len - 1, &mut min_weight, &mut codewords);
+    DistanceEstimate { msg_len, code_len, min_weight, exact: true, codewords }
+}
+
+/// Upper bound on the minimum distance of the code (precodes, postcodes) from the lightest codeword of
+///
+/// - the messages of weight one,
+/// - `trials` messages supported on at most `MAX_SUPPORT` positions that share rows of the first precode,
+///   with coefficients chosen to cancel on as many codeword positions as the support allows,
+/// - for msg_len up to `PRANGE_MAX_MSG_LEN`, another `trials` codewords vanishing on msg_len - 1 random positions
+///   (Prange's information set search).
+pub fn estimate_distance<F, R>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>], trials: usize, rng: &mut R) -> DistanceEstimate
+where
+    F: PrimeField + Num + MulAcc,
+    R: Rng,
+{
+    let msg_len = precodes[0].cols();
+    let code_len = codeword_length(precodes, postcodes);
+    let mut min_weight = code_len;
+    let mut codewords = 0;
+
+    for i in 0..msg_len {
+        min_weight = min_weight.min(weight(&encode_msg(&unit::<F>(i, msg_len), precodes, postcodes)));
+        codewords += 1;
+    }
+
+    // message positions sharing a row of the first precode can cancel each other in its output
+    let precode = &precodes[0];
+    let mut col_rows = vec![Vec::new(); msg_len];
+    let mut row_cols = vec![Vec::new(); precode.rows()];
+    for (outer, vec) in precode.outer_iterator().enumerate() {
+        for (inner, _) in vec.iter() {
+            let (row, col) = if precode.is_csc() { (inner, outer) } else { (outer, inner) };
+            col_rows[col].push(row);
+            row_cols[row].push(col);
+        }
+    }
+
+    for _ in 0..trials {
+        let size = rng.gen_range(2..=MAX_SUPPORT.min(msg_len).max(2));
+        let mut support = vec![rng.gen_range(0..msg_len)];
+        // grow the support along shared rows, giving up after a few misses
+        for _ in 0..(4 * size) {
+            if support.len() >= size.min(msg_len) {
+                break;
+            }
+            let col = *support.choose(rng).unwrap();
+            let next = match col_rows[col].choose(rng).and_then(|&row| row_cols[row].choose(rng)) {
+                Some(&next) => next,
+                None => rng.gen_range(0..msg_len),
+            };
+            if !support.contains(&next) {
+                support.push(next);
+            }
+        }
+        if support.len() < 2 {
+            continue;
+        }
+
+        let columns = support.iter().map(|&i| encode_msg(&unit::<F>(i, msg_len), precodes, postcodes)).collect::<Vec<_>>();
+        // positions outside the message where at least two columns could cancel
+        let candidates = (msg_len..code_len)
+            .filter(|&j| columns.iter().filter(|column| !bool::from(<F as Field>::is_zero(&column[j]))).count() >= 2)
+            .collect::<Vec<_>>();
+        let zero_no = (support.len() - 1).min(candidates.len());
+        let rows = sample(rng, candidates.len(), zero_no)
+            .into_iter()
+            .map(|j| columns.iter().map(|column| column[candidates[j]]).collect())
+            .collect();
+        if let Some(coefs) = null_vector(rows, support.len()) {
+            min_weight = min_weight.min(weight(&combine(&columns, &coefs)));
+            codewords += 1;
+        }
+    }
+
+    if msg_len <= PRANGE_MAX_MSG_LEN {
+        let columns = (0..msg_len).map(|i| encode_msg(&unit::<F>(i, msg_len), precodes, postcodes)).collect::<Vec<_>>();
+        for _ in 0..trials {
+            let rows = sample(rng, code_len, msg_len - 1)
+                .into_iter()
+                .map(|j| columns.iter().map(|column| column[j]).collect())
+                .collect();
+            if let Some(msg) = null_vector(rows, msg_len) {
+                min_weight = min_weight.min(weight(&combine(&columns, &msg)));
+                codewords += 1;
+            }
+        }
+    }
+
+    DistanceEstimate { msg_len, code_len, min_weight, exact: false, codewords }
+}
+
+/// Distance of the code generated from `seed`: exact if that takes at most max_subsets codewords, estimated with
+/// `trials` otherwise
+pub fn seed_distance<F, R>(code: &CodeParams, msg_len: usize, seed: u64, trials: usize, max_subsets: usize, rng: &mut R) -> DistanceEstimate
+where
+    F: PrimeField + Num + MulAcc,
+    R: Rng,
+{
+    let (precodes, postcodes) = generate_from::<F>(code, msg_len, seed);
+    match exact_distance(&precodes, &postcodes, max_subsets) {
+        Some(estimate) => estimate,
+        None => estimate_distance(&precodes, &postcodes, trials, rng),
+    }
+}
+
+/// Seeds whose code has a codeword lighter than code.dist() * code_len, with the lightest codeword found
+pub fn bad_seeds<F, R>(code: &CodeParams, msg_len: usize, seeds: Range<u64>, trials: usize, max_subsets: usize, rng: &mut R) -> Vec<(u64, DistanceEstimate)>
+where
+    F: PrimeField + Num + MulAcc,
+    R: Rng,
+{
+    seeds
+        .map(|seed| (seed, seed_distance::<F, R>(code, msg_len, seed, trials, max_subsets, rng)))
+        .filter(|(_, estimate)| estimate.relative() < code.dist())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use crate::codegen::generate;
+    use crate::codespec::Code6;
+    use crate::codespec::CodeSpecification;
+    use crate::fieldspec::ft255::Ft255;
+
+    #[test]
+    fn exact_and_estimate() {
+        let mut rng = ChaCha20Rng::seed_from_u64(0);
+        // a small base length keeps the exhaustive search short
+        let code = CodeParams { baselen: 4, ..Code6::code_params() };
+        for msg_len in [6, 8] {
+            let (precodes, postcodes) = generate_from::<Ft255>(&code, msg_len, 0);
+            let exact = exact_distance(&precodes, &postcodes, 1 << 16).unwrap();
+            let estimate = estimate_distance(&precodes, &postcodes, 20, &mut rng);
+            assert!(exact.min_weight >= 1 && exact.min_weight <= estimate.min_weight);
+            assert!(exact.min_weight <= exact.code_len - msg_len + 1);
+        }
+
+        let (precodes, postcodes) = generate::<Ft255, Code6>(100, 0);
+        assert_eq!(exact_distance(&precodes, &postcodes, 1 << 16), None);
+        assert!(bad_seeds::<Ft255, _>(&Code6::code_params(), 100, 0..2, 20, 1 << 16, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn flags_bad_code() {
+        let mut rng = ChaCha20Rng::seed_from_u64(1);
+        let (mut precodes, postcodes) = generate::<Ft255, Code6>(100, 0);
+        // without its entries in the first precode, message position 0 is a codeword of weight one
+        let precode = &precodes[0];
+        assert!(precode.is_csc());
+        let skip = precode.indptr().index(1);
+        let indptr = precode.indptr().as_slice().unwrap().iter().map(|&i| if i == 0 { 0 } else { i - skip }).collect();
+        let indices = precode.indices()[skip..].to_vec();
+        let data = precode.data()[skip..].to_vec();
+        precodes[0] = CsMat::new_csc(precode.shape(), indptr, indices, data);
+
+        let estimate = estimate_distance(&precodes, &postcodes, 20, &mut rng);
+        assert_eq!(estimate.min_weight, 1);
+        assert!(estimate.relative() < Code6::dist());
+    }
+}
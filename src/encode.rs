@@ -1,3 +1,4 @@
+use std::time::Duration;
 use std::time::Instant;
 use sprs::CsMat;
 use sprs::MulAcc;
@@ -6,12 +7,21 @@ use ff::PrimeField;
 use ndarray::ArrayView;
 use ndarray::linalg::Dot;
 use num_traits::Num;
-use rand::thread_rng;
 use rand::seq::SliceRandom;
+use rayon::prelude::*;
+use rand::SeedableRng;
+use rand::distributions::Uniform;
+use rand_chacha::ChaCha20Rng;
+use crate::codegen::gen_column;
 use crate::codegen::generate;
+use crate::codegen::generate_from;
 use crate::codegen::generate_rev;
-use crate::helper::degree_bound;
+use crate::codegen::generated_dims;
+use crate::codegen::generated_length;
+use crate::codespec::CodeParams;
 use crate::codespec::CodeSpecification;
+use crate::helper::next_pow_2;
+use crate::linear_code::LinearCode;
 
 // given a set of precodes and postcodes, output length of codeword
 pub fn codeword_length<F>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> usize
@@ -92,6 +102,375 @@ where
     assert_eq!(out_start, xi.as_mut().len());
 }
 
+// a sparse matrix in row-major order, so that every output symbol is a single sparse dot product
+struct CsrKernel<F> {
+    rows: usize,
+    cols: usize,
+    indptr: Vec<usize>,
+    indices: Vec<usize>,
+    data: Vec<F>,
+}
+
+impl<F> CsrKernel<F>
+where
+    F: PrimeField,
+{
+    fn new(matrix: &CsMat<F>) -> Self {
+        let (rows, cols) = matrix.shape();
+        let (indptr, indices, data) = matrix.to_csr().into_raw_storage();
+        CsrKernel { rows, cols, indptr, indices, data }
+    }
+
+    // xo = M xi, overwriting xo, for blocks holding `width` vectors symbol by symbol: the ith row of a block
+    // is xi[i * width..(i + 1) * width], so every nonzero entry is applied to width columns at once
+    fn mul(&self, xi: &[F], xo: &mut [F], width: usize) {
+        debug_assert_eq!(xi.len(), self.cols * width);
+        debug_assert_eq!(xo.len(), self.rows * width);
+        for (out_row, range) in xo.chunks_exact_mut(width).zip(self.indptr.windows(2)) {
+            out_row.fill(<F as Field>::zero());
+            for (&j, &a) in self.indices[range[0]..range[1]].iter().zip(self.data[range[0]..range[1]].iter()) {
+                for (x, &y) in out_row.iter_mut().zip(xi[(j * width)..((j + 1) * width)].iter()) {
+                    *x += a * y;
+                }
+            }
+        }
+    }
+}
+
+/// The matrices of a code in CSR order with the Reed-Solomon twiddles worked out once,
+/// so that `encode_batch` writes every product straight into the codewords
+pub struct EncodePlan<F> {
+    precodes: Vec<CsrKernel<F>>,
+    postcodes: Vec<CsrKernel<F>>,
+    code_len: usize,
+    /// size and twiddles of the base case NTT, None for the Vandermonde fallback
+    ntt: Option<(usize, Vec<F>)>,
+}
+
+impl<F> EncodePlan<F>
+where
+    F: PrimeField,
+{
+    pub fn new(precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> Self {
+        let code_len = codeword_length(precodes, postcodes);
+        let base_len = precodes.last().unwrap().rows();
+        let rs_len = postcodes.last().unwrap().cols();
+        let ntt = ntt_size::<F>(base_len, rs_len).map(|n| (n, ntt_twiddles(n, root_of_unity::<F>(n).unwrap())));
+        EncodePlan {
+            precodes: precodes.iter().map(CsrKernel::new).collect(),
+            postcodes: postcodes.iter().map(CsrKernel::new).collect(),
+            code_len,
+            ntt,
+        }
+    }
+
+    pub fn message_len(&self) -> usize {
+        self.precodes[0].cols
+    }
+
+    pub fn codeword_len(&self) -> usize {
+        self.code_len
+    }
+
+    /// symbols of scratch space `encode_batch` uses for `width` codewords: the input of the base case and the NTT buffer
+    pub fn scratch_len(&self, width: usize) -> usize {
+        self.precodes.last().unwrap().rows * width + self.ntt.as_ref().map_or(0, |(n, _)| *n)
+    }
+}
+
+/// `encode` without allocating: products go straight into xi and the base case runs in `scratch`,
+/// which keeps its capacity across calls
+pub fn encode_with<F>(xi: &mut [F], plan: &EncodePlan<F>, scratch: &mut Vec<F>)
+where
+    F: PrimeField,
+{
+    encode_batch(xi, 1, plan, scratch);
+}
+
+/// `encode_with` for `width` codewords at once, stored symbol by symbol: xi[i * width + c] is symbol i of codeword c.
+/// Every nonzero matrix entry is applied to a row of width symbols, so the matrices are walked once per block
+/// instead of once per codeword
+pub fn encode_batch<F>(xi: &mut [F], width: usize, plan: &EncodePlan<F>, scratch: &mut Vec<F>)
+where
+    F: PrimeField,
+{
+    assert!(width > 0);
+    assert_eq!(xi.len(), plan.code_len * width);
+    let w = width;
+    let levels = plan.precodes.len();
+    let base_len = plan.precodes[levels - 1].rows;
+    let rs_len = plan.postcodes[levels - 1].cols;
+    scratch.clear();
+    scratch.resize(plan.scratch_len(w), <F as Field>::zero());
+    let (base, buf) = scratch.split_at_mut(base_len * w);
+
+    // compute precodes all the way down, the last one into scratch
+    let mut in_start = 0usize;
+    for precode in plan.precodes.iter().take(levels - 1) {
+        let in_end = in_start + precode.cols;
+        let (in_arr, out_arr) = xi.split_at_mut(in_end * w);
+        precode.mul(&in_arr[(in_start * w)..], &mut out_arr[..(precode.rows * w)], w);
+        in_start = in_end;
+    }
+    let in_end = in_start + plan.precodes[levels - 1].cols;
+    plan.precodes[levels - 1].mul(&xi[(in_start * w)..(in_end * w)], base, w);
+
+    // base-case code: Reed-Solomon
+    let out_end = in_end + rs_len;
+    reed_solomon_batch(base, &mut xi[(in_end * w)..(out_end * w)], w, plan.ntt.as_ref(), buf);
+
+    // postcodes back up
+    let (mut in_start, mut out_start) = (in_end + base_len, out_end);
+    for (precode, postcode) in plan.precodes.iter().rev().zip(plan.postcodes.iter().rev()) {
+        in_start -= precode.rows;
+        let (in_arr, out_arr) = xi.split_at_mut(out_start * w);
+        postcode.mul(&in_arr[(in_start * w)..], &mut out_arr[..(postcode.rows * w)], w);
+        out_start += postcode.rows;
+    }
+    debug_assert_eq!(out_start * w, xi.len());
+}
+
+// evaluate the Reed-Solomon code on a block of base case inputs into rs, both stored symbol by symbol,
+// one column at a time through the NTT buffer or with `horner` a row of the block at a time
+fn reed_solomon_batch<F>(base: &[F], rs: &mut [F], w: usize, ntt: Option<&(usize, Vec<F>)>, buf: &mut [F])
+where
+    F: PrimeField,
+{
+    match ntt {
+        Some((_, twiddles)) => {
+            for c in 0..w {
+                buf.fill(<F as Field>::zero());
+                for (b, row) in buf.iter_mut().zip(base.chunks_exact(w)) {
+                    *b = row[c];
+                }
+                ntt_with(buf, twiddles);
+                for (row, &b) in rs.chunks_exact_mut(w).zip(buf.iter()) {
+                    row[c] = b;
+                }
+            }
+        }
+        None => horner(base, rs, w),
+    }
+}
+
+// one matrix of a `LazyPlan`: the dimensions passed to `codegen::gen_matrix` and where in its
+// stream of the seed the matrix starts
+struct LazyMatrix {
+    dims: (usize, usize, usize),
+    stream: u64,
+    word_pos: u128,
+}
+
+impl LazyMatrix {
+    // xo = M xi for blocks of `width` vectors as in `CsrKernel::mul`, regenerating M a column at a time;
+    // every column is scattered into the rows of xo it has nonzero entries in
+    fn mul<F>(&self, seed: u64, xi: &[F], xo: &mut [F], width: usize)
+    where
+        F: PrimeField,
+    {
+        let (n, m, d) = self.dims;
+        debug_assert_eq!(xi.len(), n * width);
+        debug_assert_eq!(xo.len(), m * width);
+        xo.fill(<F as Field>::zero());
+        let (dist, mut rng) = (Uniform::new(0, m.max(1)), self.rng(seed));
+        let (mut rows, mut vals) = (Vec::with_capacity(d), Vec::<F>::with_capacity(d));
+        for x in xi.chunks_exact(width) {
+            gen_column(&dist, d, &mut rng, &mut rows, &mut vals);
+            for (&i, &a) in rows.iter().zip(vals.iter()) {
+                for (y, &b) in xo[(i * width)..((i + 1) * width)].iter_mut().zip(x.iter()) {
+                    *y += a * b;
+                }
+            }
+        }
+    }
+
+    // xo += M^T xi for single vectors, a column of M per symbol of xo
+    fn mul_transposed<F>(&self, seed: u64, xi: &[F], xo: &mut [F])
+    where
+        F: PrimeField,
+    {
+        let (n, m, d) = self.dims;
+        debug_assert_eq!(xi.len(), m);
+        debug_assert_eq!(xo.len(), n);
+        let (dist, mut rng) = (Uniform::new(0, m.max(1)), self.rng(seed));
+        let (mut rows, mut vals) = (Vec::with_capacity(d), Vec::<F>::with_capacity(d));
+        for y in xo.iter_mut() {
+            gen_column(&dist, d, &mut rng, &mut rows, &mut vals);
+            for (&i, &a) in rows.iter().zip(vals.iter()) {
+                *y += a * xi[i];
+            }
+        }
+    }
+
+    fn rng(&self, seed: u64) -> ChaCha20Rng {
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(self.word_pos);
+        rng
+    }
+}
+
+/// The code `codegen::generate_from` would generate, kept as its dimensions and seed only: every matrix is
+/// regenerated column by column from its ChaCha20 stream while encoding, so memory stays constant in the code size
+/// at the cost of sampling the matrices again for every block
+pub struct LazyPlan<F> {
+    seed: u64,
+    /// precodes and postcodes of every level, empty for the identity plus Reed-Solomon code of short messages
+    precodes: Vec<LazyMatrix>,
+    postcodes: Vec<LazyMatrix>,
+    msg_len: usize,
+    code_len: usize,
+    base_len: usize,
+    rs_len: usize,
+    ntt: Option<(usize, Vec<F>)>,
+}
+
+impl<F> LazyPlan<F>
+where
+    F: PrimeField,
+{
+    pub fn new(code: &CodeParams, msg_len: usize, seed: u64) -> Self {
+        let code_len = generated_length(code, msg_len);
+        let (precodes, postcodes) = match generated_dims::<F>(code, msg_len) {
+            Some((pre_dims, post_dims)) => pre_dims
+                .par_iter()
+                .zip(&post_dims[..])
+                .enumerate()
+                .map(|(i, (&pre, &post))| {
+                    // level i samples its precode and then its postcode from stream i, skip over the precode once
+                    let precode = LazyMatrix { dims: pre, stream: i as u64, word_pos: 0 };
+                    let mut rng = precode.rng(seed);
+                    let (n, m, d) = pre;
+                    let dist = Uniform::new(0, m);
+                    let (mut rows, mut vals) = (Vec::with_capacity(d), Vec::<F>::with_capacity(d));
+                    for _ in 0..n {
+                        gen_column(&dist, d, &mut rng, &mut rows, &mut vals);
+                    }
+                    let postcode = LazyMatrix { dims: post, stream: i as u64, word_pos: rng.get_word_pos() };
+                    (precode, postcode)
+                })
+                .unzip(),
+            None => (Vec::new(), Vec::new()),
+        };
+        let base_len = precodes.last().map_or(msg_len, |pc: &LazyMatrix| pc.dims.1);
+        let rs_len = postcodes.last().map_or(code_len - msg_len, |pc: &LazyMatrix| pc.dims.0);
+        let ntt = ntt_size::<F>(base_len, rs_len).map(|n| (n, ntt_twiddles(n, root_of_unity::<F>(n).unwrap())));
+        LazyPlan { seed, precodes, postcodes, msg_len, code_len, base_len, rs_len, ntt }
+    }
+
+    pub fn message_len(&self) -> usize {
+        self.msg_len
+    }
+
+    pub fn codeword_len(&self) -> usize {
+        self.code_len
+    }
+
+    /// symbols of scratch space `encode_lazy_batch` uses for `width` codewords, as `EncodePlan::scratch_len`
+    pub fn scratch_len(&self, width: usize) -> usize {
+        self.base_len * width + self.ntt.as_ref().map_or(0, |(n, _)| *n)
+    }
+
+    /// bytes this plan keeps between calls, the NTT twiddles dominate
+    pub fn heap_bytes(&self) -> usize {
+        (self.precodes.len() + self.postcodes.len()) * std::mem::size_of::<LazyMatrix>()
+            + self.ntt.as_ref().map_or(0, |(_, twiddles)| twiddles.len() * std::mem::size_of::<F>())
+    }
+}
+
+/// `encode_batch` for a `LazyPlan`, with the same layout and results as the generated matrices
+pub fn encode_lazy_batch<F>(xi: &mut [F], width: usize, plan: &LazyPlan<F>, scratch: &mut Vec<F>)
+where
+    F: PrimeField,
+{
+    assert!(width > 0);
+    assert_eq!(xi.len(), plan.code_len * width);
+    let w = width;
+    let seed = plan.seed;
+    scratch.clear();
+    scratch.resize(plan.scratch_len(w), <F as Field>::zero());
+    let (base, buf) = scratch.split_at_mut(plan.base_len * w);
+
+    // compute precodes all the way down, the last one into scratch; short messages go into it unchanged
+    let mut in_start = 0usize;
+    if let Some((last, precodes)) = plan.precodes.split_last() {
+        for precode in precodes {
+            let in_end = in_start + precode.dims.0;
+            let (in_arr, out_arr) = xi.split_at_mut(in_end * w);
+            precode.mul(seed, &in_arr[(in_start * w)..], &mut out_arr[..(precode.dims.1 * w)], w);
+            in_start = in_end;
+        }
+        let in_end = in_start + last.dims.0;
+        last.mul(seed, &xi[(in_start * w)..(in_end * w)], base, w);
+        in_start = in_end;
+    }else{
+        base.copy_from_slice(&xi[..(plan.msg_len * w)]);
+        in_start = plan.msg_len;
+    }
+
+    // base-case code: Reed-Solomon
+    let in_end = in_start;
+    let out_end = in_end + plan.rs_len;
+    reed_solomon_batch(base, &mut xi[(in_end * w)..(out_end * w)], w, plan.ntt.as_ref(), buf);
+
+    // postcodes back up
+    let (mut in_start, mut out_start) = (in_end + plan.precodes.last().map_or(0, |pc| pc.dims.1), out_end);
+    for (precode, postcode) in plan.precodes.iter().rev().zip(plan.postcodes.iter().rev()) {
+        in_start -= precode.dims.1;
+        let (in_arr, out_arr) = xi.split_at_mut(out_start * w);
+        postcode.mul(seed, &in_arr[(in_start * w)..((in_start + postcode.dims.0) * w)], &mut out_arr[..(postcode.dims.1 * w)], w);
+        out_start += postcode.dims.1;
+    }
+    debug_assert_eq!(out_start * w, xi.len());
+}
+
+/// multiply with the transposed generator matrix of a `LazyPlan`, see `LinearCode::encode_transposed`
+pub fn encode_lazy_transposed<F>(xi: &[F], xo: &mut [F], plan: &LazyPlan<F>)
+where
+    F: PrimeField,
+{
+    assert_eq!(xi.len(), plan.code_len);
+    assert_eq!(xo.len(), plan.msg_len);
+    let seed = plan.seed;
+    let levels = plan.precodes.len();
+
+    // same layout as `encode_lazy_batch`: precode i reads starts[i].., postcode i reads ins[i].. and writes outs[i]..
+    let mut starts = vec![0usize];
+    for precode in plan.precodes.iter() {
+        starts.push(starts.last().unwrap() + precode.dims.0);
+    }
+    let rs_start = if levels == 0 { plan.msg_len } else { starts[levels] };
+    let mut ins = vec![0usize; levels];
+    let mut outs = vec![0usize; levels];
+    let mut out_start = rs_start + plan.rs_len;
+    for i in (0..levels).rev() {
+        ins[i] = if i == levels - 1 { rs_start } else { starts[i + 1] };
+        outs[i] = out_start;
+        out_start += plan.postcodes[i].dims.1;
+    }
+
+    // walk the encoder backwards, pushing every output back onto the inputs it was computed from
+    let mut y = xi.to_vec();
+    for (i, postcode) in plan.postcodes.iter().enumerate() {
+        let (lo, hi) = y.split_at_mut(outs[i]);
+        postcode.mul_transposed(seed, &hi[..postcode.dims.1], &mut lo[ins[i]..(ins[i] + postcode.dims.0)]);
+    }
+
+    let mut r = vec![<F as Field>::zero(); plan.base_len];
+    reed_solomon_rev(&y[rs_start..(rs_start + plan.rs_len)], &mut r);
+    if levels == 0 {
+        for (x, b) in y[..plan.msg_len].iter_mut().zip(r.iter()) {
+            *x += b;
+        }
+    }
+    for i in (0..levels).rev() {
+        let (lo, hi) = y.split_at_mut(starts[i + 1]);
+        let back = if i == levels - 1 { &r[..] } else { &hi[..plan.precodes[i].dims.1] };
+        plan.precodes[i].mul_transposed(seed, back, &mut lo[starts[i]..]);
+    }
+    xo.copy_from_slice(&y[..xo.len()]);
+}
+
 /// reverse-encode a vector given a code of corresponding length
 pub fn encode_rev<F, T>(mut data: T, precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> usize
 where
@@ -135,31 +514,21 @@ where
 
     // base-case code: Reed-Solomon
     let base_len_from = v_start - z_start;
-    let base_len_to = postcodes.last().unwrap().cols();
+    // back to the input of the base case, the output of the last (transposed) precode
+    let base_len_to = precodes.last().unwrap().cols();
     let vandermonde_start = z_start;
     // println!("{} {} vandermonde_start: {}", base_len_from, base_len_to, vandermonde_start);
     let mut vandermonde_result = Vec::<F>::new();
     vandermonde_result.resize_with(base_len_to, || <F as Field>::zero());
-    let mut val_i = <F as Field>::zero();
-    for i in 0..base_len_from { // n
-        val_i = val_i.add(<F as Field>::one());
-        let mut val = <F as Field>::one();
-        for j in 0..base_len_to { // k
-            vandermonde_result[j] = vandermonde_result[j].add(
-                val.mul(data_mut[vandermonde_start+i])
-            );
-            val = val.mul(val_i);
-        }
-    }
-    for i in 0..base_len_to {
-        data_mut[vandermonde_start+i] = vandermonde_result[i];
-    }
+    reed_solomon_rev(&data_mut[vandermonde_start..(vandermonde_start+base_len_from)], &mut vandermonde_result);
 
     let mut result_len: usize = 0;
-    for (precode, (x_start, z_start, v_start)) in precodes.iter().rev().zip(xzv_stack.iter().rev()) {
+    for (k, (precode, (x_start, z_start, _v_start))) in precodes.iter().rev().zip(xzv_stack.iter().rev()).enumerate() {
         // println!("{} {} {}", x_start, z_start, v_start);
         let z_len = precode.cols();
-        let z_reversed = precode.dot(&ArrayView::from(&data_mut[*z_start..(z_start+z_len)]));
+        // the last precode reads the base case, which may be longer than the space it came from
+        let z = if k == 0 { &vandermonde_result[..] } else { &data_mut[*z_start..(z_start+z_len)] };
+        let z_reversed = precode.dot(&ArrayView::from(z));
         let x_len = precode.rows();
         for i in 0..x_len {
             data_mut[x_start + i] = data_mut[x_start + i].add(z_reversed[i]);
@@ -169,15 +538,16 @@ where
     return result_len;
 }
 
+/// check that the reverse code of `generate_rev` is the transpose of `generate` for 1024 message symbols,
+/// `inspect::generator_matrix` gives the generator matrix of any code
 pub fn test_reverse_encoding<F, C>()
 where
     F: PrimeField + Num + MulAcc,
     C: CodeSpecification,
 {
     let msg_len = 1024;
-    let code_len = 1762;
-    
     let (precodes, postcodes) = generate::<F, C>(msg_len, 0);
+    let code_len = codeword_length(&precodes, &postcodes);
     let mut matrix1 = Vec::<Vec<F>>::new();
     for i in 0..msg_len {
         let mut codeword = Vec::<F>::new();
@@ -204,43 +574,211 @@ where
     }
 }
 
-// Compute Reed-Solomon encoding using Vandermonde matrix
-fn reed_solomon<F>(xi: &[F], xo: &mut [F])
+/// primitive n-th root of unity for a power of two n, None if the 2-adic subgroup of F is too small
+pub fn root_of_unity<F>(n: usize) -> Option<F>
+where
+    F: PrimeField,
+{
+    assert!(n.is_power_of_two());
+    let log_n = n.trailing_zeros();
+    if log_n > F::S {
+        return None;
+    }
+    // F::root_of_unity() has order 2^S
+    let mut w = F::root_of_unity();
+    for _ in log_n..F::S {
+        w = w.square();
+    }
+    Some(w)
+}
+
+/// size of the NTT evaluating a Reed-Solomon code from msg_len to code_len symbols,
+/// None if F has no multiplicative subgroup of that size and the Vandermonde fallback is used
+pub fn ntt_size<F>(msg_len: usize, code_len: usize) -> Option<usize>
+where
+    F: PrimeField,
+{
+    let n = next_pow_2(msg_len.max(code_len));
+    root_of_unity::<F>(n).map(|_| n)
+}
+
+/// evaluation points of the Reed-Solomon code from msg_len to code_len symbols: the first code_len powers
+/// of a root of unity of order `ntt_size`, or 1, 2, ..., code_len without a large enough subgroup
+pub fn reed_solomon_points<F>(msg_len: usize, code_len: usize) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let mut points = Vec::with_capacity(code_len);
+    let mut x = <F as Field>::one();
+    match ntt_size::<F>(msg_len, code_len) {
+        Some(n) => {
+            let w = root_of_unity::<F>(n).unwrap();
+            for _ in 0..code_len {
+                points.push(x);
+                x *= w;
+            }
+        }
+        None => {
+            for _ in 0..code_len {
+                points.push(x);
+                x += <F as Field>::one();
+            }
+        }
+    }
+    points
+}
+
+/// in-place radix-2 NTT: a[i] becomes sum_j a[j] w^(ij), w has to be a primitive a.len()-th root of unity
+pub fn ntt<F>(a: &mut [F], w: F)
 where
     F: PrimeField,
 {
+    assert!(a.len().is_power_of_two());
+    ntt_with(a, &ntt_twiddles(a.len(), w));
+}
+
+// twiddles w^0, ..., w^(n/2 - 1) of an NTT of size n
+fn ntt_twiddles<F>(n: usize, w: F) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let mut twiddles = Vec::with_capacity(n / 2);
     let mut x = <F as Field>::one();
-    for r in xo.as_mut().iter_mut() {
+    for _ in 0..(n / 2) {
+        twiddles.push(x);
+        x *= w;
+    }
+    twiddles
+}
+
+// `ntt` with the twiddles of `ntt_twiddles(a.len(), w)`
+fn ntt_with<F>(a: &mut [F], twiddles: &[F])
+where
+    F: PrimeField,
+{
+    let n = a.len();
+    assert!(n.is_power_of_two());
+    assert_eq!(twiddles.len(), n / 2);
+    let log_n = n.trailing_zeros();
+    if n == 1 {
+        return;
+    }
+
+    // bit-reversal permutation
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - log_n);
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+
+    let mut half = 1;
+    while half < n {
+        let step = n / (2 * half);
+        let butterflies = |block: &mut [F]| {
+            let (lo, hi) = block.split_at_mut(half);
+            for (k, (u, v)) in lo.iter_mut().zip(hi.iter_mut()).enumerate() {
+                let t = *v * twiddles[k * step];
+                *v = *u - t;
+                *u += t;
+            }
+        };
+        // only worth spreading over threads for large transforms
+        if n >= 1 << 12 {
+            a.par_chunks_mut(2 * half).for_each(butterflies);
+        }else{
+            a.chunks_mut(2 * half).for_each(butterflies);
+        }
+        half *= 2;
+    }
+}
+
+// evaluate the `width` polynomials with coefficients xi, stored symbol by symbol, at 1, 2, ..., xo.len() / width
+// into xo, a row of xo per point: the points of `reed_solomon_points` without a large enough subgroup
+fn horner<F>(xi: &[F], xo: &mut [F], width: usize)
+where
+    F: PrimeField,
+{
+    let eval = |(i, row): (usize, &mut [F])| {
+        let x = F::from(i as u64 + 1);
+        row.fill(<F as Field>::zero());
+        for coefs in xi.chunks_exact(width).rev() {
+            for (r, &c) in row.iter_mut().zip(coefs.iter()) {
+                *r = *r * x + c;
+            }
+        }
+    };
+    // only worth spreading over threads for large evaluations
+    if xo.len() * (xi.len() / width) >= 1 << 16 {
+        xo.par_chunks_mut(width).enumerate().for_each(eval);
+    }else{
+        xo.chunks_mut(width).enumerate().for_each(eval);
+    }
+}
+
+/// Reed-Solomon encoding of the polynomial with coefficients xi at the first xo.len() points of `reed_solomon_points`,
+/// with an NTT if the field allows it and Horner's rule otherwise
+pub fn reed_solomon<F>(xi: &[F], xo: &mut [F])
+where
+    F: PrimeField,
+{
+    if let Some(n) = ntt_size::<F>(xi.len(), xo.len()) {
+        let mut a = vec![<F as Field>::zero(); n];
+        a[..xi.len()].copy_from_slice(xi);
+        ntt(&mut a, root_of_unity::<F>(n).unwrap());
+        xo.copy_from_slice(&a[..xo.len()]);
+        return;
+    }
+    horner(xi, xo, 1);
+}
+
+/// transpose of `reed_solomon` from xo.len() to xi.len() symbols: xo[j] = sum_i xi[i] x_i^j
+pub fn reed_solomon_rev<F>(xi: &[F], xo: &mut [F])
+where
+    F: PrimeField,
+{
+    // the NTT matrix is symmetric, so its transpose evaluates at the same points
+    if ntt_size::<F>(xo.len(), xi.len()).is_some() {
+        reed_solomon(xi, xo);
+        return;
+    }
+
+    for r in xo.iter_mut() {
         *r = <F as Field>::zero();
-        for j in (0..xi.len()).rev() {
-            *r *= x;
-            *r += xi[j];
+    }
+    let mut val_i = <F as Field>::zero();
+    for &x in xi.iter() {
+        val_i += <F as Field>::one();
+        let mut val = <F as Field>::one();
+        for r in xo.iter_mut() {
+            *r += val * x;
+            val *= val_i;
         }
-        x += <F as Field>::one();
     }
 }
 
-pub fn encode_zk<F, C>(
-    mut msg: &mut Vec<F>, 
-    precodes: &[CsMat<F>], 
-    postcodes: &[CsMat<F>],
+/// The planned zero-knowledge encoding, which is not zero-knowledge yet: msg is encoded with `code` and left
+/// that way. The redistribution over a random graph of `degree`, the randomization and the reverse encoding
+/// are computed on a copy and thrown away, so that benchmarks include their cost, and step 3 is unfinished.
+pub fn encode_zk<F>(
+    msg: &mut [F],
+    code: &dyn LinearCode<F>,
     precodes_rev: &[CsMat<F>], 
     postcodes_rev: &[CsMat<F>],
     degree: usize
 )
 where
     F: PrimeField + Num + MulAcc,
-    C: CodeSpecification,
 {
-    encode(&mut msg, &precodes, &postcodes);
+    code.encode(msg);
 
-    let code_len = codeword_length(precodes, postcodes);
+    let code_len = code.codeword_len();
     
     // generate random graph
     let mut rng = rand::thread_rng();
     let mut permute: Vec<usize> = (0..code_len).collect();
     let mut graph = Vec::<Vec<usize>>::new();
-    graph.resize_with(code_len, || Vec::new());
+    graph.resize_with(code_len, Vec::new);
     for _ in 0..degree {
         permute.shuffle(&mut rng);
         for (i, j) in permute.iter().enumerate() {
@@ -279,12 +817,9 @@ where
             randomlized.push(result);
         }
     }
-    
-    // println!("{:?}", randomlized);
-    // println!("{:?}", redistributed);
 
     // TODO: step 3 reverse encoding
-    let resule_len = encode_rev::<F, _>(&mut randomlized, &precodes_rev, &postcodes_rev);
+    let resule_len = encode_rev::<F, _>(&mut randomlized, precodes_rev, postcodes_rev);
     randomlized.truncate(resule_len);
 }
 
@@ -304,7 +839,7 @@ where
     // generate random graph
     let mut permute: Vec<usize> = (0..code_len).collect();
     let mut graph = Vec::<Vec<usize>>::new();
-    graph.resize_with(code_len, || Vec::new());
+    graph.resize_with(code_len, Vec::new);
     for _ in 0..degree {
         permute.shuffle(&mut rng);
         for (i, j) in permute.iter().enumerate() {
@@ -346,4 +881,236 @@ where
     
     let end_time = Instant::now();
     println!("{}", end_time.duration_since(start_time).as_millis());
-}
\ No newline at end of file
+}
+/// Times of `encode_kernel_bench`
+pub struct EncodeTimings {
+    /// a fresh codeword per column encoded with `encode`
+    pub dot: Duration,
+    /// reused buffers and `encode_with`, one column at a time
+    pub kernel: Duration,
+    /// blocks of columns encoded with `encode_batch`
+    pub batch: Duration,
+}
+
+/// encode `columns` random messages as the commitments do, first the way they used to with a fresh codeword
+/// per column and `encode`, then with reused buffers and `encode_with`, then in blocks of `width` with `encode_batch`
+pub fn encode_kernel_bench<F>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>], columns: usize, width: usize) -> EncodeTimings
+where
+    F: PrimeField + Num + MulAcc,
+{
+    let mut rng = rand::thread_rng();
+    let plan = EncodePlan::new(precodes, postcodes);
+    let (msg_len, code_len) = (plan.message_len(), plan.codeword_len());
+    let msgs = (0..columns)
+        .map(|_| (0..msg_len).map(|_| F::random(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    // sum of all codewords, so that no loop can be optimized away and all have to agree
+    let start_time = Instant::now();
+    let mut sum_dot = <F as Field>::zero();
+    for msg in msgs.iter() {
+        let mut x = msg.to_vec();
+        x.resize(code_len, <F as Field>::zero());
+        encode(&mut x, precodes, postcodes);
+        sum_dot = x.iter().fold(sum_dot, |acc, &y| acc + y);
+    }
+    let dot = start_time.elapsed();
+
+    let start_time = Instant::now();
+    let mut sum_kernel = <F as Field>::zero();
+    let (mut x, mut scratch) = (Vec::with_capacity(code_len), Vec::new());
+    for msg in msgs.iter() {
+        x.clear();
+        x.extend_from_slice(msg);
+        x.resize(code_len, <F as Field>::zero());
+        encode_with(&mut x, &plan, &mut scratch);
+        sum_kernel = x.iter().fold(sum_kernel, |acc, &y| acc + y);
+    }
+    let kernel = start_time.elapsed();
+
+    let start_time = Instant::now();
+    let sum_batch = batch_sum(&msgs, width, code_len, |block, w| encode_batch(block, w, &plan, &mut scratch));
+    let batch = start_time.elapsed();
+
+    assert_eq!(sum_dot, sum_kernel);
+    assert_eq!(sum_dot, sum_batch);
+    EncodeTimings { dot, kernel, batch }
+}
+
+// encode msgs in blocks of width columns with `enc` and sum up all symbols of the codewords
+fn batch_sum<F, E>(msgs: &[Vec<F>], width: usize, code_len: usize, mut enc: E) -> F
+where
+    F: PrimeField,
+    E: FnMut(&mut [F], usize),
+{
+    let mut sum = <F as Field>::zero();
+    let mut block = Vec::with_capacity(code_len * width);
+    for chunk in msgs.chunks(width) {
+        let w = chunk.len();
+        block.clear();
+        block.resize(code_len * w, <F as Field>::zero());
+        for (c, msg) in chunk.iter().enumerate() {
+            for (i, &y) in msg.iter().enumerate() {
+                block[i * w + c] = y;
+            }
+        }
+        enc(&mut block, w);
+        sum = block.iter().fold(sum, |acc, &y| acc + y);
+    }
+    sum
+}
+
+/// Times and memory of `encode_lazy_bench`
+pub struct LazyTimings {
+    /// sampling the matrices and building the `EncodePlan`
+    pub generate: Duration,
+    /// blocks of columns encoded with `encode_batch`
+    pub batch: Duration,
+    /// building the `LazyPlan`, which samples the precodes once to find where the postcodes start
+    pub lazy_setup: Duration,
+    /// blocks of columns encoded with `encode_lazy_batch`
+    pub lazy: Duration,
+    /// bytes of the generated matrices and of the CSR copies in the `EncodePlan`
+    pub matrix_bytes: usize,
+    /// bytes the `LazyPlan` keeps
+    pub lazy_bytes: usize,
+}
+
+/// encode `columns` random messages in blocks of `width` with the generated matrices and with the same code
+/// sampled again from the seed for every block, to compare the time and memory of the two
+pub fn encode_lazy_bench<F>(code: &CodeParams, msg_len: usize, seed: u64, columns: usize, width: usize) -> LazyTimings
+where
+    F: PrimeField,
+{
+    let mut rng = rand::thread_rng();
+    let start_time = Instant::now();
+    let (precodes, postcodes) = generate_from::<F>(code, msg_len, seed);
+    let plan = EncodePlan::new(&precodes, &postcodes);
+    let generate = start_time.elapsed();
+    let code_len = plan.codeword_len();
+    // the CsMat and the CSR copy of every matrix store the same nonzero entries and indices
+    let matrix_bytes = precodes
+        .iter()
+        .chain(postcodes.iter())
+        .map(|pc| 2 * (pc.nnz() * (std::mem::size_of::<F>() + std::mem::size_of::<usize>()) + (pc.cols() + pc.rows() + 2) * std::mem::size_of::<usize>()))
+        .sum::<usize>();
+    drop((precodes, postcodes));
+    let msgs = (0..columns)
+        .map(|_| (0..msg_len).map(|_| F::random(&mut rng)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    let mut scratch = Vec::new();
+    let start_time = Instant::now();
+    let sum_batch = batch_sum(&msgs, width, code_len, |block, w| encode_batch(block, w, &plan, &mut scratch));
+    let batch = start_time.elapsed();
+
+    let start_time = Instant::now();
+    let lazy_plan = LazyPlan::<F>::new(code, msg_len, seed);
+    let lazy_setup = start_time.elapsed();
+    let start_time = Instant::now();
+    let sum_lazy = batch_sum(&msgs, width, code_len, |block, w| encode_lazy_batch(block, w, &lazy_plan, &mut scratch));
+    let lazy = start_time.elapsed();
+
+    assert_eq!(sum_batch, sum_lazy);
+    LazyTimings { generate, batch, lazy_setup, lazy, matrix_bytes, lazy_bytes: lazy_plan.heap_bytes() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_from;
+    use crate::codegen::generate_rev_from;
+    use crate::codespec::Code6;
+    use crate::codespec::CodeParams;
+    use crate::encode::codeword_length;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft255::Ft255;
+
+    fn vandermonde<F>(xi: &[F], points: &[F]) -> Vec<F>
+    where
+        F: PrimeField,
+    {
+        points
+            .iter()
+            .map(|&x| xi.iter().rev().fold(<F as Field>::zero(), |acc, &c| acc * x + c))
+            .collect()
+    }
+
+    #[test]
+    fn ntt_matches_vandermonde() {
+        let mut rng = rand::thread_rng();
+        for (msg_len, code_len) in [(1, 1), (5, 12), (16, 16), (20, 37), (700, 5000)] {
+            let xi = (0..msg_len).map(|_| Ft255::random(&mut rng)).collect::<Vec<_>>();
+            let mut xo = vec![<Ft255 as Field>::zero(); code_len];
+            reed_solomon(&xi, &mut xo);
+            assert!(ntt_size::<Ft255>(msg_len, code_len).is_some());
+            assert_eq!(xo, vandermonde(&xi, &reed_solomon_points::<Ft255>(msg_len, code_len)));
+        }
+
+        // p - 1 = 2 * (2^30 - 1), the fallback evaluates at 1, 2, 3, ...
+        assert_eq!(ntt_size::<Ft32>(2, 3), None);
+        let xi = (0..3).map(|_| Ft32::random(&mut rng)).collect::<Vec<_>>();
+        let mut xo = vec![<Ft32 as Field>::zero(); 7];
+        reed_solomon(&xi, &mut xo);
+        let points = (1..=7).map(Ft32::from).collect::<Vec<_>>();
+        assert_eq!(reed_solomon_points::<Ft32>(3, 7), points);
+        assert_eq!(xo, vandermonde(&xi, &points));
+    }
+
+    fn transposed<F>(msg_len: usize, code_len: usize)
+    where
+        F: PrimeField,
+    {
+        let unit = |i: usize, len: usize| (0..len).map(|j| if i == j { <F as Field>::one() } else { <F as Field>::zero() }).collect::<Vec<F>>();
+        for i in 0..msg_len {
+            let mut column = vec![<F as Field>::zero(); code_len];
+            reed_solomon(&unit(i, msg_len), &mut column);
+            for (j, &x) in column.iter().enumerate() {
+                let mut row = vec![<F as Field>::zero(); msg_len];
+                reed_solomon_rev(&unit(j, code_len), &mut row);
+                assert_eq!(row[i], x);
+            }
+        }
+    }
+
+    // encode_rev with the transposed code of length code_len is the transpose of encode
+    fn reverse_encoding<F>(code: &CodeParams, msg_len: usize)
+    where
+        F: PrimeField + Num + MulAcc,
+    {
+        let (precodes, postcodes) = generate_from::<F>(code, msg_len, 0);
+        let code_len = codeword_length(&precodes, &postcodes);
+        let (precodes_rev, postcodes_rev) = generate_rev_from::<F>(code, code_len, 0);
+        assert_eq!(precodes_rev[0].rows(), msg_len);
+        let unit = |i: usize, len: usize| (0..len).map(|j| if i == j { <F as Field>::one() } else { <F as Field>::zero() }).collect::<Vec<F>>();
+        let columns = (0..msg_len)
+            .map(|i| {
+                let mut x = unit(i, msg_len);
+                x.resize(code_len, <F as Field>::zero());
+                encode(&mut x, &precodes, &postcodes);
+                x
+            })
+            .collect::<Vec<_>>();
+        for j in 0..code_len {
+            let mut y = unit(j, code_len);
+            assert_eq!(encode_rev(&mut y, &precodes_rev, &postcodes_rev), msg_len);
+            for i in 0..msg_len {
+                assert_eq!(y[i], columns[i][j]);
+            }
+        }
+    }
+
+    #[test]
+    fn reverse_encoding_is_transpose() {
+        let code = CodeParams { baselen: 4, ..Code6::code_params() };
+        reverse_encoding::<Ft255>(&code, 40);
+        reverse_encoding::<Ft32>(&code, 40);
+        reverse_encoding::<Ft255>(&code, 3);
+    }
+
+    #[test]
+    fn reverse_is_transpose() {
+        transposed::<Ft255>(6, 13);
+        transposed::<Ft32>(6, 13);
+    }
+}
@@ -0,0 +1,402 @@
+use std::fmt::Debug;
+use std::iter::repeat_n;
+use std::marker::PhantomData;
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::ops::Mul;
+use std::ops::MulAssign;
+use std::ops::Neg;
+use std::ops::Sub;
+use std::ops::SubAssign;
+use ff::Field;
+use ff::PrimeField;
+use rand::RngCore;
+use subtle::Choice;
+use subtle::ConditionallySelectable;
+use subtle::ConstantTimeEq;
+use subtle::CtOption;
+
+/// A field containing F whose elements are DEGREE coefficients over F. Verifier challenges are drawn from it while
+/// committed data stays in F; vectors over the extension are stored as F vectors holding the DEGREE coefficients
+/// of every symbol next to each other, the layout of a block of DEGREE codewords in `LinearCode::encode_batch`
+pub trait ExtensionField<F>: Field
+where
+    F: PrimeField,
+{
+    const DEGREE: usize;
+
+    fn from_base(x: F) -> Self;
+
+    /// self * x for x in the base field, cheaper than a multiplication in the extension
+    fn mul_base(&self, x: &F) -> Self;
+
+    /// the element with coefficients xs[0..DEGREE]
+    fn from_coeffs(xs: &[F]) -> Self;
+
+    /// write the DEGREE coefficients to out[0..DEGREE], the inverse of `from_coeffs`
+    fn write_coeffs(&self, out: &mut [F]);
+}
+
+/// every field is an extension of degree 1 of itself
+impl<F> ExtensionField<F> for F
+where
+    F: PrimeField,
+{
+    const DEGREE: usize = 1;
+
+    fn from_base(x: F) -> Self {
+        x
+    }
+
+    fn mul_base(&self, x: &F) -> Self {
+        *self * x
+    }
+
+    fn from_coeffs(xs: &[F]) -> Self {
+        xs[0]
+    }
+
+    fn write_coeffs(&self, out: &mut [F]) {
+        out[0] = *self;
+    }
+}
+
+/// read xs as a vector over E, see `ExtensionField`
+pub fn from_coeff_vec<F, E>(xs: &[F]) -> Vec<E>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+{
+    assert!(xs.len().is_multiple_of(E::DEGREE));
+    xs.chunks(E::DEGREE).map(E::from_coeffs).collect()
+}
+
+/// store xs as a vector over F, see `ExtensionField`
+pub fn to_coeff_vec<F, E>(xs: &[E]) -> Vec<F>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+{
+    let mut out = Vec::from_iter(repeat_n(<F as Field>::zero(), xs.len() * E::DEGREE));
+    for (x, chunk) in xs.iter().zip(out.chunks_mut(E::DEGREE)) {
+        x.write_coeffs(chunk);
+    }
+    out
+}
+
+/// The element u with u^2 = nonresidue() that `Quadratic` adjoins to B, nonresidue() must not be a square in B
+pub trait QuadraticParams<B>: Copy + Debug + Default + Eq + Send + Sync + 'static
+where
+    B: Field,
+{
+    fn nonresidue() -> B;
+}
+
+/// c0 + c1 u in B[u] / (u^2 - P::nonresidue()), towers of these give the degree 4 extensions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Quadratic<B, P> {
+    pub c0: B,
+    pub c1: B,
+    _p: PhantomData<P>,
+}
+
+impl<B, P> Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    pub fn new(c0: B, c1: B) -> Self {
+        Quadratic { c0, c1, _p: PhantomData }
+    }
+
+    /// c0 - c1 u, the image under the Frobenius map of B[u] over B
+    pub fn conjugate(&self) -> Self {
+        Self::new(self.c0, -self.c1)
+    }
+
+    /// c0^2 - nonresidue c1^2, the product with the conjugate
+    pub fn norm(&self) -> B {
+        self.c0.square() - P::nonresidue() * self.c1.square()
+    }
+}
+
+impl<B, P> ConditionallySelectable for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self::new(B::conditional_select(&a.c0, &b.c0, choice), B::conditional_select(&a.c1, &b.c1, choice))
+    }
+}
+
+impl<B, P> ConstantTimeEq for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.c0.ct_eq(&other.c0) & self.c1.ct_eq(&other.c1)
+    }
+}
+
+impl<B, P> Neg for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.c0, -self.c1)
+    }
+}
+
+impl<B, P> AddAssign<&Self> for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn add_assign(&mut self, other: &Self) {
+        self.c0 += other.c0;
+        self.c1 += other.c1;
+    }
+}
+
+impl<B, P> SubAssign<&Self> for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn sub_assign(&mut self, other: &Self) {
+        self.c0 -= other.c0;
+        self.c1 -= other.c1;
+    }
+}
+
+impl<B, P> MulAssign<&Self> for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn mul_assign(&mut self, other: &Self) {
+        // Karatsuba: (a0 + a1 u)(b0 + b1 u) = a0 b0 + nonresidue a1 b1 + ((a0 + a1)(b0 + b1) - a0 b0 - a1 b1) u
+        let (v0, v1) = (self.c0 * other.c0, self.c1 * other.c1);
+        let c1 = (self.c0 + self.c1) * (other.c0 + other.c1) - v0 - v1;
+        self.c0 = v0 + P::nonresidue() * v1;
+        self.c1 = c1;
+    }
+}
+
+// the by-value and by-reference variants of the operators Field asks for, all in terms of the assigning ones
+macro_rules! quadratic_ops {
+    ($($op:ident, $method:ident, $assign:ident, $assign_method:ident;)*) => {
+        $(
+            impl<B, P> $assign for Quadratic<B, P>
+            where
+                B: Field,
+                P: QuadraticParams<B>,
+            {
+                fn $assign_method(&mut self, other: Self) {
+                    self.$assign_method(&other);
+                }
+            }
+
+            impl<B, P> $op<&Self> for Quadratic<B, P>
+            where
+                B: Field,
+                P: QuadraticParams<B>,
+            {
+                type Output = Self;
+
+                fn $method(mut self, other: &Self) -> Self {
+                    self.$assign_method(other);
+                    self
+                }
+            }
+
+            impl<B, P> $op for Quadratic<B, P>
+            where
+                B: Field,
+                P: QuadraticParams<B>,
+            {
+                type Output = Self;
+
+                fn $method(mut self, other: Self) -> Self {
+                    self.$assign_method(&other);
+                    self
+                }
+            }
+        )*
+    };
+}
+
+quadratic_ops! {
+    Add, add, AddAssign, add_assign;
+    Sub, sub, SubAssign, sub_assign;
+    Mul, mul, MulAssign, mul_assign;
+}
+
+impl<B, P> Field for Quadratic<B, P>
+where
+    B: Field,
+    P: QuadraticParams<B>,
+{
+    fn random(mut rng: impl RngCore) -> Self {
+        Self::new(B::random(&mut rng), B::random(&mut rng))
+    }
+
+    fn zero() -> Self {
+        Self::new(B::zero(), B::zero())
+    }
+
+    fn one() -> Self {
+        Self::new(B::one(), B::zero())
+    }
+
+    fn square(&self) -> Self {
+        *self * self
+    }
+
+    fn double(&self) -> Self {
+        Self::new(self.c0.double(), self.c1.double())
+    }
+
+    fn invert(&self) -> CtOption<Self> {
+        // x^-1 = conj(x) / norm(x), the norm is zero only for x = 0 since the nonresidue is not a square
+        self.norm().invert().map(|n| Self::new(self.c0 * n, -self.c1 * n))
+    }
+
+    /// variable time: x = x0 + x1 u squares to c0 + c1 u iff x0^2 + nonresidue x1^2 = c0 and 2 x0 x1 = c1, and
+    /// then x0^2 is one of (c0 +- sqrt(norm)) / 2
+    fn sqrt(&self) -> CtOption<Self> {
+        let none = CtOption::new(Self::zero(), Choice::from(0));
+        if bool::from(self.c1.is_zero()) {
+            // a square of B, or nonresidue times one, whose root is a multiple of u
+            return match Option::<B>::from(self.c0.sqrt()) {
+                Some(x0) => CtOption::new(Self::new(x0, B::zero()), Choice::from(1)),
+                None => match Option::<B>::from((self.c0 * P::nonresidue().invert().unwrap()).sqrt()) {
+                    Some(x1) => CtOption::new(Self::new(B::zero(), x1), Choice::from(1)),
+                    None => none,
+                },
+            };
+        }
+        let lambda = match Option::<B>::from(self.norm().sqrt()) {
+            Some(lambda) => lambda,
+            None => return none,
+        };
+        let half = B::one().double().invert().unwrap();
+        for delta in [(self.c0 + lambda) * half, (self.c0 - lambda) * half] {
+            if let Some(x0) = Option::<B>::from(delta.sqrt()) {
+                let x1 = self.c1 * x0.double().invert().unwrap();
+                let x = Self::new(x0, x1);
+                if x.square() == *self {
+                    return CtOption::new(x, Choice::from(1));
+                }
+            }
+        }
+        none
+    }
+}
+
+impl<F, B, P> ExtensionField<F> for Quadratic<B, P>
+where
+    F: PrimeField,
+    B: ExtensionField<F>,
+    P: QuadraticParams<B>,
+{
+    const DEGREE: usize = 2 * B::DEGREE;
+
+    fn from_base(x: F) -> Self {
+        Self::new(B::from_base(x), B::zero())
+    }
+
+    fn mul_base(&self, x: &F) -> Self {
+        Self::new(self.c0.mul_base(x), self.c1.mul_base(x))
+    }
+
+    fn from_coeffs(xs: &[F]) -> Self {
+        Self::new(B::from_coeffs(&xs[..B::DEGREE]), B::from_coeffs(&xs[B::DEGREE..Self::DEGREE]))
+    }
+
+    fn write_coeffs(&self, out: &mut [F]) {
+        self.c0.write_coeffs(&mut out[..B::DEGREE]);
+        self.c1.write_coeffs(&mut out[B::DEGREE..Self::DEGREE]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext2;
+    use crate::fieldspec::ft32::Ft32Ext4;
+    use crate::fieldspec::ft64::Ft64;
+    use crate::fieldspec::ft64::Ft64Ext2;
+    use crate::fieldspec::ft64::Ft64Ext4;
+    use crate::inspect::modulus;
+
+    fn axioms<F, E>()
+    where
+        F: PrimeField,
+        E: ExtensionField<F>,
+    {
+        let mut rng = ChaCha20Rng::seed_from_u64(3);
+        for _ in 0..50 {
+            let (a, b, c) = (E::random(&mut rng), E::random(&mut rng), E::random(&mut rng));
+            assert_eq!(a * (b + c), a * b + a * c);
+            assert_eq!((a * b) * c, a * (b * c));
+            assert_eq!(a * b, b * a);
+            assert_eq!(a.invert().unwrap() * a, E::one());
+            assert_eq!(a.square(), a * a);
+            assert_eq!(a.double(), a + a);
+            assert_eq!(a - b + b, a);
+            let root = a.square().sqrt().unwrap();
+            assert!(root == a || root == -a);
+
+            let x = F::random(&mut rng);
+            assert_eq!(a.mul_base(&x), a * E::from_base(x));
+            let mut coeffs = vec![<F as Field>::zero(); E::DEGREE];
+            a.write_coeffs(&mut coeffs);
+            assert_eq!(E::from_coeffs(&coeffs), a);
+        }
+        assert!(bool::from(E::zero().invert().is_none()));
+
+        // Frobenius: x^|F| generates the automorphisms, so x^(|F|^DEGREE) = x and x^(|F|^k) != x below DEGREE
+        let a = E::random(&mut rng);
+        let p = (-<F as Field>::one()).to_repr().as_ref().iter().rev().fold(0u128, |p, &b| (p << 8) | b as u128) + 1;
+        let mut x = a;
+        for k in 1..=E::DEGREE {
+            x = x.pow_vartime([p as u64, (p >> 64) as u64]);
+            assert_eq!(x == a, k == E::DEGREE);
+        }
+    }
+
+    #[test]
+    fn extension_fields() {
+        assert_eq!(modulus::<Ft64>(), "18446744069414584321");
+        axioms::<Ft32, Ft32>();
+        axioms::<Ft32, Ft32Ext2>();
+        axioms::<Ft32, Ft32Ext4>();
+        axioms::<Ft64, Ft64Ext2>();
+        axioms::<Ft64, Ft64Ext4>();
+
+        // the polynomials are irreducible: the nonresidues have no square roots
+        assert!(bool::from((-<Ft32 as Field>::one()).sqrt().is_none()));
+        assert!(bool::from(Ft32Ext2::new(Ft32::from(2), Ft32::from(1)).sqrt().is_none()));
+        assert!(bool::from(<Ft64 as Field>::sqrt(&Ft64::from(7)).is_none()));
+        assert!(bool::from(Ft64Ext2::new(Ft64::from(0), Ft64::from(1)).sqrt().is_none()));
+    }
+
+    #[test]
+    fn coefficient_vectors() {
+        let xs = (0..12).map(|i| Ft32::from(i as u64)).collect::<Vec<_>>();
+        let ext = from_coeff_vec::<Ft32, Ft32Ext4>(&xs);
+        assert_eq!(ext.len(), 3);
+        assert_eq!(ext[1], Ft32Ext4::new(Ft32Ext2::new(Ft32::from(4), Ft32::from(5)), Ft32Ext2::new(Ft32::from(6), Ft32::from(7))));
+        assert_eq!(to_coeff_vec::<Ft32, Ft32Ext4>(&ext), xs);
+        assert_eq!(from_coeff_vec::<Ft32, Ft32>(&xs), xs);
+    }
+}
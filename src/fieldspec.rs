@@ -1,12 +1,72 @@
 pub mod ft32 {
+    use ff::Field;
     use ff::PrimeField;
     use ff_derive_num::Num;
+    use crate::extension::Quadratic;
+    use crate::extension::QuadraticParams;
 
     #[derive(PrimeField, Num)]
     #[PrimeFieldModulus = "2147483647"]
     #[PrimeFieldGenerator = "3"]
     #[PrimeFieldReprEndianness = "little"]
     pub struct Ft32([u64; 1]);
+
+    /// u^2 = -1, a nonresidue since 2^31 - 1 = 3 mod 4
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub struct Ext2Params;
+
+    impl QuadraticParams<Ft32> for Ext2Params {
+        fn nonresidue() -> Ft32 {
+            -<Ft32 as Field>::one()
+        }
+    }
+
+    pub type Ft32Ext2 = Quadratic<Ft32, Ext2Params>;
+
+    /// v^2 = 2 + u, whose norm 5 is a nonresidue modulo 2^31 - 1
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub struct Ext4Params;
+
+    impl QuadraticParams<Ft32Ext2> for Ext4Params {
+        fn nonresidue() -> Ft32Ext2 {
+            Ft32Ext2::new(Ft32::from(2), <Ft32 as Field>::one())
+        }
+    }
+
+    pub type Ft32Ext4 = Quadratic<Ft32Ext2, Ext4Params>;
+}
+
+/// Goldilocks, 2^64 - 2^32 + 1
+pub mod ft64 {
+    use ff::Field;
+    use crate::extension::Quadratic;
+    use crate::extension::QuadraticParams;
+
+    pub use crate::goldilocks::Ft64;
+
+    /// u^2 = 7, the multiplicative generator
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub struct Ext2Params;
+
+    impl QuadraticParams<Ft64> for Ext2Params {
+        fn nonresidue() -> Ft64 {
+            Ft64::from(7)
+        }
+    }
+
+    pub type Ft64Ext2 = Quadratic<Ft64, Ext2Params>;
+
+    /// v^2 = u, so v^4 = 7
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+    pub struct Ext4Params;
+
+    impl QuadraticParams<Ft64Ext2> for Ext4Params {
+        fn nonresidue() -> Ft64Ext2 {
+            Ft64Ext2::new(<Ft64 as Field>::zero(), <Ft64 as Field>::one())
+        }
+    }
+
+    pub type Ft64Ext4 = Quadratic<Ft64Ext2, Ext4Params>;
 }
 
 pub mod ft127 {
@@ -0,0 +1,298 @@
+use std::fmt;
+use std::ops::Add;
+use std::ops::AddAssign;
+use std::ops::Mul;
+use std::ops::MulAssign;
+use std::ops::Neg;
+use std::ops::Sub;
+use std::ops::SubAssign;
+use ff::Field;
+use ff::PrimeField;
+use ff_derive_num::Num;
+use rand::RngCore;
+use subtle::Choice;
+use subtle::ConditionallySelectable;
+use subtle::ConstantTimeEq;
+use subtle::CtOption;
+
+const MODULUS: u64 = 0xffff_ffff_0000_0001;
+
+// 2^64 - p = 2^32 - 1, so 2^64 = EPSILON mod p
+const EPSILON: u64 = 0xffff_ffff;
+
+/// The Goldilocks field p = 2^64 - 2^32 + 1 with elements kept canonical in [0, p) in a single u64, so they
+/// serialize to 8 bytes; ff_derive would size its limbs for 2p and take two
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Num)]
+pub struct Ft64(u64);
+
+impl Ft64 {
+    // reduce x = lo + 2^64 hi_lo + 2^96 hi_hi using 2^64 = 2^32 - 1 and 2^96 = -1
+    fn reduce(x: u128) -> Self {
+        let lo = x as u64;
+        let hi = (x >> 64) as u64;
+        let (hi_hi, hi_lo) = (hi >> 32, hi & EPSILON);
+
+        let (mut t, borrow) = lo.overflowing_sub(hi_hi);
+        if borrow {
+            t -= EPSILON;
+        }
+        let (mut t, carry) = t.overflowing_add(hi_lo * EPSILON);
+        if carry {
+            t += EPSILON;
+        }
+        if t >= MODULUS {
+            t -= MODULUS;
+        }
+        Self(t)
+    }
+}
+
+impl fmt::Debug for Ft64 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ft64({:#018x})", self.0)
+    }
+}
+
+impl From<u64> for Ft64 {
+    fn from(x: u64) -> Self {
+        Self(if x >= MODULUS { x - MODULUS } else { x })
+    }
+}
+
+impl From<Ft64> for [u8; 8] {
+    fn from(x: Ft64) -> Self {
+        x.to_repr()
+    }
+}
+
+impl ConstantTimeEq for Ft64 {
+    fn ct_eq(&self, other: &Self) -> Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl ConditionallySelectable for Ft64 {
+    fn conditional_select(a: &Self, b: &Self, choice: Choice) -> Self {
+        Self(u64::conditional_select(&a.0, &b.0, choice))
+    }
+}
+
+impl Neg for Ft64 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        if self.0 == 0 { self } else { Self(MODULUS - self.0) }
+    }
+}
+
+impl AddAssign<&Self> for Ft64 {
+    fn add_assign(&mut self, other: &Self) {
+        let (sum, carry) = self.0.overflowing_add(other.0);
+        self.0 = if carry {
+            // sum + 2^64 < 2p, so this lands below p
+            sum + EPSILON
+        } else if sum >= MODULUS {
+            sum - MODULUS
+        } else {
+            sum
+        };
+    }
+}
+
+impl SubAssign<&Self> for Ft64 {
+    fn sub_assign(&mut self, other: &Self) {
+        let (diff, borrow) = self.0.overflowing_sub(other.0);
+        self.0 = if borrow { diff - EPSILON } else { diff };
+    }
+}
+
+impl MulAssign<&Self> for Ft64 {
+    fn mul_assign(&mut self, other: &Self) {
+        *self = Self::reduce(self.0 as u128 * other.0 as u128);
+    }
+}
+
+macro_rules! goldilocks_ops {
+    ($($op:ident, $method:ident, $assign:ident, $assign_method:ident;)*) => {
+        $(
+            impl $assign for Ft64 {
+                fn $assign_method(&mut self, other: Self) {
+                    self.$assign_method(&other);
+                }
+            }
+
+            impl $op<&Self> for Ft64 {
+                type Output = Self;
+
+                fn $method(mut self, other: &Self) -> Self {
+                    self.$assign_method(other);
+                    self
+                }
+            }
+
+            impl $op for Ft64 {
+                type Output = Self;
+
+                fn $method(mut self, other: Self) -> Self {
+                    self.$assign_method(&other);
+                    self
+                }
+            }
+        )*
+    };
+}
+
+goldilocks_ops! {
+    Add, add, AddAssign, add_assign;
+    Sub, sub, SubAssign, sub_assign;
+    Mul, mul, MulAssign, mul_assign;
+}
+
+impl Field for Ft64 {
+    fn random(mut rng: impl RngCore) -> Self {
+        loop {
+            let x = rng.next_u64();
+            if x < MODULUS {
+                return Self(x);
+            }
+        }
+    }
+
+    fn zero() -> Self {
+        Self(0)
+    }
+
+    fn one() -> Self {
+        Self(1)
+    }
+
+    fn is_zero_vartime(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn square(&self) -> Self {
+        *self * self
+    }
+
+    fn double(&self) -> Self {
+        *self + self
+    }
+
+    fn invert(&self) -> CtOption<Self> {
+        CtOption::new(self.pow_vartime([MODULUS - 2]), !self.is_zero())
+    }
+
+    /// Tonelli-Shanks over the 2^32 part of p - 1, variable time
+    fn sqrt(&self) -> CtOption<Self> {
+        if self.0 == 0 {
+            return CtOption::new(*self, Choice::from(1));
+        }
+        if self.pow_vartime([(MODULUS - 1) >> 1]) != Self::one() {
+            return CtOption::new(Self::zero(), Choice::from(0));
+        }
+        let mut m = Self::S;
+        let mut c = Self::root_of_unity();
+        let mut t = self.pow_vartime([(MODULUS - 1) >> Self::S]);
+        let mut r = self.pow_vartime([1 << (Self::S - 1)]);
+        while t != Self::one() {
+            let mut i = 0;
+            let mut t2 = t;
+            while t2 != Self::one() {
+                t2 = t2.square();
+                i += 1;
+            }
+            let mut b = c;
+            for _ in 0..m - i - 1 {
+                b = b.square();
+            }
+            r *= b;
+            c = b.square();
+            t *= c;
+            m = i;
+        }
+        CtOption::new(r, Choice::from(1))
+    }
+}
+
+impl PrimeField for Ft64 {
+    type Repr = [u8; 8];
+
+    fn from_repr(repr: Self::Repr) -> CtOption<Self> {
+        let x = u64::from_le_bytes(repr);
+        CtOption::new(Self(x), Choice::from((x < MODULUS) as u8))
+    }
+
+    fn to_repr(&self) -> Self::Repr {
+        self.0.to_le_bytes()
+    }
+
+    fn is_odd(&self) -> Choice {
+        Choice::from((self.0 & 1) as u8)
+    }
+
+    const NUM_BITS: u32 = 64;
+    const CAPACITY: u32 = 63;
+    const S: u32 = 32;
+
+    fn multiplicative_generator() -> Self {
+        Self(7)
+    }
+
+    fn root_of_unity() -> Self {
+        Self(7).pow_vartime([(MODULUS - 1) >> Self::S])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    // schoolbook arithmetic on u128 as the reference
+    #[test]
+    fn matches_u128_arithmetic() {
+        let p = MODULUS as u128;
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let edges = [0, 1, 2, EPSILON, 1 << 32, MODULUS - 2, MODULUS - 1];
+        let samples: Vec<u64> = edges
+            .into_iter()
+            .chain((0..200).map(|_| Ft64::random(&mut rng).0))
+            .collect();
+        for &a in &samples {
+            for &b in &samples {
+                let (x, y) = (Ft64(a), Ft64(b));
+                let (a, b) = (a as u128, b as u128);
+                assert_eq!((x + y).0 as u128, (a + b) % p);
+                assert_eq!((x - y).0 as u128, (a + p - b) % p);
+                assert_eq!((x * y).0 as u128, a * b % p);
+            }
+            assert_eq!((-Ft64(a)).0 as u128, (p - a as u128) % p);
+        }
+    }
+
+    #[test]
+    fn field_structure() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let w = Ft64::root_of_unity();
+        assert_eq!(w.pow_vartime([1 << 32]), Ft64::one());
+        assert_ne!(w.pow_vartime([1 << 31]), Ft64::one());
+        for _ in 0..100 {
+            let x = Ft64::random(&mut rng);
+            if x != Ft64::zero() {
+                assert_eq!(x * x.invert().unwrap(), Ft64::one());
+            }
+            assert_eq!(x.square().sqrt().map(|r| r.square()).unwrap(), x.square());
+        }
+        assert!(bool::from(Ft64::zero().invert().is_none()));
+        assert!(bool::from(Ft64::multiplicative_generator().sqrt().is_none()));
+    }
+
+    #[test]
+    fn repr() {
+        assert_eq!(Ft64::from(MODULUS), Ft64::zero());
+        assert_eq!(Ft64::from_repr((-Ft64::one()).to_repr()).unwrap(), -Ft64::one());
+        assert!(bool::from(Ft64::from_repr(MODULUS.to_le_bytes()).is_none()));
+        assert!(bool::from(Ft64::from_repr(u64::MAX.to_le_bytes()).is_none()));
+    }
+}
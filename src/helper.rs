@@ -1,10 +1,20 @@
 use std::f64::consts::LN_2;
+use ff::Field;
 use ff::PrimeField;
-use ndarray::Dim;
 use ndarray::Array;
+use ndarray::ArrayViewD;
+use ndarray::ArrayD;
+use ndarray::IxDyn;
+use ndarray::Slice;
 use ndarray::parallel::prelude::*;
+use rayon::slice::ParallelSliceMut;
 use ndarray::Axis;
+use ndarray::s;
 use num_traits::Num;
+use crate::extension::ExtensionField;
+
+/// columns `encode_tensor` encodes together, every matrix entry is applied to this many symbols at once
+pub const ENCODE_BATCH: usize = 16;
 
 pub fn next_pow_2(x: usize) -> usize {
     let mut y : usize = 1;
@@ -14,6 +24,18 @@ pub fn next_pow_2(x: usize) -> usize {
     return y;
 }
 
+pub fn inner_product<F>(a: &[F], b: &[F]) -> F
+where
+    F: PrimeField,
+{
+    assert_eq!(a.len(), b.len());
+    let mut s = <F as Field>::zero();
+    for i in 0..a.len() {
+        s = s.add(a[i].mul(b[i]));
+    }
+    return s;
+}
+
 pub fn binary_entropy(x: f64) -> f64 {
     return - x * x.log2() - (1.0 - x) * (1.0 - x).log2();
 }
@@ -23,104 +45,148 @@ pub fn degree_bound(e: f64, security_level: i32, code_len: usize) -> usize {
     return result.ceil() as usize;
 }
 
-pub fn linear_combination_2_1<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    r: &Vec<F>,
+// sum_l r[l] * row[l] for a row of symbols of `degree` coefficients, 1 or E::DEGREE
+pub fn combine_row<F, E>(row: &[F], degree: usize, r: &[E]) -> E
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+{
+    assert!(r.len() * degree <= row.len());
+    let mut s = <E as Field>::zero();
+    if degree == 1 {
+        for l in 0..r.len() {
+            s += r[l].mul_base(&row[l]);
+        }
+    }else{
+        for l in 0..r.len() {
+            s += r[l] * E::from_coeffs(&row[(l * degree)..((l + 1) * degree)]);
+        }
+    }
+    return s;
+}
+
+// compute the random linear combination along the last axis of a tensor:
+// (n0, ..., n(s-2), row_len) -> (n0, ..., n(s-3), range * E::DEGREE), where every row holds symbols of `degree`
+// coefficients (1 for base field entries, E::DEGREE for extension field entries, see `ExtensionField`), only the
+// first r.len() symbols of every row are combined and the first range entries along axis s-2 are kept
+pub fn linear_combination<F, E>(
+    m: &ArrayViewD<F>,
+    degree: usize,
+    r: &[E],
     range: usize
-) -> Array<F, Dim<[usize; 1]>>
+) -> ArrayD<F>
 where
     F: PrimeField + Num,
+    E: ExtensionField<F>,
 {
-    assert!(m_2d.shape() == &[code_len, msg_len] || m_2d.shape() == &[code_len, code_len]);
-    assert_eq!(r.len(), msg_len);
-    assert!(range == msg_len || range == code_len);
+    let t = m.ndim();
+    assert!(t >= 2);
+    assert!(degree == 1 || degree == E::DEGREE);
+    let (n, row_len) = (m.shape()[t - 2], m.shape()[t - 1]);
+    assert!(r.len() * degree <= row_len);
+    assert!(range <= n);
+
+    // view M as (n0 * ... * n(s-3), n(s-2), row_len)
+    let outer = m.shape()[..(t - 2)].iter().product::<usize>();
+    let m_3d = m.view().into_shape((outer, n, row_len)).unwrap();
 
-    let mut result = Array::<F, _>::zeros(range);
+    let mut result = Array::<F, _>::zeros((outer, range * E::DEGREE));
     result
         .axis_iter_mut(Axis(0))
         .into_par_iter()
         .enumerate()
         .for_each(|(i1, mut x)| {
-            let data = x.first_mut().unwrap();
-            for i2 in 0..msg_len {
-                *data = data.add(r[i2].mul(m_2d[[i1, i2]]));
+            let x = x.as_slice_mut().unwrap();
+            for i2 in 0..range {
+                let row = m_3d.slice(s![i1, i2, ..]);
+                let data = combine_row(row.as_slice().unwrap(), degree, r);
+                data.write_coeffs(&mut x[(i2 * E::DEGREE)..((i2 + 1) * E::DEGREE)]);
             }
-            *x.first_mut().unwrap() = *data;
         });
-    return result;
+
+    let mut shape = m.shape()[..(t - 1)].to_vec();
+    shape[t - 2] = range * E::DEGREE;
+    return result.into_shape(IxDyn(&shape)).unwrap();
 }
 
-pub fn linear_combination_3_2<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_3d: &Array<F, Dim<[usize; 3]>>,
-    r: &Vec<F>,
-    range: usize
-) -> Array<F, Dim<[usize; 2]>>
+// lay out a m0 * ... * m(t-1) tensor of coefficients in M0: N0 * ... * N(t-2) * m(t-1) and encode it along all
+// but the last axis; enc(a, block, width, scratch) encodes a block of `width` codewords of axis a stored symbol by
+// symbol (see `LinearCode::encode_batch`) given scratch space, lanes are gathered into blocks of ENCODE_BATCH
+// columns and both buffers are reused across the blocks of a thread
+pub fn encode_tensor<F, E>(
+    msg_lens: &[usize],
+    code_lens: &[usize],
+    coefs: &ArrayD<F>,
+    enc: &E
+) -> ArrayD<F>
 where
     F: PrimeField + Num,
+    E: Fn(usize, &mut [F], usize, &mut Vec<F>) + Sync,
 {
-    assert!(m_3d.shape() == &[code_len, code_len, msg_len] || m_3d.shape() == &[code_len, code_len, code_len]);
-    assert_eq!(r.len(), msg_len);
-    assert!(range == msg_len || range == code_len);
+    let t = coefs.ndim();
+    assert!(t >= 2);
+    assert_eq!(coefs.shape(), msg_lens);
+    assert_eq!(code_lens.len(), t);
 
-    let mut result = Array::<F, _>::zeros((code_len, range));
-    result
-        .axis_iter_mut(Axis(1))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i2, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(0))
-                .enumerate()
-                .for_each(|(i1, mut x)| {
-                    let data = x.first_mut().unwrap();
-                    for i3 in 0..msg_len {
-                        *data = data.add(r[i3].mul(m_3d[[i1, i2, i3]]));
+    let mut shape = code_lens.to_vec();
+    shape[t - 1] = msg_lens[t - 1];
+    let mut m0 = ArrayD::<F>::zeros(IxDyn(&shape));
+    {
+        let mut coefs_view = m0.view_mut();
+        for axis in 0..(t - 1) {
+            coefs_view.slice_axis_inplace(Axis(axis), Slice::from(..msg_lens[axis]));
+        }
+        coefs_view.assign(coefs);
+    }
+
+    // encode for axis a, where the axes a+1, ..., t-2 still hold message entries only
+    for a in 0..(t - 1) {
+        let (msg_len, code_len) = (msg_lens[a], code_lens[a]);
+        let mut m_view = m0.view_mut();
+        for axis in (a + 1)..(t - 1) {
+            m_view.slice_axis_inplace(Axis(axis), Slice::from(..msg_lens[axis]));
+        }
+        let mut lanes = m_view.lanes_mut(Axis(a)).into_iter().collect::<Vec<_>>();
+        lanes
+            .par_chunks_mut(ENCODE_BATCH)
+            .for_each_init(|| (Vec::new(), Vec::new()), |(block, scratch), chunk| {
+                let width = chunk.len();
+                block.clear();
+                block.resize(code_len * width, <F as Field>::zero());
+                for (c, x) in chunk.iter().enumerate() {
+                    for i in 0..msg_len {
+                        block[i * width + c] = x[i];
                     }
-                    *x.first_mut().unwrap() = *data;
-                });
-        });
-    return result;
+                }
+                enc(a, block, width, scratch);
+                // the codes are systematic, only the parity symbols are new
+                for (c, x) in chunk.iter_mut().enumerate() {
+                    for i in msg_len..code_len {
+                        x[i] = block[i * width + c];
+                    }
+                }
+            });
+    }
+    return m0;
 }
 
-pub fn linear_combination_4_3<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_4d: &Array<F, Dim<[usize; 4]>>,
-    r: &Vec<F>,
-    range: usize
-) -> Array<F, Dim<[usize; 3]>>
+// read the first len entries of the row at (i1, i2, ...) along the last axis
+pub fn tensor_row<F>(
+    m: &ArrayViewD<F>,
+    idx: &[usize],
+    len: usize
+) -> Vec<F>
 where
-    F: PrimeField + Num,
+    F: PrimeField,
 {
-    assert_eq!(m_4d.shape(), &[code_len, code_len, code_len, msg_len]);
-    assert_eq!(r.len(), msg_len);
-    assert!(range == msg_len || range == code_len);
+    assert_eq!(idx.len() + 1, m.ndim());
 
-    let mut result = Array::<F, _>::zeros((code_len, code_len, range));
-    result
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i3, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .for_each(|(i2, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(0))
-                        .enumerate()
-                        .for_each(|(i1, mut x)| {
-                            let data = x.first_mut().unwrap();
-                            for i4 in 0..msg_len {
-                                *data = data.add(r[i4].mul(m_4d[[i1, i2, i3, i4]]));
-                            }
-                            *x.first_mut().unwrap() = *data;
-                        });
-                });
-        });
-    return result;
-}
\ No newline at end of file
+    let mut pos = idx.to_vec();
+    pos.push(0);
+    let mut row = Vec::<F>::with_capacity(len);
+    for i in 0..len {
+        pos[idx.len()] = i;
+        row.push(m[IxDyn(&pos)]);
+    }
+    return row;
+}
@@ -0,0 +1,257 @@
+use std::io;
+use std::io::Write;
+use ff::Field;
+use ff::PrimeField;
+use sprs::CsMat;
+use crate::codegen::generated_dims;
+use crate::codespec::CodeParams;
+use crate::encode::codeword_length;
+use crate::linear_code::LinearCode;
+
+/// largest msg_len for which `generator_matrix` is worth building, it has msg_len * code_len dense entries
+pub const DENSE_MAX_MSG_LEN: usize = 1024;
+
+/// One level of a generated code, dimensions as computed by `codegen::get_dims`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelInfo {
+    /// precode from ni to mi symbols with cn nonzero entries per column
+    pub ni: usize,
+    pub mi: usize,
+    pub cn: usize,
+    /// postcode from ni' to mi' symbols with dn nonzero entries per column
+    pub nip: usize,
+    pub mip: usize,
+    pub dn: usize,
+    /// nonzero entries actually stored in the precode and postcode
+    pub pre_nnz: usize,
+    pub post_nnz: usize,
+}
+
+/// Shape of a generated code, see `inspect`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeInfo {
+    pub msg_len: usize,
+    pub code_len: usize,
+    /// recursion levels, empty for the identity plus Reed-Solomon code of messages of at most baselen symbols
+    pub levels: Vec<LevelInfo>,
+    /// message and codeword length of the Reed-Solomon base case
+    pub base_len: usize,
+    pub rs_len: usize,
+    /// relative distance the code parameters promise
+    pub predicted_distance: f64,
+    /// nonzero entries of all precodes and postcodes
+    pub nnz: usize,
+}
+
+impl CodeInfo {
+    /// msg_len over code_len
+    pub fn rate(&self) -> f64 {
+        self.msg_len as f64 / self.code_len as f64
+    }
+
+    /// predicted_distance in symbols
+    pub fn predicted_min_weight(&self) -> usize {
+        (self.predicted_distance * self.code_len as f64).ceil() as usize
+    }
+}
+
+/// Describe the code generated for `code` and msg_len from its matrices, e.g. as returned by `codegen::generate_from`
+pub fn inspect<F>(code: &CodeParams, precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> CodeInfo
+where
+    F: PrimeField,
+{
+    let msg_len = precodes[0].cols();
+    let levels = match generated_dims::<F>(code, msg_len) {
+        Some((pre_dims, post_dims)) => {
+            assert_eq!(pre_dims.len(), precodes.len());
+            pre_dims
+                .iter()
+                .zip(post_dims.iter())
+                .zip(precodes.iter().zip(postcodes.iter()))
+                .map(|((&(ni, mi, cn), &(nip, mip, dn)), (precode, postcode))| {
+                    assert_eq!((precode.cols(), precode.rows()), (ni, mi));
+                    assert_eq!((postcode.cols(), postcode.rows()), (nip, mip));
+                    LevelInfo { ni, mi, cn, nip, mip, dn, pre_nnz: precode.nnz(), post_nnz: postcode.nnz() }
+                })
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    CodeInfo {
+        msg_len,
+        code_len: codeword_length(precodes, postcodes),
+        levels,
+        base_len: precodes.last().unwrap().rows(),
+        rs_len: postcodes.last().unwrap().cols(),
+        predicted_distance: code.dist(),
+        nnz: precodes.iter().chain(postcodes.iter()).map(|pc| pc.nnz()).sum(),
+    }
+}
+
+/// The dense generator matrix of a code as its columns, the codewords of the unit vectors
+pub fn generator_matrix<F, L>(code: &L) -> Vec<Vec<F>>
+where
+    F: PrimeField,
+    L: LinearCode<F> + ?Sized,
+{
+    let (k, n) = (code.message_len(), code.codeword_len());
+    let mut scratch = Vec::new();
+    (0..k)
+        .map(|i| {
+            let mut x = vec![<F as Field>::zero(); n];
+            x[i] = <F as Field>::one();
+            code.encode_with(&mut x, &mut scratch);
+            x
+        })
+        .collect()
+}
+
+/// decimal digits of the canonical representative of x in 0..p
+pub fn to_decimal<F>(x: &F) -> String
+where
+    F: PrimeField,
+{
+    // little-endian bytes as base 256 digits, divided by 10 until nothing is left
+    let mut digits = x.to_repr().as_ref().iter().rev().copied().collect::<Vec<u8>>();
+    let mut out = Vec::new();
+    while digits.iter().any(|&d| d != 0) {
+        let mut rem = 0u32;
+        for d in digits.iter_mut() {
+            let cur = (rem << 8) | *d as u32;
+            *d = (cur / 10) as u8;
+            rem = cur % 10;
+        }
+        out.push(b'0' + rem as u8);
+    }
+    if out.is_empty() {
+        out.push(b'0');
+    }
+    out.reverse();
+    String::from_utf8(out).unwrap()
+}
+
+/// decimal digits of the field's prime p
+pub fn modulus<F>() -> String
+where
+    F: PrimeField,
+{
+    // p - 1 plus one, carried through the decimal digits
+    let mut digits = to_decimal(&-<F as Field>::one()).into_bytes();
+    for d in digits.iter_mut().rev() {
+        if *d == b'9' {
+            *d = b'0';
+        }else{
+            *d += 1;
+            return String::from_utf8(digits).unwrap();
+        }
+    }
+    digits.insert(0, b'1');
+    String::from_utf8(digits).unwrap()
+}
+
+/// write a sparse matrix in MatrixMarket coordinate format, entries as integers modulo the field's prime
+pub fn write_matrix_market<F, W>(mut out: W, matrix: &CsMat<F>) -> io::Result<()>
+where
+    F: PrimeField,
+    W: Write,
+{
+    writeln!(out, "%%MatrixMarket matrix coordinate integer general")?;
+    writeln!(out, "% entries modulo {}", modulus::<F>())?;
+    writeln!(out, "{} {} {}", matrix.rows(), matrix.cols(), matrix.nnz())?;
+    for (&value, (row, col)) in matrix.iter() {
+        writeln!(out, "{} {} {}", row + 1, col + 1, to_decimal(&value))?;
+    }
+    Ok(())
+}
+
+/// write the columns of a dense matrix, e.g. from `generator_matrix`, in MatrixMarket array format
+pub fn write_dense_matrix_market<F, W>(mut out: W, columns: &[Vec<F>]) -> io::Result<()>
+where
+    F: PrimeField,
+    W: Write,
+{
+    let rows = columns.first().map_or(0, |c| c.len());
+    writeln!(out, "%%MatrixMarket matrix array integer general")?;
+    writeln!(out, "% entries modulo {}", modulus::<F>())?;
+    writeln!(out, "{} {}", rows, columns.len())?;
+    // array format lists the entries column by column
+    for column in columns.iter() {
+        assert_eq!(column.len(), rows);
+        for x in column.iter() {
+            writeln!(out, "{}", to_decimal(x))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::generate_from;
+    use crate::codespec::Code6;
+    use crate::codespec::CodeSpecification;
+    use crate::encode::encode;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft255::Ft255;
+    use crate::linear_code::ExpanderCode;
+
+    #[test]
+    fn describes_levels() {
+        let params = Code6::code_params();
+        let (precodes, postcodes) = generate_from::<Ft255>(&params, 300, 0);
+        let info = inspect(&params, &precodes, &postcodes);
+        assert_eq!(info.code_len, codeword_length(&precodes, &postcodes));
+        assert_eq!(info.levels.len(), precodes.len());
+        assert_eq!(info.levels[0].ni, 300);
+        // every level feeds the next, and every column has its sampled number of nonzeros
+        for (level, next) in info.levels.iter().zip(info.levels.iter().skip(1)) {
+            assert_eq!(level.mi, next.ni);
+        }
+        for level in info.levels.iter() {
+            assert_eq!(level.pre_nnz, level.ni * level.cn);
+            assert_eq!(level.post_nnz, level.nip * level.dn);
+        }
+        assert_eq!(info.base_len, info.levels.last().unwrap().mi);
+        assert_eq!(info.nnz, info.levels.iter().map(|l| l.pre_nnz + l.post_nnz).sum::<usize>());
+
+        let (precodes, postcodes) = generate_from::<Ft255>(&params, 4, 0);
+        let info = inspect(&params, &precodes, &postcodes);
+        assert!(info.levels.is_empty());
+        assert_eq!((info.base_len, info.base_len + info.rs_len), (4, info.code_len));
+    }
+
+    #[test]
+    fn generator_columns() {
+        let params = Code6::code_params();
+        let code = ExpanderCode::<Ft32>::generate(&params, 40, 1);
+        let columns = generator_matrix(&code);
+        assert_eq!(columns.len(), 40);
+        for (i, column) in columns.iter().enumerate() {
+            let mut x = vec![<Ft32 as Field>::zero(); column.len()];
+            x[i] = <Ft32 as Field>::one();
+            encode(&mut x, &code.precodes, &code.postcodes);
+            assert_eq!(&x, column);
+        }
+    }
+
+    #[test]
+    fn matrix_market() {
+        assert_eq!(to_decimal(&Ft255::from(0)), "0");
+        assert_eq!(to_decimal(&Ft255::from(1234567890123)), "1234567890123");
+        assert_eq!(to_decimal(&-Ft32::from(1)), "2147483646");
+        assert_eq!(modulus::<Ft32>(), "2147483647");
+        assert_eq!(modulus::<Ft255>(), "46242760681095663677370860714659204618859642560429202607213929836750194081793");
+
+        let matrix = CsMat::new_csc((2, 3), vec![0, 1, 1, 3], vec![1, 0, 1], vec![Ft32::from(5), Ft32::from(6), -Ft32::from(1)]);
+        let mut out = Vec::new();
+        write_matrix_market(&mut out, &matrix).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines = text.lines().collect::<Vec<_>>();
+        assert_eq!(lines[2..], ["2 3 3", "2 1 5", "1 3 6", "2 3 2147483646"]);
+
+        let mut out = Vec::new();
+        write_dense_matrix_market(&mut out, &[vec![Ft32::from(1), Ft32::from(2)], vec![Ft32::from(3), Ft32::from(4)]]).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(text.lines().skip(2).collect::<Vec<_>>(), ["2 2", "1", "2", "3", "4"]);
+    }
+}
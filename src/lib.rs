@@ -0,0 +1,32 @@
+#![allow(clippy::needless_return)]
+#![allow(clippy::ptr_arg)]
+#![allow(clippy::too_many_arguments)]
+#![allow(clippy::needless_range_loop)]
+#![allow(clippy::type_complexity)]
+
+pub mod codespec;
+pub mod fieldspec;
+pub mod goldilocks;
+pub mod extension;
+pub mod codegen;
+pub mod codecache;
+pub mod encode;
+pub mod decode;
+pub mod distance;
+pub mod inspect;
+pub mod linear_code;
+pub mod helper;
+pub mod merkle;
+pub mod transcript;
+pub mod pcs;
+pub mod commit;
+pub mod simple_zk;
+pub mod commit_zk;
+pub mod lwe;
+pub mod serialize;
+pub mod params;
+
+pub use pcs::PolynomialCommitment;
+pub use commit::TensorCommitment;
+pub use simple_zk::SimpleZkTensorCommitment;
+pub use commit_zk::ZkTensorCommitment;
@@ -0,0 +1,809 @@
+use std::sync::Arc;
+use ff::Field;
+use ff::PrimeField;
+use ndarray::ArrayView;
+use ndarray::linalg::Dot;
+use num_traits::Num;
+use rand::SeedableRng;
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use sprs::CsMat;
+use sprs::MulAcc;
+use crate::codegen::generate_from;
+use crate::codespec::CodeParams;
+use crate::encode::EncodePlan;
+use crate::encode::LazyPlan;
+use crate::encode::encode_batch;
+use crate::encode::encode_lazy_batch;
+use crate::encode::encode_lazy_transposed;
+use crate::encode::encode_with;
+use crate::encode::ntt;
+use crate::encode::ntt_size;
+use crate::encode::reed_solomon;
+use crate::encode::reed_solomon_points;
+use crate::encode::reed_solomon_rev;
+use crate::encode::root_of_unity;
+
+/// A linear code mapping message_len symbols to codeword_len symbols
+pub trait LinearCode<F: Copy>: Send + Sync {
+    fn message_len(&self) -> usize;
+
+    fn codeword_len(&self) -> usize;
+
+    /// encode in place: xi has codeword_len symbols and holds the message in the first message_len of them
+    fn encode(&self, xi: &mut [F]);
+
+    /// `encode` with scratch space the caller keeps across calls, so that codes which need some need not allocate
+    fn encode_with(&self, xi: &mut [F], scratch: &mut Vec<F>) {
+        let _ = scratch;
+        self.encode(xi);
+    }
+
+    /// encode `width` codewords at once, stored symbol by symbol: xi[i * width + c] is symbol i of codeword c
+    fn encode_batch(&self, xi: &mut [F], width: usize, scratch: &mut Vec<F>) {
+        encode_columns(xi, width, self.codeword_len(), scratch, |x| self.encode(x));
+    }
+
+    /// multiply with the transposed generator matrix, mapping codeword_len symbols xi to message_len symbols xo
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]);
+
+    /// whether `encode` leaves the message in place as the first message_len symbols of the codeword and keeps
+    /// a distance the tensor commitments can rely on; codes are taken to be non-systematic unless they say so,
+    /// see `Systematic`
+    fn is_systematic(&self) -> bool {
+        false
+    }
+}
+
+/// encode the `width` codewords of a block stored symbol by symbol one after another with `enc`,
+/// for encoders without a batched kernel; `column` keeps its capacity across calls
+pub fn encode_columns<F, E>(xi: &mut [F], width: usize, code_len: usize, column: &mut Vec<F>, mut enc: E)
+where
+    F: Copy,
+    E: FnMut(&mut [F]),
+{
+    assert_eq!(xi.len(), code_len * width);
+    column.clear();
+    column.extend(xi.iter().step_by(width).copied());
+    for c in 0..width {
+        for (x, row) in column.iter_mut().zip(xi.chunks_exact(width)) {
+            *x = row[c];
+        }
+        enc(column);
+        for (&x, row) in column.iter().zip(xi.chunks_exact_mut(width)) {
+            row[c] = x;
+        }
+    }
+}
+
+/// The Brakedown code of `encode::encode`: precodes down to a Reed-Solomon base case and postcodes back up
+pub struct ExpanderCode<F> {
+    pub precodes: Vec<CsMat<F>>,
+    pub postcodes: Vec<CsMat<F>>,
+    plan: EncodePlan<F>,
+}
+
+impl<F> ExpanderCode<F>
+where
+    F: PrimeField,
+{
+    pub fn new(precodes: Vec<CsMat<F>>, postcodes: Vec<CsMat<F>>) -> Self {
+        assert!(!precodes.is_empty());
+        assert_eq!(precodes.len(), postcodes.len());
+        let plan = EncodePlan::new(&precodes, &postcodes);
+        ExpanderCode { precodes, postcodes, plan }
+    }
+
+    /// the code for msg_len symbols generated from `seed`
+    pub fn generate(code: &CodeParams, msg_len: usize, seed: u64) -> Self {
+        let (precodes, postcodes) = generate_from::<F>(code, msg_len, seed);
+        Self::new(precodes, postcodes)
+    }
+}
+
+impl<F> LinearCode<F> for ExpanderCode<F>
+where
+    F: PrimeField + Num + MulAcc,
+{
+    fn message_len(&self) -> usize {
+        self.precodes[0].cols()
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.plan.codeword_len()
+    }
+
+    fn is_systematic(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        self.encode_with(xi, &mut Vec::new());
+    }
+
+    fn encode_with(&self, xi: &mut [F], scratch: &mut Vec<F>) {
+        encode_with(xi, &self.plan, scratch);
+    }
+
+    fn encode_batch(&self, xi: &mut [F], width: usize, scratch: &mut Vec<F>) {
+        encode_batch(xi, width, &self.plan, scratch);
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        let levels = self.precodes.len();
+        assert_eq!(xi.len(), self.codeword_len());
+        assert_eq!(xo.len(), self.message_len());
+
+        // same layout as `encode`: precode i reads starts[i].., postcode i reads ins[i]..outs[i] and writes outs[i]..
+        let mut starts = vec![0usize];
+        for precode in self.precodes.iter() {
+            starts.push(starts.last().unwrap() + precode.cols());
+        }
+        let rs_start = starts[levels];
+        let rs_len = self.postcodes[levels - 1].cols();
+        let mut ins = vec![0usize; levels];
+        let mut outs = vec![0usize; levels];
+        let mut out_start = rs_start + rs_len;
+        for i in (0..levels).rev() {
+            ins[i] = if i == levels - 1 { rs_start } else { starts[i + 1] };
+            outs[i] = out_start;
+            out_start += self.postcodes[i].rows();
+        }
+
+        // walk `encode` backwards, pushing every output back onto the inputs it was computed from
+        let mut y = xi.to_vec();
+        for (i, postcode) in self.postcodes.iter().enumerate() {
+            let (lo, hi) = y.split_at_mut(outs[i]);
+            let back = postcode.transpose_view().dot(&ArrayView::from(&hi[..postcode.rows()]));
+            for (x, b) in lo[ins[i]..].iter_mut().zip(back.iter()) {
+                *x += b;
+            }
+        }
+
+        let precode = &self.precodes[levels - 1];
+        let mut r = vec![<F as Field>::zero(); precode.rows()];
+        reed_solomon_rev(&y[rs_start..(rs_start + rs_len)], &mut r);
+        let mut back = precode.transpose_view().dot(&ArrayView::from(&r[..]));
+        for i in (0..levels).rev() {
+            for (x, b) in y[starts[i]..starts[i + 1]].iter_mut().zip(back.iter()) {
+                *x += b;
+            }
+            if i > 0 {
+                back = self.precodes[i - 1].transpose_view().dot(&ArrayView::from(&y[starts[i]..starts[i + 1]]));
+            }
+        }
+        xo.copy_from_slice(&y[..xo.len()]);
+    }
+}
+
+/// The expander code of `ExpanderCode::generate` without its matrices, which are sampled again from the seed
+/// on every call, see `encode::LazyPlan`
+pub struct LazyExpanderCode<F> {
+    plan: LazyPlan<F>,
+}
+
+impl<F> LazyExpanderCode<F>
+where
+    F: PrimeField,
+{
+    pub fn new(code: &CodeParams, msg_len: usize, seed: u64) -> Self {
+        LazyExpanderCode { plan: LazyPlan::new(code, msg_len, seed) }
+    }
+
+    /// bytes kept between calls, to compare with the matrices of `ExpanderCode`
+    pub fn heap_bytes(&self) -> usize {
+        self.plan.heap_bytes()
+    }
+}
+
+impl<F> LinearCode<F> for LazyExpanderCode<F>
+where
+    F: PrimeField,
+{
+    fn message_len(&self) -> usize {
+        self.plan.message_len()
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.plan.codeword_len()
+    }
+
+    fn is_systematic(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        self.encode_with(xi, &mut Vec::new());
+    }
+
+    fn encode_with(&self, xi: &mut [F], scratch: &mut Vec<F>) {
+        encode_lazy_batch(xi, 1, &self.plan, scratch);
+    }
+
+    fn encode_batch(&self, xi: &mut [F], width: usize, scratch: &mut Vec<F>) {
+        encode_lazy_batch(xi, width, &self.plan, scratch);
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        encode_lazy_transposed(xi, xo, &self.plan);
+    }
+}
+
+/// Reed-Solomon code evaluating the message as a polynomial at `encode::reed_solomon_points`
+pub struct ReedSolomonCode {
+    msg_len: usize,
+    code_len: usize,
+}
+
+impl ReedSolomonCode {
+    pub fn new(msg_len: usize, code_len: usize) -> Self {
+        assert!(msg_len >= 1 && msg_len <= code_len);
+        ReedSolomonCode { msg_len, code_len }
+    }
+}
+
+impl<F> LinearCode<F> for ReedSolomonCode
+where
+    F: PrimeField,
+{
+    fn message_len(&self) -> usize {
+        self.msg_len
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.code_len
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        assert_eq!(xi.len(), self.code_len);
+        let mut res = vec![<F as Field>::zero(); self.code_len];
+        reed_solomon(&xi[..self.msg_len], &mut res);
+        xi.copy_from_slice(&res);
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        assert_eq!(xi.len(), self.code_len);
+        assert_eq!(xo.len(), self.msg_len);
+        reed_solomon_rev(xi, xo);
+    }
+}
+
+/// Systematic Reed-Solomon code: the message holds the values of a polynomial of degree below msg_len at msg_len
+/// points and the parity symbols its values at code_len - msg_len other points, so two codewords differ in at
+/// least code_len - msg_len + 1 symbols
+pub struct SystematicReedSolomonCode<F> {
+    msg_len: usize,
+    code_len: usize,
+    parity: RsParity<F>,
+}
+
+// how `SystematicReedSolomonCode` gets from the message to the parity symbols
+enum RsParity<F> {
+    // msg_len is a power of two and F has a subgroup of order n >= code_len generated by w: the message sits on
+    // the subgroup of order msg_len, its coefficients come from an inverse NTT with w_msg_inv, and the parity
+    // symbols are the values at the first code_len - msg_len powers of w outside it, at the indices `points`
+    Ntt { n: usize, w: F, w_msg_inv: F, msg_len_inv: F, points: Vec<usize> },
+    // the message sits on the first msg_len points of `reed_solomon_points` and parity symbol j is
+    // sum_i lagrange[j * msg_len + i] x[i], the Lagrange basis of the message points at point msg_len + j
+    Lagrange(Vec<F>),
+}
+
+impl<F> SystematicReedSolomonCode<F>
+where
+    F: PrimeField,
+{
+    pub fn new(msg_len: usize, code_len: usize) -> Self {
+        assert!(msg_len >= 1 && msg_len <= code_len);
+        let parity = match ntt_size::<F>(msg_len, code_len) {
+            Some(n) if msg_len.is_power_of_two() => {
+                let w = root_of_unity::<F>(n).unwrap();
+                let w_msg_inv = root_of_unity::<F>(msg_len).unwrap().invert().unwrap();
+                let msg_len_inv = F::from(msg_len as u64).invert().unwrap();
+                let step = n / msg_len;
+                let points = (0..n).filter(|i| i % step != 0).take(code_len - msg_len).collect();
+                RsParity::Ntt { n, w, w_msg_inv, msg_len_inv, points }
+            }
+            _ => RsParity::Lagrange(lagrange_basis(&reed_solomon_points::<F>(msg_len, code_len), msg_len)),
+        };
+        SystematicReedSolomonCode { msg_len, code_len, parity }
+    }
+}
+
+// values of the Lagrange basis of points[..msg_len] at points[msg_len..], point by point, from the barycentric
+// weights 1 / prod_{l != i} (x_i - x_l)
+fn lagrange_basis<F>(points: &[F], msg_len: usize) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let (xs, ys) = points.split_at(msg_len);
+    let mut weights = xs
+        .iter()
+        .enumerate()
+        .map(|(i, &xi)| xs.iter().enumerate().filter(|&(l, _)| l != i).fold(<F as Field>::one(), |acc, (_, &xl)| acc * (xi - xl)))
+        .collect::<Vec<_>>();
+    batch_invert(&mut weights);
+
+    let mut basis = Vec::with_capacity(ys.len() * msg_len);
+    let mut diffs = vec![<F as Field>::zero(); msg_len];
+    for &y in ys {
+        for (d, &x) in diffs.iter_mut().zip(xs.iter()) {
+            *d = y - x;
+        }
+        let vanishing = diffs.iter().fold(<F as Field>::one(), |acc, &d| acc * d);
+        batch_invert(&mut diffs);
+        basis.extend(diffs.iter().zip(weights.iter()).map(|(&d, &w)| vanishing * w * d));
+    }
+    basis
+}
+
+// invert every element of xs, which have to be nonzero, with a single inversion
+fn batch_invert<F>(xs: &mut [F])
+where
+    F: PrimeField,
+{
+    let mut prefix = Vec::with_capacity(xs.len());
+    let mut acc = <F as Field>::one();
+    for &x in xs.iter() {
+        prefix.push(acc);
+        acc *= x;
+    }
+    let mut inv = acc.invert().unwrap();
+    for (x, p) in xs.iter_mut().zip(prefix).rev() {
+        let next = inv * *x;
+        *x = inv * p;
+        inv = next;
+    }
+}
+
+impl<F> LinearCode<F> for SystematicReedSolomonCode<F>
+where
+    F: PrimeField,
+{
+    fn message_len(&self) -> usize {
+        self.msg_len
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.code_len
+    }
+
+    fn is_systematic(&self) -> bool {
+        true
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        assert_eq!(xi.len(), self.code_len);
+        let (msg, parity) = xi.split_at_mut(self.msg_len);
+        match &self.parity {
+            RsParity::Ntt { n, w, w_msg_inv, msg_len_inv, points } => {
+                let mut a = vec![<F as Field>::zero(); *n];
+                a[..self.msg_len].copy_from_slice(msg);
+                ntt(&mut a[..self.msg_len], *w_msg_inv);
+                for c in a[..self.msg_len].iter_mut() {
+                    *c *= msg_len_inv;
+                }
+                ntt(&mut a, *w);
+                for (p, &j) in parity.iter_mut().zip(points.iter()) {
+                    *p = a[j];
+                }
+            }
+            RsParity::Lagrange(basis) => {
+                parity.par_iter_mut().zip(basis.par_chunks(self.msg_len)).for_each(|(p, row)| {
+                    *p = row.iter().zip(msg.iter()).fold(<F as Field>::zero(), |acc, (&l, &x)| acc + l * x);
+                });
+            }
+        }
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        assert_eq!(xi.len(), self.code_len);
+        assert_eq!(xo.len(), self.msg_len);
+        let (msg, parity) = xi.split_at(self.msg_len);
+        match &self.parity {
+            // both NTT matrices are symmetric, so the transpose scatters, evaluates and interpolates back
+            RsParity::Ntt { n, w, w_msg_inv, msg_len_inv, points } => {
+                let mut a = vec![<F as Field>::zero(); *n];
+                for (&p, &j) in parity.iter().zip(points.iter()) {
+                    a[j] = p;
+                }
+                ntt(&mut a, *w);
+                ntt(&mut a[..self.msg_len], *w_msg_inv);
+                for (x, &c) in xo.iter_mut().zip(a.iter()) {
+                    *x = c * msg_len_inv;
+                }
+            }
+            RsParity::Lagrange(basis) => {
+                xo.fill(<F as Field>::zero());
+                for (&p, row) in parity.iter().zip(basis.chunks_exact(self.msg_len)) {
+                    for (x, &l) in xo.iter_mut().zip(row.iter()) {
+                        *x += l * p;
+                    }
+                }
+            }
+        }
+        for (x, &y) in xo.iter_mut().zip(msg.iter()) {
+            *x += y;
+        }
+    }
+}
+
+/// Repeat-accumulate-accumulate code: every message symbol is repeated `repetitions` times,
+/// then the word is permuted and replaced by its prefix sums, twice
+pub struct RaaCode {
+    msg_len: usize,
+    repetitions: usize,
+    permutations: [Vec<usize>; 2],
+}
+
+impl RaaCode {
+    /// code of rate 1/repetitions with permutations derived from `seed`
+    pub fn new(msg_len: usize, repetitions: usize, seed: u64) -> Self {
+        assert!(msg_len >= 1 && repetitions >= 1);
+        let code_len = msg_len * repetitions;
+        let mut rng = ChaCha20Rng::seed_from_u64(seed);
+        let mut permutation = || {
+            let mut p = (0..code_len).collect::<Vec<_>>();
+            p.shuffle(&mut rng);
+            p
+        };
+        let permutations = [permutation(), permutation()];
+        RaaCode { msg_len, repetitions, permutations }
+    }
+}
+
+impl<F> LinearCode<F> for RaaCode
+where
+    F: PrimeField,
+{
+    fn message_len(&self) -> usize {
+        self.msg_len
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.msg_len * self.repetitions
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        assert_eq!(xi.len(), self.msg_len * self.repetitions);
+        let mut word = xi[..self.msg_len]
+            .iter()
+            .flat_map(|&x| std::iter::repeat_n(x, self.repetitions))
+            .collect::<Vec<_>>();
+        for permutation in self.permutations.iter() {
+            let mut acc = <F as Field>::zero();
+            for (x, &j) in xi.iter_mut().zip(permutation.iter()) {
+                acc += word[j];
+                *x = acc;
+            }
+            word.copy_from_slice(xi);
+        }
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        assert_eq!(xi.len(), self.msg_len * self.repetitions);
+        assert_eq!(xo.len(), self.msg_len);
+        // the transpose of a prefix sum is a suffix sum and the transpose of a permutation its inverse
+        let mut word = xi.to_vec();
+        for permutation in self.permutations.iter().rev() {
+            let mut acc = <F as Field>::zero();
+            for x in word.iter_mut().rev() {
+                acc += *x;
+                *x = acc;
+            }
+            let mut permuted = vec![<F as Field>::zero(); word.len()];
+            for (&x, &j) in word.iter().zip(permutation.iter()) {
+                permuted[j] = x;
+            }
+            word = permuted;
+        }
+        for (x, block) in xo.iter_mut().zip(word.chunks(self.repetitions)) {
+            *x = block.iter().fold(<F as Field>::zero(), |acc, &y| acc + y);
+        }
+    }
+}
+
+/// Systematic form [x | inner(x)] of a code with a good distance on its own, such as `RaaCode` with at least two
+/// repetitions or `ReedSolomonCode` with code_len >= 2 msg_len: the tensor commitments read messages back from the
+/// first message_len symbols of a codeword, so they need codes of this shape.
+///
+/// The wrapper adds at most message_len to the distance of inner, it does not make a weak code good: a square
+/// `ReedSolomonCode` is a bijection and [x | inner(x)] then only has distance 2. `is_systematic` is false unless
+/// inner has at least twice as many symbols as the message, see `SystematicReedSolomonCode` for an MDS code.
+pub struct Systematic<L> {
+    pub inner: L,
+}
+
+impl<L> Systematic<L> {
+    pub fn new(inner: L) -> Self {
+        Systematic { inner }
+    }
+}
+
+impl<F, L> LinearCode<F> for Systematic<L>
+where
+    F: PrimeField,
+    L: LinearCode<F>,
+{
+    fn message_len(&self) -> usize {
+        self.inner.message_len()
+    }
+
+    fn codeword_len(&self) -> usize {
+        self.inner.message_len() + self.inner.codeword_len()
+    }
+
+    fn is_systematic(&self) -> bool {
+        self.inner.codeword_len() >= 2 * self.inner.message_len()
+    }
+
+    fn encode(&self, xi: &mut [F]) {
+        let msg_len = self.inner.message_len();
+        assert_eq!(xi.len(), self.codeword_len());
+        let (msg, parity) = xi.split_at_mut(msg_len);
+        parity[..msg_len].copy_from_slice(msg);
+        self.inner.encode(parity);
+    }
+
+    fn encode_batch(&self, xi: &mut [F], width: usize, scratch: &mut Vec<F>) {
+        let msg_len = self.inner.message_len();
+        assert_eq!(xi.len(), self.codeword_len() * width);
+        let (msg, parity) = xi.split_at_mut(msg_len * width);
+        parity[..(msg_len * width)].copy_from_slice(msg);
+        self.inner.encode_batch(parity, width, scratch);
+    }
+
+    fn encode_transposed(&self, xi: &[F], xo: &mut [F]) {
+        let msg_len = self.inner.message_len();
+        assert_eq!(xi.len(), self.codeword_len());
+        assert_eq!(xo.len(), msg_len);
+        self.inner.encode_transposed(&xi[msg_len..], xo);
+        for (x, &y) in xo.iter_mut().zip(xi[..msg_len].iter()) {
+            *x += y;
+        }
+    }
+}
+
+/// Code families a tensor axis can be encoded with, see `commit::TensorParams::with_families`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CodeFamily {
+    /// Brakedown expander code
+    Expander,
+    /// the expander code sampled again from the seed on every encode instead of being stored
+    Lazy,
+    /// systematic Reed-Solomon code, see `SystematicReedSolomonCode`
+    ReedSolomon,
+    /// the message followed by a repeat-accumulate-accumulate codeword, needs an expansion of at least 3
+    Raa,
+}
+
+impl CodeFamily {
+    /// the systematic code of this family for msg_len symbols: `code` describes the expander codes, the
+    /// Reed-Solomon and RAA codes have msg_len * (expansion - 1) parity symbols
+    pub fn code<F>(self, code: &CodeParams, msg_len: usize, expansion: usize, seed: u64) -> Arc<dyn LinearCode<F>>
+    where
+        F: PrimeField + Num + MulAcc,
+    {
+        match self {
+            CodeFamily::Expander => Arc::new(ExpanderCode::generate(code, msg_len, seed)),
+            CodeFamily::Lazy => Arc::new(LazyExpanderCode::new(code, msg_len, seed)),
+            CodeFamily::ReedSolomon => Arc::new(SystematicReedSolomonCode::new(msg_len, msg_len * expansion)),
+            CodeFamily::Raa => {
+                // a single repetition is a permutation of prefix sums, with a constant distance
+                assert!(expansion >= 3, "the RAA family needs an expansion of at least 3");
+                Arc::new(Systematic::new(RaaCode::new(msg_len, expansion - 1, seed)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codespec::Code6;
+    use crate::encode::encode;
+    use crate::codespec::CodeSpecification;
+    use crate::distance::exact_code_distance;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft255::Ft255;
+
+    // encode_transposed(e_j)[i] == encode(e_i)[j]
+    fn transposed<F, L>(code: &L)
+    where
+        F: PrimeField,
+        L: LinearCode<F>,
+    {
+        let (k, n) = (code.message_len(), code.codeword_len());
+        let unit = |i: usize, len: usize| {
+            let mut x = vec![<F as Field>::zero(); len];
+            x[i] = <F as Field>::one();
+            x
+        };
+        let columns = (0..k)
+            .map(|i| {
+                let mut x = unit(i, n);
+                code.encode(&mut x);
+                x
+            })
+            .collect::<Vec<_>>();
+        if code.is_systematic() {
+            for (i, x) in columns.iter().enumerate() {
+                assert_eq!(x[..k], unit(i, k));
+            }
+        }
+        for j in 0..n {
+            let mut row = vec![<F as Field>::zero(); k];
+            code.encode_transposed(&unit(j, n), &mut row);
+            for i in 0..k {
+                assert_eq!(row[i], columns[i][j]);
+            }
+        }
+    }
+
+    #[test]
+    fn transposes() {
+        transposed::<Ft255, _>(&ExpanderCode::<Ft255>::generate(&Code6::code_params(), 200, 0));
+        transposed::<Ft32, _>(&ExpanderCode::<Ft32>::generate(&Code6::code_params(), 200, 0));
+        transposed::<Ft255, _>(&ReedSolomonCode::new(10, 25));
+        transposed::<Ft32, _>(&ReedSolomonCode::new(10, 25));
+        transposed::<Ft255, _>(&RaaCode::new(10, 4, 0));
+        transposed::<Ft32, _>(&Systematic::new(RaaCode::new(10, 3, 1)));
+        assert!(!LinearCode::<Ft32>::is_systematic(&ReedSolomonCode::new(10, 25)));
+        assert!(!LinearCode::<Ft32>::is_systematic(&RaaCode::new(10, 4, 0)));
+        assert!(LinearCode::<Ft32>::is_systematic(&Systematic::new(RaaCode::new(10, 4, 0))));
+        assert!(LinearCode::<Ft32>::is_systematic(&Systematic::new(ReedSolomonCode::new(10, 20))));
+        // square inner codes leave [x | inner(x)] with a constant distance
+        assert!(!LinearCode::<Ft32>::is_systematic(&Systematic::new(RaaCode::new(10, 1, 0))));
+        assert!(!LinearCode::<Ft32>::is_systematic(&Systematic::new(ReedSolomonCode::new(10, 10))));
+    }
+
+    #[test]
+    fn short_messages() {
+        // at or below the base length the code is the message followed by Reed-Solomon up to rate 1/r
+        let params = Code6::code_params();
+        for msg_len in 1..=(params.baselen + 1) {
+            let code = ExpanderCode::<Ft255>::generate(&params, msg_len, 0);
+            if msg_len <= params.baselen {
+                let code_len = LinearCode::<Ft255>::codeword_len(&code);
+                assert_eq!(code_len, (msg_len * params.r_num).div_ceil(params.r_den).max(msg_len + 1));
+            }
+            transposed::<Ft255, _>(&code);
+            batched::<Ft255, _>(&code, 3);
+        }
+        transposed::<Ft32, _>(&ExpanderCode::<Ft32>::generate(&params, 7, 0));
+    }
+
+    fn matches_encode<F>(msg_len: usize, seed: u64)
+    where
+        F: PrimeField + Num + MulAcc,
+    {
+        let code = ExpanderCode::<F>::generate(&Code6::code_params(), msg_len, seed);
+        let mut rng = rand::thread_rng();
+        let mut scratch = Vec::new();
+        for _ in 0..3 {
+            let mut x = vec![<F as Field>::zero(); LinearCode::<F>::codeword_len(&code)];
+            for xi in x[..msg_len].iter_mut() {
+                *xi = F::random(&mut rng);
+            }
+            let mut y = x.clone();
+            encode(&mut x, &code.precodes, &code.postcodes);
+            code.encode_with(&mut y, &mut scratch);
+            assert_eq!(x, y);
+        }
+    }
+
+    // encode_batch(block)[i * width + c] == encode(column c)[i]
+    fn batched<F, L>(code: &L, width: usize)
+    where
+        F: PrimeField,
+        L: LinearCode<F>,
+    {
+        let (k, n) = (code.message_len(), code.codeword_len());
+        let mut rng = rand::thread_rng();
+        let columns = (0..width)
+            .map(|_| {
+                let mut x = vec![<F as Field>::zero(); n];
+                for xi in x[..k].iter_mut() {
+                    *xi = F::random(&mut rng);
+                }
+                x
+            })
+            .collect::<Vec<_>>();
+        let mut block = vec![<F as Field>::zero(); n * width];
+        for (c, x) in columns.iter().enumerate() {
+            for i in 0..k {
+                block[i * width + c] = x[i];
+            }
+        }
+        code.encode_batch(&mut block, width, &mut Vec::new());
+        for (c, mut x) in columns.into_iter().enumerate() {
+            code.encode(&mut x);
+            for i in 0..n {
+                assert_eq!(block[i * width + c], x[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn batches() {
+        batched::<Ft255, _>(&ExpanderCode::<Ft255>::generate(&Code6::code_params(), 100, 5), 7);
+        batched::<Ft32, _>(&ExpanderCode::<Ft32>::generate(&Code6::code_params(), 300, 6), 16);
+        batched::<Ft255, _>(&Systematic::new(ReedSolomonCode::new(20, 40)), 3);
+        batched::<Ft32, _>(&RaaCode::new(20, 2, 0), 5);
+    }
+
+    // the unit messages and e_0 - e_(msg_len / 2) have weight at least code_len - msg_len + 1, and so has every
+    // codeword for small codes; transposes and batches hold for the NTT and the Lagrange parity alike
+    fn mds<F>(msg_len: usize, code_len: usize)
+    where
+        F: PrimeField,
+    {
+        let code = SystematicReedSolomonCode::<F>::new(msg_len, code_len);
+        let bound = code_len - msg_len + 1;
+        let weight = |msg: &[F]| {
+            let mut x = vec![<F as Field>::zero(); code_len];
+            x[..msg_len].copy_from_slice(msg);
+            code.encode(&mut x);
+            x.iter().filter(|x| !bool::from(x.is_zero())).count()
+        };
+        let mut msg = vec![<F as Field>::zero(); msg_len];
+        msg[0] = <F as Field>::one();
+        assert!(weight(&msg) >= bound);
+        msg[msg_len / 2] -= <F as Field>::one();
+        if msg_len > 1 {
+            assert!(weight(&msg) >= bound);
+        }
+        if let Some(distance) = exact_code_distance::<F>(&code, 1 << 16) {
+            assert_eq!(distance.min_weight, bound);
+        }
+        transposed::<F, _>(&code);
+        batched::<F, _>(&code, 3);
+    }
+
+    #[test]
+    fn systematic_reed_solomon() {
+        // Ft255 takes the NTT for msg_len a power of two, Ft32 has no subgroup to speak of
+        for (msg_len, code_len) in [(1, 2), (4, 8), (8, 16), (6, 12), (5, 11), (64, 128), (40, 100)] {
+            mds::<Ft255>(msg_len, code_len);
+            mds::<Ft32>(msg_len, code_len);
+        }
+        assert!(LinearCode::<Ft32>::is_systematic(&SystematicReedSolomonCode::<Ft32>::new(4, 8)));
+
+        // the message followed by Reed-Solomon on the 8th roots of unity misses this: 1 - X^4 vanishes on w^(2i)
+        let mut parity = vec![<Ft255 as Field>::zero(); 8];
+        parity[0] = <Ft255 as Field>::one();
+        parity[4] = -<Ft255 as Field>::one();
+        LinearCode::<Ft255>::encode(&ReedSolomonCode::new(8, 8), &mut parity);
+        assert_eq!(parity.iter().filter(|x| !bool::from(x.is_zero())).count(), 4);
+    }
+
+    #[test]
+    fn lazy_matches_expander() {
+        let params = Code6::code_params();
+        for (msg_len, seed) in [(1, 0), (params.baselen, 1), (100, 2), (300, 3)] {
+            let code = ExpanderCode::<Ft255>::generate(&params, msg_len, seed);
+            let lazy = LazyExpanderCode::<Ft255>::new(&params, msg_len, seed);
+            assert_eq!(LinearCode::<Ft255>::codeword_len(&code), LinearCode::<Ft255>::codeword_len(&lazy));
+            let mut rng = rand::thread_rng();
+            let mut x = vec![<Ft255 as Field>::zero(); LinearCode::<Ft255>::codeword_len(&code)];
+            for xi in x[..msg_len].iter_mut() {
+                *xi = Ft255::random(&mut rng);
+            }
+            let mut y = x.clone();
+            code.encode(&mut x);
+            lazy.encode(&mut y);
+            assert_eq!(x, y);
+            batched::<Ft255, _>(&lazy, 4);
+        }
+        transposed::<Ft255, _>(&LazyExpanderCode::<Ft255>::new(&params, 60, 4));
+        transposed::<Ft255, _>(&LazyExpanderCode::<Ft255>::new(&params, 5, 4));
+        transposed::<Ft32, _>(&LazyExpanderCode::<Ft32>::new(&params, 60, 5));
+        batched::<Ft32, _>(&LazyExpanderCode::<Ft32>::new(&params, 200, 5), 16);
+    }
+
+    #[test]
+    fn expander_matches_encode() {
+        matches_encode::<Ft255>(100, 3);
+        matches_encode::<Ft32>(300, 4);
+    }
+}
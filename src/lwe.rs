@@ -1,5 +1,5 @@
-use std::collections::HashMap;
-use std::sync::RwLock;
+#![allow(non_snake_case)]
+
 use std::time::Instant;
 use digest::Digest;
 use digest::Output;
@@ -13,13 +13,23 @@ use num_traits::Num;
 use sprs::MulAcc;
 use rand::Rng;
 use crate::codespec::CodeSpecification;
-use crate::codegen::generate;
-use crate::encode::codeword_length;
-use crate::encode::encode;
+use crate::extension::ExtensionField;
+use crate::extension::from_coeff_vec;
+use crate::extension::to_coeff_vec;
 use crate::helper::next_pow_2;
+use crate::linear_code::ExpanderCode;
+use crate::linear_code::LinearCode;
+use crate::linear_code::ReedSolomonCode;
 use crate::merkle::build_merkle_tree;
 use crate::merkle::check_merkle_path;
-
+use crate::merkle::merkle_path;
+use crate::merkle::merkle_tree_len;
+use crate::pcs::Timings;
+use crate::pcs::VerifyError;
+use crate::serialize::to_bytes;
+use crate::transcript::Transcript;
+
+/// replace msg[..msg_len] by its Reed-Solomon encoding msg[..code_len], see `ReedSolomonCode`
 pub fn encode_reed_solomon<F>(
     msg: &mut Vec<F>,
     msg_len: usize,
@@ -28,27 +38,7 @@ pub fn encode_reed_solomon<F>(
 where 
     F: PrimeField + Num + MulAcc,
 {
-    let mut res = Vec::<F>::new();
-    res.resize(code_len, <F as Field>::zero());
-
-    let mut coef = Vec::<F>::new();
-    let mut cur = <F as Field>::zero();
-    for i in (0..code_len){
-        cur += <F as Field>::one();
-        coef.push(cur);
-    }
-    res
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, x)|{
-            for j in (0..msg_len).rev() {
-                *x *= coef[i];
-                *x += msg[j];
-            }
-        });
-    for i in (0..code_len){
-        msg[i] = res[i];
-    }
+    ReedSolomonCode::new(msg_len, code_len).encode(&mut msg[..code_len]);
 }
 
 pub fn generate_ternary_vector<F>(
@@ -58,7 +48,7 @@ where
     F: PrimeField + Num + MulAcc,
 {
 
-    let mut result = Array::<F, _>::zeros((size));
+    let mut result = Array::<F, _>::zeros(size);
     result.par_iter_mut().for_each(|x| {
         let mut rng = rand::thread_rng();
         let data: u32 = rng.gen_range(0..=2);
@@ -120,47 +110,199 @@ where
 
     let mut hashes_vec = Vec::<Output<D>>::new();
     let item_no = code_len;
-    let np2 = next_pow_2(item_no);
-    hashes_vec.resize_with(2*np2-1, Default::default);
-    (&mut hashes_vec)
+    hashes_vec.resize_with(merkle_tree_len(item_no), Default::default);
+    let leaf_start = hashes_vec.len() - item_no;
+    (&mut hashes_vec[leaf_start..])
         .into_par_iter()
         .enumerate()
-        .filter(|(i, _)| i >= &(np2-1) && i < &(np2-1+item_no))
-        .for_each(|(i, x)| {
+        .for_each(|(idx, x)| {
         let mut digest = D::new();
-        let idx = i-(np2-1);
         digest.update(H2[idx].to_repr());
         digest.update(H1[idx].to_repr());
         digest.update(H0[idx].to_repr());
         *x = digest.finalize();
     });
-    build_merkle_tree::<D>(&mut hashes_vec, np2);
+    build_merkle_tree::<D>(&mut hashes_vec, item_no);
     return hashes_vec;
 }
 
-pub fn ternary_lwe<F, C, D>(
+/// Opening of E = (H2, H1, H0) at one queried position
+pub struct LweQuery<F, D>
+where
+    D: Digest,
+{
+    pub h2: F,
+    pub h1: F,
+    pub h0: F,
+    /// authentication path of (H2[j], H1[j], H0[j])
+    pub path: Vec<Output<D>>,
+}
+
+/// Proof that u = A * s + e for ternary s and e
+pub struct LweProof<F, D>
+where
+    D: Digest,
+{
+    /// merkle root of E
+    pub root: Output<D>,
+    /// f(X) = t * X + s: m symbols over the extension X is drawn from, E::DEGREE coefficients each
+    pub fx: Vec<F>,
+    pub queries: Vec<LweQuery<F, D>>,
+}
+
+// transcript bound to the parameters, the instance (A, u) and the root of E
+fn lwe_transcript<F, D>(
     n: usize,
     m: usize,
     lambda: usize,
     seed: u64,
-    RS_code: bool
-)
+    RS_code: bool,
+    A: &Array<F, Dim<[usize; 2]>>,
+    u: &Array<F, Dim<[usize; 1]>>,
+    root: &Output<D>,
+) -> Transcript<D>
 where
-    F: PrimeField + Num + MulAcc,
-    C: CodeSpecification,
+    F: PrimeField,
     D: Digest,
 {
-    let zero = <F as Field>::zero();
-    let one = <F as Field>::one();
-    let two = one.add(one);
-    let three = two.add(one);
-    let mut rng = rand::thread_rng();
+    let mut transcript = Transcript::<D>::new(b"ternary lwe");
+    transcript.absorb_usize(n);
+    transcript.absorb_usize(m);
+    transcript.absorb_usize(lambda);
+    transcript.absorb_bytes(&seed.to_le_bytes());
+    transcript.absorb_usize(RS_code as usize);
+    transcript.absorb_fields(A.as_slice().unwrap());
+    transcript.absorb_fields(u.as_slice().unwrap());
+    transcript.absorb_digest(root);
+    transcript
+}
 
-    // generate codes
+/// check an `LweProof` that u = A * s + e for ternary s and e, with the challenge X drawn from the extension E of F
+pub fn verify_ternary_lwe<F, E, D>(
+    n: usize,
+    m: usize,
+    lambda: usize,
+    seed: u64,
+    RS_code: bool,
+    A: &Array<F, Dim<[usize; 2]>>,
+    u: &Array<F, Dim<[usize; 1]>>,
+    code: &dyn LinearCode<F>,
+    proof: &LweProof<F, D>,
+) -> Result<(), VerifyError>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    let zero = <E as Field>::zero();
+    let one = <E as Field>::one();
+    let d = E::DEGREE;
     let msg_len: usize = 2 * m + n;
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-    let code_len = codeword_length::<F>(&precodes, &postcodes);
+    let code_len = code.codeword_len();
+    let np2 = next_pow_2(code_len);
+    if A.shape() != [n, m] || u.len() != n || code.message_len() != msg_len || proof.fx.len() != m * d || proof.queries.len() != lambda {
+        return Err(VerifyError::ShapeMismatch);
+    }
+
+    let mut transcript = lwe_transcript::<F, D>(n, m, lambda, seed, RS_code, A, u, &proof.root);
+    let X = transcript.squeeze_extension::<F, E>(1)[0];
+    // the prover can grind the root for X = 0, which has no inverse
+    let X_invert = Option::<E>::from(X.invert()).ok_or(VerifyError::ZeroChallenge)?;
+    transcript.absorb_fields(&proof.fx);
+    let idx = transcript.squeeze_indices(lambda, code_len);
+
+    idx.par_iter().zip(proof.queries.par_iter()).enumerate().try_for_each(|(index, (&j, query))| {
+        // verify the merkle path for E
+        let mut digest = D::new();
+        digest.update(query.h2.to_repr());
+        digest.update(query.h1.to_repr());
+        digest.update(query.h0.to_repr());
+        let cur_hash = digest.finalize();
+        let k = j + np2 - 1;
+        if check_merkle_path::<D>(cur_hash, k, &query.path, &proof.root) {
+            Ok(())
+        }else{
+            Err(VerifyError::MerklePathMismatch { index })
+        }
+    })?;
+
+    // fx: m
+    let fx = from_coeff_vec::<F, E>(&proof.fx);
+
+    // dx = u - A * fx: n
+    let mut dx = Vec::<E>::new();
+    dx.resize(n, zero);
+    dx
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, x)|{
+            let mut Afx = zero;
+            for j in 0..m {
+                Afx += fx[j].mul_base(&A[[i, j]]);
+            }
+            *x = E::from_base(u[i]).sub(Afx);
+        });
+
+    // fxx: m
+    let mut fxx = Vec::<E>::new();
+    fxx.resize(m, zero);
+    fxx
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, x)|{
+            *x = fx[i].mul(
+                fx[i].sub(one)
+            ).mul(
+                fx[i].add(one)
+            ).mul(
+                X_invert
+            );
+        });
+
+    // dxx: n
+    let mut dxx = Vec::<E>::new();
+    dxx.resize(n, zero);
+    dxx
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(i, x)|{
+            *x = dx[i].mul(
+                dx[i].sub(one)
+            ).mul(
+                dx[i].add(one)
+            ).mul(
+                X_invert
+            );
+        });
+
+    // H(X) = (fx, fxx, dxx) is over E, encode it as a block of d codewords over F
+    let mut Hx = [fx, fxx, dxx].concat();
+    Hx.resize(code_len, zero);
+    let mut Hx = to_coeff_vec::<F, E>(&Hx);
+    code.encode_batch(&mut Hx, d, &mut Vec::new());
+
+    idx.par_iter().zip(proof.queries.par_iter()).enumerate().try_for_each(|(index, (&j, query))| {
+        let x = X.mul(X).mul_base(&query.h2).add(
+            X.mul_base(&query.h1)
+        ).add(
+            E::from_base(query.h0)
+        );
+        if E::from_coeffs(&Hx[(j * d)..((j + 1) * d)]) == x {
+            Ok(())
+        }else{
+            Err(VerifyError::LweRelationMismatch { index })
+        }
+    })
+}
 
+/// sample a random instance u = A * s + e with A: n * m and ternary s: m, e: n; returns (A, s, e, u)
+pub fn sample_ternary_lwe<F>(
+    n: usize,
+    m: usize,
+) -> (Array<F, Dim<[usize; 2]>>, Array<F, Dim<[usize; 1]>>, Array<F, Dim<[usize; 1]>>, Array<F, Dim<[usize; 1]>>)
+where
+    F: PrimeField + Num + MulAcc,
+{
     // A: n * m
     let mut A = Array::<F, _>::zeros((n, m));
     A.par_iter_mut().for_each(|x| {
@@ -184,8 +326,32 @@ where
             *data = data.add(e[i]);
             *x.first_mut().unwrap() = *data;
         });
+    (A, s, e, u)
+}
 
-    let start_time = Instant::now();
+/// prove that u = A * s + e for the ternary witness s, e, with the challenge X drawn from the extension E of F
+pub fn prove_ternary_lwe<F, E, D>(
+    n: usize,
+    m: usize,
+    lambda: usize,
+    seed: u64,
+    RS_code: bool,
+    A: &Array<F, Dim<[usize; 2]>>,
+    u: &Array<F, Dim<[usize; 1]>>,
+    s: &Array<F, Dim<[usize; 1]>>,
+    e: &Array<F, Dim<[usize; 1]>>,
+    code: &dyn LinearCode<F>,
+) -> LweProof<F, D>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    D: Digest,
+{
+    let zero = <F as Field>::zero();
+    let one = <F as Field>::one();
+    let two = one.add(one);
+    let three = two.add(one);
+    let code_len = code.codeword_len();
 
     // t: m
     let mut t = Array::<F, _>::zeros(m);
@@ -284,148 +450,183 @@ where
     let mut H0 = Vec::<F>::new();
     H0.resize(code_len, zero);
     fill_H_array::<F>(
-        &mut H0, n, m, &s, &v0, &w0
+        &mut H0, n, m, s, &v0, &w0
     );
     
     // encoding
-    if RS_code {
-        encode_reed_solomon(&mut H2, msg_len, code_len);
-        encode_reed_solomon(&mut H1, msg_len, code_len);
-        encode_reed_solomon(&mut H0, msg_len, code_len);
-    }else{
-        encode(&mut H2, &precodes, &postcodes);
-        encode(&mut H1, &precodes, &postcodes);
-        encode(&mut H0, &precodes, &postcodes);
-    }
+    code.encode(&mut H2);
+    code.encode(&mut H1);
+    code.encode(&mut H0);
 
     let hashes_E = merkle_tree_commit_lwe::<F, D>(code_len, &H2, &H1, &H0);
 
-
     // X
-    let X = F::random(&mut rng);
-    let X_invert = X.invert().unwrap();
+    let mut transcript = lwe_transcript::<F, D>(n, m, lambda, seed, RS_code, A, u, &hashes_E[0]);
+    let X = transcript.squeeze_extension::<F, E>(1)[0];
 
     // fx: m
-    let mut fx = Vec::<F>::new();
-    fx.resize(m, zero);
+    let mut fx = Vec::<E>::new();
+    fx.resize(m, <E as Field>::zero());
     fx
         .par_iter_mut()
         .enumerate()
         .for_each(|(i, x)|{
-            *x = t[i].mul(X).add(s[i]);
-        });
-    let mut fx_copy = Vec::<F>::new();
-    fx_copy.resize(m, zero);
-    fx_copy
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, x)|{
-            *x = t[i].mul(X).add(s[i]);
+            *x = X.mul_base(&t[i]).add(E::from_base(s[i]));
         });
+    let fx = to_coeff_vec::<F, E>(&fx);
+
+    // sample idx and open E
+    transcript.absorb_fields(&fx);
+    let idx = transcript.squeeze_indices(lambda, code_len);
+    let np2 = next_pow_2(code_len);
+    let queries = idx
+        .par_iter()
+        .map(|&j| LweQuery {
+            h2: H2[j],
+            h1: H1[j],
+            h0: H0[j],
+            path: merkle_path::<D>(&hashes_E, code_len, j + np2 - 1),
+        })
+        .collect();
+    LweProof::<F, D> { root: hashes_E[0].clone(), fx, queries }
+}
 
-    let committed_time = Instant::now();
-
-    // verifier sampling idx
-    let mut idx = Vec::<usize>::new();
-    idx.resize_with(lambda, || rng.gen_range(0..code_len));
-
-    let mut E_map = HashMap::<usize, Output<D>>::new();
-    E_map.insert(0, hashes_E[0].clone());
-    let E_map_rwlock = RwLock::new(E_map);
 
-    (0..lambda).into_par_iter().for_each(|i| {
-        // verify the merkle path for E
-        let j = idx[i];
-        let mut digest = D::new();
-        digest.update(H2[j].to_repr());
-        digest.update(H1[j].to_repr());
-        digest.update(H0[j].to_repr());
-        let cur_hash = digest.finalize();
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let k = j + np2 - 1;
-        assert!(check_merkle_path::<D>(cur_hash, k, &E_map_rwlock, &hashes_E));
-    });
+/// sample a random instance u = A * s + e, prove it with the challenge X drawn from the extension E of F and verify
+/// the proof; returns the proof, the code length and the timings
+pub fn run_ternary_lwe<F, E, C, D>(
+    n: usize,
+    m: usize,
+    lambda: usize,
+    seed: u64,
+    RS_code: bool
+) -> Result<(LweProof<F, D>, usize, Timings), VerifyError>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+    D: Digest,
+{
+    // generate codes
+    let msg_len: usize = 2 * m + n;
+    let expander = ExpanderCode::<F>::generate(&C::code_params(), msg_len, seed);
+    let code_len = expander.codeword_len();
+    // the Reed-Solomon code has the same length as the expander code
+    let code: Box<dyn LinearCode<F>> = if RS_code {
+        Box::new(ReedSolomonCode::new(msg_len, code_len))
+    }else{
+        Box::new(expander)
+    };
+    let (A, s, e, u) = sample_ternary_lwe::<F>(n, m);
 
-    let mut Afx = A.dot(&Array::from(fx_copy));
-    let mut dx = Vec::<F>::new();
-    dx.resize(n, zero);
-    dx
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, x)|{
-            *x = u[i].sub(Afx[i]);
-        });
+    let start_time = Instant::now();
+    let proof = prove_ternary_lwe::<F, E, D>(n, m, lambda, seed, RS_code, &A, &u, &s, &e, &*code);
+    let committed_time = Instant::now();
 
-    let mid_time = Instant::now();
+    // verifier has access to A, u and the proof
+    verify_ternary_lwe::<F, E, D>(n, m, lambda, seed, RS_code, &A, &u, &*code, &proof)?;
 
-    // fxx: m
-    let mut fxx = Vec::<F>::new();
-    fxx.resize(m, zero);
-    fxx
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, x)|{
-            *x = fx[i].mul(
-                fx[i].sub(one)
-            ).mul(
-                fx[i].add(one)
-            ).mul(
-                X_invert
-            );
-        });
+    let verified_time = Instant::now();
 
-    // dxx: n
-    let mut dxx = Vec::<F>::new();
-    dxx.resize(n, zero);
-    dxx
-        .par_iter_mut()
-        .enumerate()
-        .for_each(|(i, x)|{
-            *x = dx[i].mul(
-                dx[i].sub(one)
-            ).mul(
-                dx[i].add(one)
-            ).mul(
-                X_invert
-            );
-        });
+    let timings = Timings {
+        commit: committed_time.duration_since(start_time),
+        verify: verified_time.duration_since(committed_time),
+    };
+    Ok((proof, code_len, timings))
+}
 
+pub fn ternary_lwe<F, E, C, D>(
+    n: usize,
+    m: usize,
+    lambda: usize,
+    seed: u64,
+    RS_code: bool
+)
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+    D: Digest,
+{
+    let (proof, code_len, timings) = match run_ternary_lwe::<F, E, C, D>(n, m, lambda, seed, RS_code) {
+        Ok(res) => res,
+        Err(e) => panic!("verification failed: {}", e),
+    };
+
+    println!("RS_code:{:?} n:{:?} m:{:?} lambda:{:?} code_len:{:?}", RS_code, n, m, lambda, code_len);
+    println!("commit_time: {} ms", timings.commit.as_millis());
+    println!("verify_time: {} ms", timings.verify.as_millis());
+    println!("total_time: {} ms", timings.total().as_millis());
+    println!("Proof Size: {} bytes\n", to_bytes(&proof).len());
+}
 
-    let mut Hx = Vec::<F>::new();
-    Hx.resize(code_len, zero);
-    fill_H_array::<F>(
-        &mut Hx, n, m, 
-        &Array::from(fx), 
-        &Array::from(fxx), 
-        &Array::from(dxx), 
-    );
-    
-    // encoding
-    if RS_code {
-        encode_reed_solomon(&mut Hx, msg_len, code_len);
-    }else{
-        encode(&mut Hx, &precodes, &postcodes);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use crate::codespec::Code6;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext4;
+    use crate::fieldspec::ft64::Ft64;
+    use crate::fieldspec::ft64::Ft64Ext2;
+
+    #[test]
+    fn extension_challenge() {
+        for rs in [false, true] {
+            let (proof, _, _) = run_ternary_lwe::<Ft32, Ft32Ext4, Code6, Blake3>(16, 24, 8, 0, rs).unwrap();
+            assert_eq!(proof.fx.len(), 24 * 4);
+        }
+        let (proof, _, _) = run_ternary_lwe::<Ft64, Ft64Ext2, Code6, Blake3>(16, 24, 8, 0, false).unwrap();
+        assert_eq!(proof.fx.len(), 24 * 2);
+        let (proof, _, _) = run_ternary_lwe::<Ft64, Ft64, Code6, Blake3>(16, 24, 8, 0, true).unwrap();
+        assert_eq!(proof.fx.len(), 24);
     }
 
-    (0..lambda).into_par_iter().for_each(|i| {
-        let j = idx[i];
-        let x = H2[j].mul(X).mul(X).add(
-            H1[j].mul(X)
-        ).add(
-            H0[j]
-        );
-        assert_eq!(Hx[j], x);
-    });
-
-    let verified_time = Instant::now();
-
-    println!("RD_code:{:?} n:{:?} m:{:?} lambda:{:?} code_len:{:?}", RS_code, n, m, lambda, code_len);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("mid_time: {} ms", mid_time.duration_since(committed_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms", verified_time.duration_since(start_time).as_millis());
-    println!("Proof Size: {} bytes\n", (m + (100 as usize) * (3 + ((code_len as f64).log2()) as usize)) * 8);
-
+    // a proof for u' = u + 1 with the witness of u, and a proof for a witness that is not ternary, both with
+    // consistent merkle paths
+    fn rejects<F, E>(rs: bool)
+    where
+        F: PrimeField + Num + MulAcc,
+        E: ExtensionField<F>,
+    {
+        let (n, m, lambda) = (16, 24, 8);
+        let msg_len = 2 * m + n;
+        let expander = ExpanderCode::<F>::generate(&Code6::code_params(), msg_len, 0);
+        let code: Box<dyn LinearCode<F>> = if rs {
+            Box::new(ReedSolomonCode::new(msg_len, expander.codeword_len()))
+        }else{
+            Box::new(expander)
+        };
+        let verify = |A: &Array<F, Dim<[usize; 2]>>, u: &Array<F, Dim<[usize; 1]>>, proof: &LweProof<F, Blake3>| {
+            verify_ternary_lwe::<F, E, Blake3>(n, m, lambda, 0, rs, A, u, &*code, proof)
+        };
+
+        let (A, s, e, u) = sample_ternary_lwe::<F>(n, m);
+        let proof = prove_ternary_lwe::<F, E, Blake3>(n, m, lambda, 0, rs, &A, &u, &s, &e, &*code);
+        assert_eq!(verify(&A, &u, &proof), Ok(()));
+
+        // every coordinate differs, so the encoded difference is nonzero at almost every queried position
+        let other = u.mapv(|x| x + <F as Field>::one());
+        let proof = prove_ternary_lwe::<F, E, Blake3>(n, m, lambda, 0, rs, &A, &other, &s, &e, &*code);
+        assert!(matches!(verify(&A, &other, &proof), Err(VerifyError::LweRelationMismatch { .. })));
+
+        let s = s.mapv(|_| F::from(2));
+        let u = A.dot(&s) + &e;
+        let mut proof = prove_ternary_lwe::<F, E, Blake3>(n, m, lambda, 0, rs, &A, &u, &s, &e, &*code);
+        assert!(matches!(verify(&A, &u, &proof), Err(VerifyError::LweRelationMismatch { .. })));
+
+        // opened values that are not committed, and a proof of the wrong length
+        proof.queries[2].h1 += <F as Field>::one();
+        assert_eq!(verify(&A, &u, &proof), Err(VerifyError::MerklePathMismatch { index: 2 }));
+        proof.queries.pop();
+        assert_eq!(verify(&A, &u, &proof), Err(VerifyError::ShapeMismatch));
+    }
 
-}
\ No newline at end of file
+    #[test]
+    fn tampered_proofs() {
+        rejects::<Ft32, Ft32Ext4>(false);
+        rejects::<Ft32, Ft32Ext4>(true);
+        rejects::<Ft64, Ft64Ext2>(false);
+        rejects::<Ft64, Ft64>(true);
+    }
+}
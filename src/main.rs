@@ -1,141 +1,1032 @@
-mod codespec;
-mod fieldspec;
-mod codegen;
-mod encode;
-mod commit;
-mod helper;
-mod simple_zk;
-mod merkle;
-mod commit_zk;
-mod lwe;
-
-use std::collections::HashMap;
-use std::time::Instant;
-use std::sync::RwLock;
-use rand::Rng;
-use ff::Field;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+use std::time::Duration;
+use blake3::Hasher as Blake3;
+use clap::Args;
+use clap::Parser;
+use clap::Subcommand;
+use clap::ValueEnum;
 use ff::PrimeField;
-use ndarray::Array;
-use ndarray::Axis;
-use ndarray::Dim;
-use ndarray::parallel::prelude::*;
 use num_traits::Num;
-use sprs::MulAcc;
-use digest::Digest;
-use digest::Output;
-use sprs::CsMat;
-use fieldspec::ft32::Ft32;
-use fieldspec::ft127::Ft127;
-use fieldspec::ft255::Ft255;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
-use blake3::Hasher as Blake3;
-use num_traits::pow;
-use ndarray::parallel::prelude::*;
-use helper::binary_entropy;
-use helper::degree_bound;
-use codegen::generate_rev;
-use std::ops::Add;
-use encode::encode_rev;
-use encode::test_reverse_encoding;
-use encode::encode_zk_bench;
-use lwe::ternary_lwe;
+use sprs::MulAcc;
+use thesis::codespec::CodeParams;
+use thesis::codespec::CodeSpecification;
+use thesis::codespec::Code1;
+use thesis::codespec::Code2;
+use thesis::codespec::Code3;
+use thesis::codespec::Code4;
+use thesis::codespec::Code5;
+use thesis::codespec::Code6;
+use thesis::fieldspec::ft32::Ft32;
+use thesis::fieldspec::ft32::Ft32Ext2;
+use thesis::fieldspec::ft32::Ft32Ext4;
+use thesis::fieldspec::ft64::Ft64;
+use thesis::fieldspec::ft64::Ft64Ext2;
+use thesis::fieldspec::ft64::Ft64Ext4;
+use thesis::fieldspec::ft127::Ft127;
+use thesis::fieldspec::ft255::Ft255;
+use thesis::codecache::generate_cached_from;
+use thesis::codecache::generate_rev_cached_from;
+use thesis::codegen::generate_from;
+use thesis::codegen::generate_rev_from;
+use thesis::commit::TensorParams;
+use thesis::commit_zk::ZK_DEGREE;
+use thesis::commit_zk::ZkTensorParams;
+use thesis::lwe::run_ternary_lwe;
+use thesis::distance::seed_distance;
+use thesis::encode::codeword_length;
+use thesis::encode::encode_kernel_bench;
+use thesis::encode::encode_lazy_bench;
+use thesis::extension::ExtensionField;
+use thesis::helper::ENCODE_BATCH;
+use thesis::inspect::DENSE_MAX_MSG_LEN;
+use thesis::inspect::generator_matrix;
+use thesis::inspect::inspect;
+use thesis::inspect::write_dense_matrix_market;
+use thesis::inspect::write_matrix_market;
+use thesis::linear_code::CodeFamily;
+use thesis::linear_code::ExpanderCode;
+use thesis::linear_code::LinearCode;
+use thesis::params::lwe_test_no;
+use thesis::params::select_params;
+use thesis::pcs::Timings;
+use thesis::pcs::fit_shape;
+use thesis::pcs::random_coefs;
+use thesis::pcs::run;
+use thesis::serialize::Decode;
+use thesis::serialize::Encode;
+use thesis::serialize::from_bytes;
+use thesis::serialize::to_bytes;
+use thesis::PolynomialCommitment;
+use thesis::SimpleZkTensorCommitment;
+use thesis::TensorCommitment;
+use thesis::ZkTensorCommitment;
+
+#[derive(Parser)]
+#[command(about = "Tensor polynomial commitments over Brakedown codes")]
+struct Cli {
+    /// how results are written to stdout
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Commit to the coefficients in a file and write the commitment
+    Commit {
+        #[command(flatten)]
+        setup: Setup,
+        /// coefficients c0, c1, ... as whitespace separated decimal integers
+        #[arg(long)]
+        coefs: PathBuf,
+        /// where to write the commitment
+        #[arg(long)]
+        commitment: PathBuf,
+    },
+    /// Commit to the coefficients in a file, open the commitment and write commitment and proof
+    ///
+    /// `commit` does not save the prover state, so `prove` commits to the coefficients again and writes that
+    /// commitment too. For the plain and zk schemes it is the one `commit` writes; the simple zk scheme draws
+    /// fresh randomness, so check its proofs against the commitment written by `prove`.
+    Prove {
+        #[command(flatten)]
+        setup: Setup,
+        /// coefficients c0, c1, ... as whitespace separated decimal integers
+        #[arg(long)]
+        coefs: PathBuf,
+        /// where to write the commitment
+        #[arg(long)]
+        commitment: PathBuf,
+        /// where to write the proof
+        #[arg(long)]
+        proof: PathBuf,
+    },
+    /// Check a proof against a commitment, exits with status 1 if it is rejected
+    Verify {
+        #[command(flatten)]
+        setup: Setup,
+        #[arg(long)]
+        commitment: PathBuf,
+        #[arg(long)]
+        proof: PathBuf,
+    },
+    /// Commit to random coefficients, open, verify and report the timings
+    Bench {
+        #[command(flatten)]
+        setup: Setup,
+        /// number of coefficients, defaults to the product of the message lengths
+        #[arg(long)]
+        coef_no: Option<usize>,
+        /// pick --dim message lengths that hold --coef-no coefficients with little padding, overrides --msg-len and --shape
+        #[arg(long, requires = "coef_no")]
+        fit: bool,
+        #[command(flatten)]
+        lwe: LweArgs,
+    },
+    /// Estimate the minimum distance of generated codes and flag seeds below the code's distance
+    Distance {
+        #[arg(long, value_enum, default_value_t = Field::Ft255)]
+        field: Field,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = Code::Code6)]
+        code: Code,
+        #[command(flatten)]
+        args: DistanceArgs,
+    },
+    /// Time the allocation-free, batched and lazy encode kernels against sprs products on random columns
+    Encode {
+        #[arg(long, value_enum, default_value_t = Field::Ft255)]
+        field: Field,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = Code::Code6)]
+        code: Code,
+        /// file with alpha, beta, r and baselen of the code, overrides --code
+        #[arg(long)]
+        code_file: Option<PathBuf>,
+        #[arg(long, default_value_t = 1024)]
+        msg_len: usize,
+        /// number of columns encoded by each path
+        #[arg(long, default_value_t = 1024)]
+        columns: usize,
+        /// columns encoded together by the batched kernel
+        #[arg(long, default_value_t = ENCODE_BATCH)]
+        width: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Report the levels, rate, predicted distance and nonzero entries of a generated code and export its matrices
+    Inspect {
+        #[arg(long, value_enum, default_value_t = Field::Ft255)]
+        field: Field,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = Code::Code6)]
+        code: Code,
+        /// file with alpha, beta, r and baselen of the code, overrides --code
+        #[arg(long)]
+        code_file: Option<PathBuf>,
+        #[arg(long, default_value_t = 1024)]
+        msg_len: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// directory to write precode_i.mtx and postcode_i.mtx to in MatrixMarket format,
+        /// and generator.mtx for msg_len up to --dense-max
+        #[arg(long)]
+        export: Option<PathBuf>,
+        #[arg(long, default_value_t = DENSE_MAX_MSG_LEN)]
+        dense_max: usize,
+    },
+    /// Pick t, msg_len, code_len and the number of queries for a target soundness
+    Params {
+        #[arg(long, value_enum, default_value_t = Field::Ft255)]
+        field: Field,
+        /// degree of the extension of --field the challenges are drawn from
+        #[arg(long, default_value_t = 1)]
+        extension: usize,
+        #[arg(long, value_enum, ignore_case = true, default_value_t = Code::Code6)]
+        code: Code,
+        /// number of coefficients to commit to
+        #[arg(long)]
+        coef_no: usize,
+        /// bits of soundness
+        #[arg(long, default_value_t = 100.0)]
+        bits: f64,
+        /// largest tensor dimension to consider
+        #[arg(long, default_value_t = 4)]
+        max_dim: usize,
+    },
+}
+
+/// Public parameters shared by all subcommands, the verifier has to use the prover's values
+#[derive(Args, Clone)]
+struct Setup {
+    #[arg(long, value_enum, default_value_t = Scheme::Plain)]
+    scheme: Scheme,
+    #[arg(long, value_enum, default_value_t = Field::Ft255)]
+    field: Field,
+    /// degree of the extension of --field the challenges are drawn from, 2 and 4 are available for ft32 and ft64;
+    /// the committed data stays in --field
+    #[arg(long, default_value_t = 1)]
+    extension: usize,
+    #[arg(long, value_enum, ignore_case = true, default_value_t = Code::Code6)]
+    code: Code,
+    /// tensor dimension t
+    #[arg(long, default_value_t = 2)]
+    dim: usize,
+    /// message length m of every axis
+    #[arg(long, default_value_t = 100)]
+    msg_len: usize,
+    /// message lengths m0,m1,... of the axes, overrides --dim and --msg-len
+    #[arg(long, value_delimiter = ',')]
+    shape: Option<Vec<usize>>,
+    /// seed the code is generated from
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// number of queried columns
+    #[arg(long = "tests", default_value_t = 5)]
+    test_no: usize,
+    /// directory of generated codes, reused across runs instead of regenerating them
+    #[arg(long)]
+    code_cache: Option<PathBuf>,
+    /// file with alpha, beta, r and baselen of the code, overrides --code
+    #[arg(long)]
+    code_file: Option<PathBuf>,
+    /// code family applied along every axis, or one per axis as f0,f1,...; --code only describes the expander code
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [Family::Expander])]
+    family: Vec<Family>,
+    /// codeword length over message length of the reed-solomon and raa families, raa needs at least 3
+    #[arg(long, default_value_t = 2)]
+    expansion: usize,
+}
+
+impl Setup {
+    /// message length of every axis, from --shape or else --msg-len repeated --dim times
+    fn msg_lens(&self) -> Vec<usize> {
+        match &self.shape {
+            Some(shape) => shape.clone(),
+            None => vec![self.msg_len; self.dim],
+        }
+    }
+
+    /// code family of every axis, a single --family applies to all of them
+    fn families(&self) -> Vec<Family> {
+        match self.family.as_slice() {
+            [family] => vec![*family; self.msg_lens().len()],
+            families => families.to_vec(),
+        }
+    }
+}
+
+/// Codes searched by the `distance` subcommand
+#[derive(Args)]
+struct DistanceArgs {
+    /// file with alpha, beta, r and baselen of the code, overrides --code
+    #[arg(long)]
+    code_file: Option<PathBuf>,
+    #[arg(long, default_value_t = 100)]
+    msg_len: usize,
+    /// first seed to check
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// number of consecutive seeds to check
+    #[arg(long, default_value_t = 1)]
+    seeds: u64,
+    /// random codewords tried by each search
+    #[arg(long, default_value_t = 1000)]
+    trials: usize,
+    /// largest number of codewords the exhaustive search may look at
+    #[arg(long, default_value_t = 1000000)]
+    max_subsets: usize,
+}
+
+/// Sizes of the ternary LWE instance, only used by `--scheme lwe`
+#[derive(Args)]
+struct LweArgs {
+    /// rows of A
+    #[arg(long = "lwe-n", default_value_t = 1024)]
+    n: usize,
+    /// columns of A
+    #[arg(long = "lwe-m", default_value_t = 1024)]
+    m: usize,
+    /// number of queried positions
+    #[arg(long, default_value_t = 100)]
+    lambda: usize,
+    /// encode with Reed-Solomon instead of the Brakedown code
+    #[arg(long)]
+    rs: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Scheme {
+    Plain,
+    SimpleZk,
+    /// the planned zero-knowledge encoding, which does not hide the coefficients yet
+    Zk,
+    Lwe,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Field {
+    Ft32,
+    /// Goldilocks, 2^64 - 2^32 + 1
+    Ft64,
+    Ft127,
+    Ft255,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Code {
+    Code1,
+    Code2,
+    Code3,
+    Code4,
+    Code5,
+    Code6,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Family {
+    /// Brakedown expander code
+    Expander,
+    /// the expander code sampled again from the seed on every encode instead of being stored
+    Lazy,
+    ReedSolomon,
+    /// repeat-accumulate-accumulate
+    Raa,
+}
+
+impl Family {
+    fn code_family(self) -> CodeFamily {
+        match self {
+            Family::Expander => CodeFamily::Expander,
+            Family::Lazy => CodeFamily::Lazy,
+            Family::ReedSolomon => CodeFamily::ReedSolomon,
+            Family::Raa => CodeFamily::Raa,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    Json,
+}
+
+enum Value {
+    Number(String),
+    Bool(bool),
+    Text(String),
+}
+
+/// Ordered key-value pairs printed as the result of a subcommand
+struct Report {
+    entries: Vec<(&'static str, Value)>,
+}
+
+impl Report {
+    fn new(setup: &Setup) -> Self {
+        let mut report = Report { entries: Vec::new() };
+        report.text("scheme", &name(setup.scheme));
+        report.text("field", &name(setup.field));
+        report.number("extension", setup.extension);
+        if setup.scheme != Scheme::Lwe {
+            let families = setup.family.iter().map(|&family| name(family)).collect::<Vec<_>>();
+            report.text("family", &families.join(","));
+            if setup.family.iter().any(|family| matches!(family, Family::Expander | Family::Lazy)) {
+                match &setup.code_file {
+                    Some(path) => report.text("code", &path.display().to_string()),
+                    None => report.text("code", &name(setup.code)),
+                }
+            }
+            if setup.family.iter().any(|family| !matches!(family, Family::Expander | Family::Lazy)) {
+                report.number("expansion", setup.expansion);
+            }
+            let msg_lens = setup.msg_lens();
+            report.number("dim", msg_lens.len());
+            report.lens("msg_len", "msg_lens", &msg_lens);
+        }
+        report.number("seed", setup.seed);
+        report
+    }
+
+    fn number<T: ToString>(&mut self, key: &'static str, value: T) {
+        self.entries.push((key, Value::Number(value.to_string())));
+    }
+
+    // one length per axis, as a single number if all axes agree
+    fn lens(&mut self, key: &'static str, keys: &'static str, lens: &[usize]) {
+        if lens.iter().all(|&n| n == lens[0]) {
+            self.number(key, lens[0]);
+        }else{
+            let list = lens.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+            self.number(keys, format!("[{}]", list.join(", ")));
+        }
+    }
+
+    fn bool(&mut self, key: &'static str, value: bool) {
+        self.entries.push((key, Value::Bool(value)));
+    }
+
+    fn text(&mut self, key: &'static str, value: &str) {
+        self.entries.push((key, Value::Text(value.to_string())));
+    }
+
+    fn timings(&mut self, timings: &Timings) {
+        self.number("commit_time_ms", timings.commit.as_millis());
+        self.number("verify_time_ms", timings.verify.as_millis());
+        self.number("total_time_ms", timings.total().as_millis());
+    }
+
+    fn print(&self, format: Format) {
+        match format {
+            Format::Text => {
+                for (key, value) in self.entries.iter() {
+                    match value {
+                        Value::Number(x) => println!("{}: {}", key, x),
+                        Value::Bool(x) => println!("{}: {}", key, x),
+                        Value::Text(x) => println!("{}: {}", key, x),
+                    }
+                }
+            }
+            Format::Json => {
+                let fields = self
+                    .entries
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            Value::Number(x) => x.clone(),
+                            Value::Bool(x) => x.to_string(),
+                            Value::Text(x) => json_string(x),
+                        };
+                        format!("{}: {}", json_string(key), value)
+                    })
+                    .collect::<Vec<_>>();
+                println!("{{{}}}", fields.join(", "));
+            }
+        }
+    }
+}
+
+// the command line spelling of a value
+fn name<T: ValueEnum>(value: T) -> String {
+    value.to_possible_value().unwrap().get_name().to_string()
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+// call f::<F, C>(args) for the field and code selected on the command line
+macro_rules! dispatch {
+    ($field:expr, $code:expr, $f:ident ( $($arg:expr),* )) => {
+        match $field {
+            Field::Ft32 => dispatch!(@code [Ft32], $code, $f($($arg),*)),
+            Field::Ft64 => dispatch!(@code [Ft64], $code, $f($($arg),*)),
+            Field::Ft127 => dispatch!(@code [Ft127], $code, $f($($arg),*)),
+            Field::Ft255 => dispatch!(@code [Ft255], $code, $f($($arg),*)),
+        }
+    };
+    (@code [$($T:ty),*], $code:expr, $f:ident ( $($arg:expr),* )) => {
+        match $code {
+            Code::Code1 => $f::<$($T,)* Code1>($($arg),*),
+            Code::Code2 => $f::<$($T,)* Code2>($($arg),*),
+            Code::Code3 => $f::<$($T,)* Code3>($($arg),*),
+            Code::Code4 => $f::<$($T,)* Code4>($($arg),*),
+            Code::Code5 => $f::<$($T,)* Code5>($($arg),*),
+            Code::Code6 => $f::<$($T,)* Code6>($($arg),*),
+        }
+    };
+}
+
+// call f::<F, E, C>(args) for the field, the degree of the extension E the challenges are drawn from and the code
+macro_rules! dispatch_extension {
+    ($field:expr, $extension:expr, $code:expr, $f:ident ( $($arg:expr),* )) => {
+        match ($field, $extension) {
+            (Field::Ft32, 1) => dispatch!(@code [Ft32, Ft32], $code, $f($($arg),*)),
+            (Field::Ft32, 2) => dispatch!(@code [Ft32, Ft32Ext2], $code, $f($($arg),*)),
+            (Field::Ft32, 4) => dispatch!(@code [Ft32, Ft32Ext4], $code, $f($($arg),*)),
+            (Field::Ft64, 1) => dispatch!(@code [Ft64, Ft64], $code, $f($($arg),*)),
+            (Field::Ft64, 2) => dispatch!(@code [Ft64, Ft64Ext2], $code, $f($($arg),*)),
+            (Field::Ft64, 4) => dispatch!(@code [Ft64, Ft64Ext4], $code, $f($($arg),*)),
+            (Field::Ft127, 1) => dispatch!(@code [Ft127, Ft127], $code, $f($($arg),*)),
+            (Field::Ft255, 1) => dispatch!(@code [Ft255, Ft255], $code, $f($($arg),*)),
+            (field, degree) => Err(format!("--field {} has no extension of degree {}, ft32 and ft64 support 2 and 4", name(field), degree).into()),
+        }
+    };
+}
+
+fn check_setup(setup: &Setup) -> Result<()> {
+    if setup.scheme == Scheme::Lwe {
+        if setup.code_file.is_some() || setup.family != [Family::Expander] {
+            return Err("--scheme lwe does not support --code-file or --family, use --rs".into());
+        }
+        return Ok(());
+    }
+    let msg_lens = setup.msg_lens();
+    if msg_lens.len() < 2 {
+        return Err("--dim must be at least 2 and --shape list at least two lengths".into());
+    }
+    if setup.scheme == Scheme::Zk && msg_lens.len() != 2 {
+        return Err("--scheme zk only supports two axes".into());
+    }
+    if msg_lens.contains(&0) || setup.test_no == 0 {
+        return Err("message lengths and --tests must be positive".into());
+    }
+    if msg_lens.iter().try_fold(1usize, |acc, &m| acc.checked_mul(m)).is_none() {
+        return Err("the product of the message lengths does not fit into usize".into());
+    }
+    let families = setup.families();
+    if families.len() != msg_lens.len() {
+        return Err(format!("--family lists {} families for {} axes", families.len(), msg_lens.len()).into());
+    }
+    if setup.scheme == Scheme::Zk && families.iter().any(|&family| family != Family::Expander) {
+        return Err("--scheme zk only supports --family expander".into());
+    }
+    if families.contains(&Family::Lazy) && setup.code_cache.is_some() {
+        return Err("--family lazy keeps no matrices to cache, drop --code-cache".into());
+    }
+    // message lengths of the axes encoded by the reed-solomon and raa families
+    let expanded = msg_lens
+        .iter()
+        .zip(families.iter())
+        .filter(|(_, family)| !matches!(family, Family::Expander | Family::Lazy))
+        .map(|(&m, _)| m)
+        .collect::<Vec<_>>();
+    if !expanded.is_empty() && (setup.expansion < 2 || expanded.iter().any(|m| m.checked_mul(setup.expansion).is_none())) {
+        return Err("--expansion must be at least 2 and msg_len * expansion fit into usize".into());
+    }
+    if families.contains(&Family::Raa) && setup.expansion < 3 {
+        return Err("--family raa needs --expansion 3 or more, a single repetition has a constant distance".into());
+    }
+    Ok(())
+}
+
+fn read_coefs<F>(path: &Path) -> Result<Vec<F>>
+where
+    F: PrimeField,
+{
+    let text = fs::read_to_string(path)?;
+    text
+        .split_whitespace()
+        .enumerate()
+        .map(|(i, word)| {
+            F::from_str_vartime(word)
+                .ok_or_else(|| format!("{}: coefficient {} ({:?}) is not a field element", path.display(), i, word).into())
+        })
+        .collect()
+}
+
+fn read_file<T>(path: &Path) -> Result<T>
+where
+    T: Decode,
+{
+    let bytes = fs::read(path)?;
+    from_bytes::<T>(&bytes).map_err(|e| format!("{}: {}", path.display(), e).into())
+}
+
+fn write_file<T>(path: &Path, value: &T) -> Result<usize>
+where
+    T: Encode,
+{
+    let bytes = to_bytes(value);
+    fs::write(path, &bytes)?;
+    Ok(bytes.len())
+}
+
+// the code read from --code-file if there is one
+fn code_params<C>(code_file: Option<&Path>) -> Result<CodeParams>
+where
+    C: CodeSpecification,
+{
+    match code_file {
+        Some(path) => CodeParams::load(path).map_err(|e| format!("{}: {}", path.display(), e).into()),
+        None => Ok(C::code_params()),
+    }
+}
+
+// the code of `family` along an axis of msg_len coefficients, expander codes are taken from the cache if there is one
+fn axis_code<F>(setup: &Setup, family: CodeFamily, code: &CodeParams, msg_len: usize) -> Result<Arc<dyn LinearCode<F>>>
+where
+    F: PrimeField + Num + MulAcc,
+{
+    match (family, &setup.code_cache) {
+        (CodeFamily::Expander, Some(dir)) => {
+            let (precodes, postcodes) = generate_cached_from::<F>(dir, code, msg_len, setup.seed)?;
+            Ok(Arc::new(ExpanderCode::new(precodes, postcodes)))
+        }
+        _ => Ok(family.code(code, msg_len, setup.expansion, setup.seed)),
+    }
+}
+
+// the tensor parameters of the setup, axes of equal message length and family share their code
+fn tensor_params<F, C>(setup: &Setup) -> Result<TensorParams<F>>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+{
+    let code = code_params::<C>(setup.code_file.as_deref())?;
+    let families = setup.families().into_iter().map(Family::code_family).collect::<Vec<_>>();
+    TensorParams::with_family_codes(&setup.msg_lens(), &families, setup.seed, setup.test_no, |family, msg_len| {
+        axis_code::<F>(setup, family, &code, msg_len)
+    })
+}
+
+fn zk_params<F, C>(setup: &Setup) -> Result<ZkTensorParams<F>>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+{
+    let code = code_params::<C>(setup.code_file.as_deref())?;
+    let base = tensor_params::<F, C>(setup)?;
+    let rev_len = ZK_DEGREE * base.code_lens[0];
+    let (precodes_rev, postcodes_rev) = match &setup.code_cache {
+        Some(dir) => generate_rev_cached_from::<F>(dir, &code, rev_len, setup.seed)?,
+        None => generate_rev_from::<F>(&code, rev_len, setup.seed),
+    };
+    Ok(ZkTensorParams { base, degree: ZK_DEGREE, precodes_rev, postcodes_rev })
+}
+
+// the scheme's parameters and codeword lengths for the setup on the command line
+macro_rules! with_scheme {
+    ($setup:expr, $F:ty, $E:ty, $C:ty, $f:ident ( $($arg:expr),* )) => {
+        match $setup.scheme {
+            Scheme::Plain => {
+                let params = tensor_params::<$F, $C>($setup)?;
+                let code_lens = params.code_lens.clone();
+                $f::<$F, TensorCommitment<$F, $C, Blake3, $E>>(&params, &code_lens, $($arg),*)
+            }
+            Scheme::SimpleZk => {
+                let params = tensor_params::<$F, $C>($setup)?;
+                let code_lens = params.code_lens.clone();
+                $f::<$F, SimpleZkTensorCommitment<$F, $C, Blake3, $E>>(&params, &code_lens, $($arg),*)
+            }
+            Scheme::Zk => {
+                let params = zk_params::<$F, $C>($setup)?;
+                let code_lens = params.base.code_lens.clone();
+                $f::<$F, ZkTensorCommitment<$F, $C, Blake3, $E>>(&params, &code_lens, $($arg),*)
+            }
+            Scheme::Lwe => Err("--scheme lwe is only supported by bench".into()),
+        }
+    };
+}
+
+// fail unless coef_no coefficients fit into the setup's tensor
+fn check_fit(setup: &Setup, coef_no: usize) -> Result<()> {
+    let msg_lens = setup.msg_lens();
+    if coef_no > msg_lens.iter().product() {
+        let shape = msg_lens.iter().map(|m| m.to_string()).collect::<Vec<_>>();
+        return Err(format!("{} coefficients do not fit into {}", coef_no, shape.join(" * ")).into());
+    }
+    Ok(())
+}
+
+fn commit<F, E, C>(setup: &Setup, coefs: &Path, commitment: &Path) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    with_scheme!(setup, F, E, C, commit_with(setup, coefs, commitment))
+}
+
+fn commit_with<F, P>(params: &P::Params, code_lens: &[usize], setup: &Setup, coefs: &Path, commitment_path: &Path) -> Result<Report>
+where
+    F: PrimeField,
+    P: PolynomialCommitment<F>,
+    P::Commitment: Encode,
+{
+    let coefs = read_coefs::<F>(coefs)?;
+    check_fit(setup, coefs.len())?;
+    let (commitment, _) = P::commit_coefs(params, &coefs);
+    let commitment_bytes = write_file(commitment_path, &commitment)?;
+
+    let mut report = Report::new(setup);
+    report.lens("code_len", "code_lens", code_lens);
+    report.number("coef_no", coefs.len());
+    report.number("commitment_bytes", commitment_bytes);
+    Ok(report)
+}
+
+fn prove<F, E, C>(setup: &Setup, coefs: &Path, commitment: &Path, proof: &Path) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    with_scheme!(setup, F, E, C, prove_with(setup, coefs, commitment, proof))
+}
+
+fn prove_with<F, P>(params: &P::Params, code_lens: &[usize], setup: &Setup, coefs: &Path, commitment_path: &Path, proof_path: &Path) -> Result<Report>
+where
+    F: PrimeField,
+    P: PolynomialCommitment<F>,
+    P::Commitment: Encode,
+    P::Proof: Encode,
+{
+    let coefs = read_coefs::<F>(coefs)?;
+    check_fit(setup, coefs.len())?;
+    // the prover state is not persisted, so prove commits again
+    let (commitment, state) = P::commit_coefs(params, &coefs);
+    let proof = P::open(params, &state);
+    let commitment_bytes = write_file(commitment_path, &commitment)?;
+    let proof_bytes = write_file(proof_path, &proof)?;
+
+    let mut report = Report::new(setup);
+    report.lens("code_len", "code_lens", code_lens);
+    report.number("test_no", setup.test_no);
+    report.number("coef_no", coefs.len());
+    report.number("commitment_bytes", commitment_bytes);
+    report.number("proof_bytes", proof_bytes);
+    Ok(report)
+}
+
+fn verify<F, E, C>(setup: &Setup, commitment: &Path, proof: &Path) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    with_scheme!(setup, F, E, C, verify_with(setup, commitment, proof))
+}
+
+fn verify_with<F, P>(params: &P::Params, code_lens: &[usize], setup: &Setup, commitment_path: &Path, proof_path: &Path) -> Result<Report>
+where
+    F: PrimeField,
+    P: PolynomialCommitment<F>,
+    P::Commitment: Decode,
+    P::Proof: Decode,
+{
+    let commitment = read_file::<P::Commitment>(commitment_path)?;
+    let proof = read_file::<P::Proof>(proof_path)?;
+
+    let mut report = Report::new(setup);
+    report.lens("code_len", "code_lens", code_lens);
+    report.number("test_no", setup.test_no);
+    match P::verify(params, &commitment, &proof) {
+        Ok(()) => report.bool("verified", true),
+        Err(e) => {
+            report.bool("verified", false);
+            report.text("error", &e.to_string());
+        }
+    }
+    Ok(report)
+}
+
+fn bench<F, E, C>(setup: &Setup, coef_no: Option<usize>, fit: bool, lwe: &LweArgs) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    if setup.scheme == Scheme::Lwe {
+        let (proof, code_len, timings) = run_ternary_lwe::<F, E, C, Blake3>(lwe.n, lwe.m, lwe.lambda, setup.seed, lwe.rs)?;
+        let mut report = Report::new(setup);
+        report.text("code", &if lwe.rs { "rs".to_string() } else { name(setup.code) });
+        report.number("n", lwe.n);
+        report.number("m", lwe.m);
+        report.number("lambda", lwe.lambda);
+        report.number("code_len", code_len);
+        report.timings(&timings);
+        report.number("proof_bytes", to_bytes(&proof).len());
+        return Ok(report);
+    }
+    if fit {
+        let mut fitted = setup.clone();
+        fitted.shape = Some(fit_shape(coef_no.unwrap_or(1).max(1), setup.dim));
+        return bench::<F, E, C>(&fitted, coef_no, false, lwe);
+    }
+    let coef_no = coef_no.unwrap_or_else(|| setup.msg_lens().iter().product());
+    check_fit(setup, coef_no)?;
+    with_scheme!(setup, F, E, C, bench_with(setup, coef_no))
+}
+
+fn bench_with<F, P>(params: &P::Params, code_lens: &[usize], setup: &Setup, coef_no: usize) -> Result<Report>
+where
+    F: PrimeField,
+    P: PolynomialCommitment<F>,
+    P::Proof: Encode,
+{
+    let coefs = random_coefs::<F>(coef_no);
+    let (_, proof, timings) = run::<F, P>(params, &coefs)?;
+
+    let mut report = Report::new(setup);
+    report.lens("code_len", "code_lens", code_lens);
+    report.number("test_no", setup.test_no);
+    report.number("coef_no", coef_no);
+    report.timings(&timings);
+    report.number("proof_bytes", to_bytes(&proof).len());
+    Ok(report)
+}
+
+fn params<F, E, C>(field: Field, code: Code, coef_no: usize, bits: f64, max_dim: usize) -> Result<Report>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    if coef_no == 0 || max_dim < 2 {
+        return Err("--coef-no must be positive and --max-dim at least 2".into());
+    }
+    let selected = select_params::<F, E, C, Blake3>(coef_no, bits, max_dim)
+        .ok_or_else(|| format!("the field is too small for {} bits of soundness, try a larger --extension", bits))?;
+
+    let mut report = Report { entries: Vec::new() };
+    report.text("field", &name(field));
+    report.number("extension", E::DEGREE);
+    report.text("code", &name(code));
+    report.number("coef_no", coef_no);
+    report.number("dim", selected.dim);
+    report.number("msg_len", selected.msg_len);
+    report.number("code_len", selected.code_len);
+    report.number("test_no", selected.test_no);
+    report.number("soundness_bits", format!("{:.2}", selected.soundness_bits));
+    report.number("proof_bytes", selected.proof_bytes);
+    report.number("verifier_hashes", selected.verifier_hashes);
+    report.number("verifier_muls", selected.verifier_muls);
+    if let Some(lambda) = lwe_test_no::<F, E, C>(bits) {
+        report.number("lwe_lambda", lambda);
+    }
+    Ok(report)
+}
+
+fn distance<F, C>(field: Field, code: Code, args: &DistanceArgs) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+{
+    let params = code_params::<C>(args.code_file.as_deref())?;
+    if args.msg_len == 0 || args.seeds == 0 {
+        return Err("--msg-len and --seeds must be positive".into());
+    }
+    let end = args.seed.checked_add(args.seeds).ok_or("--seed + --seeds overflows")?;
+
+    let mut rng = ChaCha20Rng::seed_from_u64(args.seed);
+    let estimates = (args.seed..end)
+        .map(|seed| (seed, seed_distance::<F, _>(&params, args.msg_len, seed, args.trials, args.max_subsets, &mut rng)))
+        .collect::<Vec<_>>();
+    let bad = estimates
+        .iter()
+        .filter(|(_, estimate)| estimate.relative() < params.dist())
+        .map(|(seed, _)| seed.to_string())
+        .collect::<Vec<_>>();
+    let (worst_seed, worst) = estimates.iter().min_by_key(|(_, estimate)| estimate.min_weight).unwrap();
+
+    let mut report = Report { entries: Vec::new() };
+    report.text("field", &name(field));
+    match &args.code_file {
+        Some(path) => report.text("code", &path.display().to_string()),
+        None => report.text("code", &name(code)),
+    }
+    report.number("msg_len", args.msg_len);
+    report.number("code_len", worst.code_len);
+    report.number("target_distance", format!("{:.4}", params.dist()));
+    report.number("seeds", args.seeds);
+    report.number("worst_seed", worst_seed);
+    report.number("min_weight", worst.min_weight);
+    report.number("relative_distance", format!("{:.4}", worst.relative()));
+    report.bool("exact", worst.exact);
+    report.text("bad_seeds", &bad.join(","));
+    report.bool("passed", bad.is_empty());
+    Ok(report)
+}
+
+fn encode<F, C>(field: Field, code: Code, code_file: Option<&Path>, msg_len: usize, columns: usize, width: usize, seed: u64) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+{
+    let params = code_params::<C>(code_file)?;
+    if msg_len == 0 || columns == 0 || width == 0 {
+        return Err("--msg-len, --columns and --width must be positive".into());
+    }
+    let (precodes, postcodes) = generate_from::<F>(&params, msg_len, seed);
+    let timings = encode_kernel_bench(&precodes, &postcodes, columns, width);
+    let speedup = |time: Duration| format!("{:.2}", timings.dot.as_secs_f64() / time.as_secs_f64());
+
+    let mut report = Report { entries: Vec::new() };
+    report.text("field", &name(field));
+    match code_file {
+        Some(path) => report.text("code", &path.display().to_string()),
+        None => report.text("code", &name(code)),
+    }
+    report.number("msg_len", msg_len);
+    report.number("code_len", codeword_length(&precodes, &postcodes));
+    report.number("seed", seed);
+    report.number("columns", columns);
+    report.number("width", width);
+    report.number("dot_time_ms", timings.dot.as_millis());
+    report.number("kernel_time_ms", timings.kernel.as_millis());
+    report.number("batch_time_ms", timings.batch.as_millis());
+    report.number("kernel_speedup", speedup(timings.kernel));
+    report.number("batch_speedup", speedup(timings.batch));
+    // matrices sampled again from the seed for every block instead of stored
+    let lazy = encode_lazy_bench::<F>(&params, msg_len, seed, columns, width);
+    report.number("generate_time_ms", lazy.generate.as_millis());
+    report.number("lazy_setup_time_ms", lazy.lazy_setup.as_millis());
+    report.number("lazy_time_ms", lazy.lazy.as_millis());
+    report.number("lazy_slowdown", format!("{:.2}", lazy.lazy.as_secs_f64() / lazy.batch.as_secs_f64()));
+    report.number("matrix_bytes", lazy.matrix_bytes);
+    report.number("lazy_bytes", lazy.lazy_bytes);
+    Ok(report)
+}
+
+fn inspect_code<F, C>(field: Field, code: Code, code_file: Option<&Path>, msg_len: usize, seed: u64, export: Option<&Path>, dense_max: usize) -> Result<Report>
+where
+    F: PrimeField + Num + MulAcc,
+    C: CodeSpecification,
+{
+    let params = code_params::<C>(code_file)?;
+    if msg_len == 0 {
+        return Err("--msg-len must be positive".into());
+    }
+    let (precodes, postcodes) = generate_from::<F>(&params, msg_len, seed);
+    let info = inspect(&params, &precodes, &postcodes);
+
+    let mut report = Report { entries: Vec::new() };
+    report.text("field", &name(field));
+    match code_file {
+        Some(path) => report.text("code", &path.display().to_string()),
+        None => report.text("code", &name(code)),
+    }
+    report.number("msg_len", msg_len);
+    report.number("code_len", info.code_len);
+    report.number("seed", seed);
+    report.number("rate", format!("{:.4}", info.rate()));
+    report.number("predicted_distance", format!("{:.4}", info.predicted_distance));
+    report.number("predicted_min_weight", info.predicted_min_weight());
+    // [ni, mi, cn, ni', mi', dn, precode nonzeros, postcode nonzeros] per level
+    let levels = info
+        .levels
+        .iter()
+        .map(|l| format!("[{}, {}, {}, {}, {}, {}, {}, {}]", l.ni, l.mi, l.cn, l.nip, l.mip, l.dn, l.pre_nnz, l.post_nnz))
+        .collect::<Vec<_>>();
+    report.number("levels", format!("[{}]", levels.join(", ")));
+    report.number("base_len", info.base_len);
+    report.number("rs_len", info.rs_len);
+    report.number("nnz", info.nnz);
+
+    if let Some(dir) = export {
+        fs::create_dir_all(dir)?;
+        let mut files = 0;
+        for (kind, matrices) in [("precode", &precodes), ("postcode", &postcodes)] {
+            for (i, matrix) in matrices.iter().enumerate() {
+                let file = fs::File::create(dir.join(format!("{}_{}.mtx", kind, i)))?;
+                write_matrix_market(std::io::BufWriter::new(file), matrix)?;
+                files += 1;
+            }
+        }
+        let dense = msg_len <= dense_max;
+        if dense {
+            let columns = generator_matrix(&ExpanderCode::new(precodes, postcodes));
+            let file = fs::File::create(dir.join("generator.mtx"))?;
+            write_dense_matrix_market(std::io::BufWriter::new(file), &columns)?;
+            files += 1;
+        }
+        report.text("export", &dir.display().to_string());
+        report.number("exported_files", files);
+        report.bool("generator_exported", dense);
+    }
+    Ok(report)
+}
 
 fn main() {
-    // rayon::ThreadPoolBuilder::new().num_threads(8).build_global().unwrap();
-    
-    // commit::commit_2_dim::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 1024, 1762, 0, 100);
-    commit::commit_2_dim::<Ft255, codespec::Code6, Blake3>(10000, 100, 172, 0, 5);
-    commit_zk::commit_2_dim_zk::<Ft255, codespec::Code6, Blake3>(10000, 100, 172, 0, 5);
-    // commit::commit_3_dim::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 101, 174, 0, 100);
-    // commit::commit_3_dim::<Ft255, codespec::Code6, Blake3>(27000, 30, 52, 0, 5);
-    // commit::commit_4_dim::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 32, 56, 0, 100);
-    // commit::commit_4_dim::<Ft255, codespec::Code6, Blake3>(65536, 16, 28, 0, 5);
-    
-    // simple_zk::commit_2_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 1024, 1762, 0, 100);
-    // simple_zk::commit_2_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(10000, 100, 172, 0, 5);
-    // simple_zk::commit_3_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 101, 174, 0, 100);
-    // simple_zk::commit_3_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(27000, 30, 52, 0, 5);
-    // simple_zk::commit_4_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 32, 56, 0, 100);
-    // simple_zk::commit_4_dim_simple_zk::<Ft255, codespec::Code6, Blake3>(65536, 16, 28, 0, 5);
-
-    // commit_zk::commit_2_dim_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 1024, 1762, 0, 100);
-    // commit_zk::commit_2_dim_zk::<Ft255, codespec::Code6, Blake3>(10000, 100, 172, 0, 5);
-    // commit_zk::commit_3_dim_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 101, 174, 0, 100);
-    // commit_zk::commit_3_dim_zk::<Ft255, codespec::Code6, Blake3>(27000, 30, 52, 0, 5);
-    // commit_zk::commit_4_dim_zk::<Ft255, codespec::Code6, Blake3>(pow(2usize, 20), 32, 56, 0, 100);
-    // commit_zk::commit_4_dim_zk::<Ft255, codespec::Code6, Blake3>(65536, 16, 28, 0, 5);
-
-    // println!("{}", binary_entropy(0.5));
-    // println!("{}", binary_entropy(0.1));
-    // println!("{}", degree_bound(1.0/1.72, 256, 1762));
-    // println!("{}", degree_bound(1.0/1.72, 256, 174));
-    // println!("{}", degree_bound(1.0/1.72, 256, 56));
-
-    // let (precodes, postcodes) = generate_rev::<Ft255, codespec::Code6>(1762, 0);
-    // let mut data = Vec::<Ft255>::new();
-    // let mut cur = <Ft255 as Field>::zero();
-    // for i in 0..1762 {
-    //     data.push(cur);
-    //     cur = cur.add(<Ft255 as Field>::one());
-    // }
-    // // println!("{:?}", data);
-    // encode_rev::<Ft255, _>(&mut data, &precodes, &postcodes);
-
-
-    // // test reverse encoding
-    // test_reverse_encoding::<Ft255, codespec::Code6>();
-
-    // println!("{}", degree_bound(0.5, 256, 128));
-    // println!("{}", degree_bound(0.45, 256, 128));
-    // println!("{}", degree_bound(0.40, 256, 128));
-    // println!("{}", degree_bound(0.35, 256, 128));
-    // println!("{}", degree_bound(0.30, 256, 128));
-    // println!("{}", degree_bound(0.25, 256, 128));
-    // println!("{}", degree_bound(0.20, 256, 128));
-    // println!("{}", degree_bound(0.15, 256, 128));
-    // println!("{}", degree_bound(0.10, 256, 128));
-    // println!("");
-
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.5, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.45, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.40, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.35, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.30, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.25, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.20, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.15, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.10, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.32, 256, 128));
-    // encode_zk_bench::<Ft255>(128, degree_bound(0.30, 256, 128));
-
-
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 2048, 100, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(128, 128, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(256, 128, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(512, 128, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 128, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 256, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 512, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 1024, 100, 0, false);
-
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(128, 128, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(256, 128, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(512, 128, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 128, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 256, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 512, 100, 0, true);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 1024, 100, 0, true);
-
-
-
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 1024, 100, 0, false);
-    // ternary_lwe::<Ft255, codespec::Code6, Blake3>(1024, 1024, 100, 0, false);
-
-    // for i in (7..12){
-    // for j in (7..12) {
-    //         ternary_lwe::<Ft32, codespec::Code6, Blake3>(pow(2usize, i), pow(2usize, j), 200, 0, false);
-    //     }
-    // }
-
-}
\ No newline at end of file
+    let cli = Cli::parse();
+    let result = match &cli.command {
+        Command::Commit { setup, coefs, commitment } => {
+            check_setup(setup).and_then(|_| dispatch_extension!(setup.field, setup.extension, setup.code, commit(setup, coefs, commitment)))
+        }
+        Command::Prove { setup, coefs, commitment, proof } => {
+            check_setup(setup).and_then(|_| dispatch_extension!(setup.field, setup.extension, setup.code, prove(setup, coefs, commitment, proof)))
+        }
+        Command::Verify { setup, commitment, proof } => {
+            check_setup(setup).and_then(|_| dispatch_extension!(setup.field, setup.extension, setup.code, verify(setup, commitment, proof)))
+        }
+        Command::Bench { setup, coef_no, fit, lwe } => {
+            check_setup(setup).and_then(|_| dispatch_extension!(setup.field, setup.extension, setup.code, bench(setup, *coef_no, *fit, lwe)))
+        }
+        Command::Distance { field, code, args } => dispatch!(*field, *code, distance(*field, *code, args)),
+        Command::Encode { field, code, code_file, msg_len, columns, width, seed } => {
+            dispatch!(*field, *code, encode(*field, *code, code_file.as_deref(), *msg_len, *columns, *width, *seed))
+        }
+        Command::Inspect { field, code, code_file, msg_len, seed, export, dense_max } => {
+            dispatch!(*field, *code, inspect_code(*field, *code, code_file.as_deref(), *msg_len, *seed, export.as_deref(), *dense_max))
+        }
+        Command::Params { field, extension, code, coef_no, bits, max_dim } => {
+            dispatch_extension!(*field, *extension, *code, params(*field, *code, *coef_no, *bits, *max_dim))
+        }
+    };
+    match result {
+        Ok(report) => {
+            report.print(cli.format);
+            let rejected = report
+                .entries
+                .iter()
+                .any(|(key, value)| (*key == "verified" || *key == "passed") && matches!(value, Value::Bool(false)));
+            if rejected {
+                process::exit(1);
+            }
+        }
+        Err(e) => {
+            let mut report = Report { entries: Vec::new() };
+            report.text("error", &e.to_string());
+            report.print(cli.format);
+            process::exit(2);
+        }
+    }
+}
@@ -1,156 +1,201 @@
-use std::collections::HashMap;
-use std::sync::RwLock;
-use std::mem::drop;
 use digest::Digest;
 use digest::Output;
 use ff::PrimeField;
-use ndarray::Dim;
-use ndarray::Array;
+use ndarray::ArrayViewD;
 use ndarray::parallel::prelude::*;
 use crate::helper::next_pow_2;
 
-pub fn build_merkle_tree<D>(data: &mut Vec::<Output<D>>, np2: usize)
+// A tree over leaf_no leaves is the tree over next_pow_2(leaf_no) leaves padded with empty subtrees, but only the
+// nodes above actual leaves are stored: level by level from the root, with ceil(leaf_no / 2^(depth - k)) nodes on
+// level k. A node is hashed with the default digest in place of a missing sibling, so neither the padding leaves
+// nor the empty subtrees above them are allocated or hashed. Positions are still those of the padded tree,
+// node i has children 2i+1 and 2i+2 and the root is node 0, see `leaf_index` and `check_merkle_path`
+
+// number of nodes on every level of a tree with leaf_no leaves, from the root down to the leaves
+fn level_sizes(leaf_no: usize) -> Vec<usize> {
+    assert!(leaf_no >= 1);
+    let depth = next_pow_2(leaf_no).trailing_zeros();
+    (0..=depth).map(|k| leaf_no.div_ceil(1 << (depth - k))).collect()
+}
+
+// number of nodes stored for a tree with leaf_no leaves, which are the last leaf_no of them
+pub fn merkle_tree_len(leaf_no: usize) -> usize {
+    level_sizes(leaf_no).iter().sum()
+}
+
+// hash the inner nodes of a tree with leaf_no leaves stored in data[(merkle_tree_len(leaf_no) - leaf_no)..]
+pub fn build_merkle_tree<D>(data: &mut Vec::<Output<D>>, leaf_no: usize)
 where
     D: Digest,
 {
-    for i in (0..(np2-1)).rev() {
-        let mut digest = D::new();
-        digest.update(&data[2*i+1]);
-        digest.update(&data[2*i+2]);
-        data[i] = digest.finalize();
+    let sizes = level_sizes(leaf_no);
+    assert_eq!(data.len(), sizes.iter().sum::<usize>());
+    let mut offset = data.len() - leaf_no;
+    for k in (0..(sizes.len() - 1)).rev() {
+        let (upper, lower) = data.split_at_mut(offset);
+        let upper = &mut upper[(offset - sizes[k])..];
+        for (p, x) in upper.iter_mut().enumerate() {
+            let mut digest = D::new();
+            digest.update(&lower[2*p]);
+            match lower[..sizes[k + 1]].get(2*p+1) {
+                Some(right) => digest.update(right),
+                None => digest.update(Output::<D>::default()),
+            }
+            *x = digest.finalize();
+        }
+        offset -= sizes[k];
     }
 }
 
+// collect the sibling hashes on the way from node idx up to the root of a tree with leaf_no leaves
+pub fn merkle_path<D>(
+    hashes_vec: &[Output<D>],
+    leaf_no: usize,
+    idx: usize
+) -> Vec<Output<D>>
+where
+    D: Digest
+{
+    let sizes = level_sizes(leaf_no);
+    let depth = sizes.len() - 1;
+    assert!(idx + 1 >= 1 << depth && idx + 1 - (1 << depth) < leaf_no);
+    let mut pos = idx + 1 - (1 << depth);
+    let mut offset = hashes_vec.len() - leaf_no;
+    let mut path = Vec::<Output<D>>::with_capacity(depth);
+    for k in (1..=depth).rev() {
+        let sibling = pos ^ 1;
+        if sibling < sizes[k] {
+            path.push(hashes_vec[offset + sibling].clone());
+        }else{
+            path.push(Output::<D>::default());
+        }
+        pos /= 2;
+        offset -= sizes[k - 1];
+    }
+    return path;
+}
+
+// recompute the root from node idx and its sibling hashes
 pub fn check_merkle_path<D>(
     mut cur_hash: Output<D>, 
     mut idx: usize, 
-    hashes_map_rwlock: &RwLock<HashMap::<usize, Output<D>>>,
-    hashes_vec: &Vec<Output<D>>
+    path: &[Output<D>],
+    root: &Output<D>
 ) -> bool 
 where
     D: Digest
 {
+    let mut siblings = path.iter();
     while idx > 0 {
-        let mut rwlock_write = hashes_map_rwlock.write().unwrap();
-        match (*rwlock_write).get(&idx) {
-            None => {
-                (*rwlock_write).insert(idx, cur_hash.clone());
-            },
-            Some(h) => {
-                if !cur_hash.eq(h) {
-                    return false;
-                }
-            },
-        }
-        drop(rwlock_write);
-
+        let sibling = match siblings.next() {
+            None => return false,
+            Some(h) => h,
+        };
         let mut digest = D::new();
-        if idx % 2 == 0 {
-            digest.update(&hashes_vec[idx-1]);
+        if idx.is_multiple_of(2) {
+            digest.update(sibling);
             digest.update(&cur_hash);
         }else{
             digest.update(&cur_hash);
-            digest.update(&hashes_vec[idx+1]);
+            digest.update(sibling);
         }
         cur_hash = digest.finalize();
         idx = (idx - 1) / 2;
     }
-    let rwlock_read = hashes_map_rwlock.read().unwrap();
-    return cur_hash.eq(&((*rwlock_read)[&0]));
+    return siblings.next().is_none() && cur_hash.eq(root);
 }
 
-pub fn merkle_tree_commit_2d<F, D>(
-    msg_len: usize, 
-    code_len: usize,
-    m_2d: &Array<F, Dim<[usize; 2]>>
+// commit to the rows along the last axis of a (N1, N2, ..., *) tensor, hashing the first msg_len entries of every row;
+// the row at (i1, i2, ...) is the leaf i1 + i2 * N1 + i3 * N1 * N2 + ...
+pub fn merkle_tree_commit<F, D>(
+    msg_len: usize,
+    m: &ArrayViewD<F>
 ) -> Vec<Output<D>>
 where
     F: PrimeField,
     D: Digest,
 {
-    assert!(m_2d.shape() == &[code_len, msg_len] || m_2d.shape() == &[code_len, code_len]);
+    let t = m.ndim();
+    assert!(t >= 2);
+    let code_lens = &m.shape()[..(t - 1)];
+    let row_len = m.shape()[t - 1];
+    assert!(row_len >= msg_len);
 
     let mut hashes_vec = Vec::<Output<D>>::new();
-    let item_no = code_len;
-    let np2 = next_pow_2(item_no);
-    hashes_vec.resize_with(2*np2-1, Default::default);
-    (&mut hashes_vec)
+    let item_no = code_lens.iter().product::<usize>();
+    let m_2d = m.view().into_shape((item_no, row_len)).unwrap();
+    hashes_vec.resize_with(merkle_tree_len(item_no), Default::default);
+    let leaf_start = hashes_vec.len() - item_no;
+    (&mut hashes_vec[leaf_start..])
         .into_par_iter()
         .enumerate()
-        .filter(|(i, _)| i >= &(np2-1) && i < &(np2-1+item_no))
         .for_each(|(i, x)| {
+        // (i1, i2, ...) is stored in row ((i1 * N2 + i2) * N3 + ...) of m_2d
+        let mut leaf = i;
+        let mut row = 0;
+        for &code_len in code_lens.iter() {
+            row = row * code_len + leaf % code_len;
+            leaf /= code_len;
+        }
         let mut digest = D::new();
-        for i2 in 0..msg_len {
-            let i1 = i-(np2-1);
-            digest.update(m_2d[[i1, i2]].to_repr());
+        for j in 0..msg_len {
+            digest.update(m_2d[[row, j]].to_repr());
         }
         *x = digest.finalize();
     });
-    build_merkle_tree::<D>(&mut hashes_vec, np2);
+    build_merkle_tree::<D>(&mut hashes_vec, item_no);
     return hashes_vec;
 }
 
-pub fn merkle_tree_commit_3d<F, D>(
-    msg_len: usize, 
-    code_len: usize,
-    m_3d: &Array<F, Dim<[usize; 3]>>
-) -> Vec<Output<D>>
+// hash a row of a tensor, i.e. a leaf of its merkle tree
+pub fn hash_row<F, D>(row: &[F]) -> Output<D>
 where
     F: PrimeField,
     D: Digest,
 {
-    assert!(m_3d.shape() == &[code_len, code_len, msg_len] || m_3d.shape() == &[code_len, code_len, code_len]);
+    let mut digest = D::new();
+    for x in row.iter() {
+        digest.update(x.to_repr());
+    }
+    return digest.finalize();
+}
 
-    let mut hashes_vec = Vec::<Output<D>>::new();
-    let item_no = code_len * code_len;
+// position in the (padded) merkle tree of the leaf for the row at (i1, i2, ...) of a (N1, N2, ..., *) tensor
+pub fn leaf_index(code_lens: &[usize], idx: &[usize]) -> usize {
+    assert_eq!(code_lens.len(), idx.len());
+    let item_no = code_lens.iter().product::<usize>();
     let np2 = next_pow_2(item_no);
-    hashes_vec.resize_with(2*np2-1, Default::default);
-    (&mut hashes_vec)
-        .into_par_iter()
-        .enumerate()
-        .filter(|(i, _)| i >= &(np2-1) && i < &(np2-1+item_no))
-        .for_each(|(i, x)| {
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            let i1 = (i-(np2-1)) % code_len;
-            let i2 = (i-(np2-1)) / code_len;
-            digest.update(m_3d[[i1, i2, i3]].to_repr());
-        }
-        *x = digest.finalize();
-    });
-    build_merkle_tree::<D>(&mut hashes_vec, np2);
-    return hashes_vec;
+    let leaf = idx.iter().zip(code_lens.iter()).rev().fold(0, |acc, (&i, &code_len)| acc * code_len + i);
+    return leaf + np2 - 1;
 }
 
-pub fn merkle_tree_commit_4d<F, D>(
-    msg_len: usize, 
-    code_len: usize,
-    m_4d: &Array<F, Dim<[usize; 4]>>
-) -> Vec<Output<D>>
-where
-    F: PrimeField,
-    D: Digest,
-{
-    assert_eq!(m_4d.shape(), &[code_len, code_len, code_len, msg_len]);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use ndarray::Array;
+    use crate::fieldspec::ft32::Ft32;
 
-    let mut hashes_vec = Vec::<Output<D>>::new();
-    let item_no = code_len * code_len * code_len;
-    let np2 = next_pow_2(item_no);
-    hashes_vec.resize_with(2*np2-1, Default::default);
-    (&mut hashes_vec)
-        .into_par_iter()
-        .enumerate()
-        .filter(|(i, _)| i >= &(np2-1) && i < &(np2-1+item_no))
-        .for_each(|(i, x)| {
-        let mut digest = D::new();
-        for i4 in 0..msg_len {
-            let i1 = (i-(np2-1)) % code_len;
-            let i2 = (i-(np2-1)) / code_len % code_len;
-            let i3 = (i-(np2-1)) / code_len / code_len;
-            digest.update(m_4d[[i1, i2, i3, i4]].to_repr());
+    #[test]
+    fn unpadded_trees() {
+        for (n1, n2) in [(1, 1), (3, 3), (5, 5), (6, 6), (8, 8), (3, 7), (6, 2)] {
+            let m = Array::from_shape_fn((n1, n2, 2), |(i, j, k)| Ft32::from((100 * i + 10 * j + k) as u64)).into_dyn();
+            let hashes = merkle_tree_commit::<Ft32, Blake3>(2, &m.view());
+            // at most one node per level more than a full tree over n1 * n2 leaves, instead of 2 * next_pow_2 - 1
+            let depth = (n1 * n2).next_power_of_two().trailing_zeros() as usize;
+            assert_eq!(hashes.len(), merkle_tree_len(n1 * n2));
+            assert!(hashes.len() <= 2 * n1 * n2 - 1 + depth);
+            for i in 0..n1 {
+                for j in 0..n2 {
+                    let leaf = leaf_index(&[n1, n2], &[i, j]);
+                    assert_eq!(leaf + 1 - next_pow_2(n1 * n2), i + j * n1);
+                    let row = [m[[i, j, 0]], m[[i, j, 1]]];
+                    let path = merkle_path::<Blake3>(&hashes, n1 * n2, leaf);
+                    assert_eq!(path.len(), depth);
+                    assert!(check_merkle_path::<Blake3>(hash_row::<Ft32, Blake3>(&row), leaf, &path, &hashes[0]));
+                    assert!(!check_merkle_path::<Blake3>(hash_row::<Ft32, Blake3>(&row[..1]), leaf, &path, &hashes[0]));
+                }
+            }
         }
-        *x = digest.finalize();
-    });
-    build_merkle_tree::<D>(&mut hashes_vec, np2);
-    return hashes_vec;
-}
\ No newline at end of file
+    }
+}
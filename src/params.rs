@@ -0,0 +1,263 @@
+use ff::Field;
+use ff::PrimeField;
+use digest::Digest;
+use sprs::CsMat;
+use crate::codegen::generate;
+use crate::codespec::CodeSpecification;
+use crate::encode::codeword_length;
+use crate::encode::ntt_size;
+use crate::extension::ExtensionField;
+use crate::helper::next_pow_2;
+
+/// Parameters of a tensor commitment chosen for a target soundness, with the predicted costs
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedParams {
+    /// tensor dimension t
+    pub dim: usize,
+    pub msg_len: usize,
+    pub code_len: usize,
+    /// number of queried columns
+    pub test_no: usize,
+    /// -log2 of the soundness error
+    pub soundness_bits: f64,
+    /// size of the encoded `TensorProof`
+    pub proof_bytes: usize,
+    /// hash compressions done by the verifier
+    pub verifier_hashes: usize,
+    /// field multiplications done by the verifier
+    pub verifier_muls: usize,
+}
+
+/// size of F, i.e. the modulus p
+pub fn field_size<F>() -> f64
+where
+    F: PrimeField,
+{
+    let p_minus_one = (<F as Field>::zero() - <F as Field>::one()).to_repr();
+    // the repr is little endian
+    p_minus_one.as_ref().iter().rev().fold(0f64, |acc, &b| acc * 256f64 + b as f64) + 1f64
+}
+
+/// size of the extension E of F the verifier's challenges are drawn from, p^E::DEGREE
+pub fn extension_size<F, E>() -> f64
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+{
+    field_size::<F>().powi(E::DEGREE as i32)
+}
+
+/// soundness error of the t-dimensional tensor test with relative distance `distance` and test_no queries
+pub fn soundness_error(distance: f64, test_no: usize, dim: usize, code_len: usize, field_size: f64) -> f64 {
+    let d = code_len as f64 * distance;
+    let part1 = d * (d.powi(dim as i32) - 1f64) / (4f64 * (d - 1f64) * field_size);
+    let part2 = (1f64 - (distance.powi(dim as i32) / 4f64).min(0.25)).powf(test_no as f64);
+    let part3 = (1f64 - distance.powi(dim as i32) / 2f64).powf(test_no as f64);
+    return part1 + part2 + part3;
+}
+
+/// soundness errors of the four rounds of the ternary LWE proof with lambda = test_no queries,
+/// the proof is as sound as the largest of them
+pub fn lwe_soundness_error(field_size: f64, distance: f64, test_no: usize) -> [f64; 4] {
+    let q = test_no as f64;
+    let part1 = 2f64 / field_size + (field_size - 2f64) / field_size * (1f64 - distance).powf(q);
+    let part2 = (1f64 - distance).powf(q);
+    let part3 = 2f64 / (field_size - 1f64) + (field_size - 3f64) / (field_size - 1f64) * (1f64 - 29f64 / 30f64 * distance).powf(q);
+    let part4 = (1f64 - 7f64 / 10f64 * distance).powf(q);
+    return [part1, part2, part3, part4];
+}
+
+// smallest q in 1..=2^32 with error(q) <= target, error has to be non-increasing in q
+fn min_queries<E>(target: f64, error: E) -> Option<usize>
+where
+    E: Fn(usize) -> f64,
+{
+    let (mut lo, mut hi) = (1usize, 1usize << 32);
+    if error(hi) > target {
+        return None;
+    }
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if error(mid) <= target {
+            hi = mid;
+        }else{
+            lo = mid + 1;
+        }
+    }
+    return Some(lo);
+}
+
+/// number of queries lambda for which the ternary LWE proof with X drawn from E reaches `bits` of soundness
+pub fn lwe_test_no<F, E, C>(bits: f64) -> Option<usize>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+{
+    let field_size = extension_size::<F, E>();
+    min_queries(2f64.powf(-bits), |q| {
+        lwe_soundness_error(field_size, C::dist(), q).iter().cloned().fold(0f64, f64::max)
+    })
+}
+
+// field multiplications of one `encode`: the sparse matrices and the Reed-Solomon base case
+fn encode_muls<F>(precodes: &[CsMat<F>], postcodes: &[CsMat<F>]) -> usize
+where
+    F: PrimeField,
+{
+    let nnz = precodes.iter().chain(postcodes.iter()).map(|code| code.nnz()).sum::<usize>();
+    let (rs_msg_len, rs_code_len) = (precodes.last().unwrap().rows(), postcodes.last().unwrap().cols());
+    let rs_muls = match ntt_size::<F>(rs_msg_len, rs_code_len) {
+        Some(n) => n / 2 * n.trailing_zeros() as usize,
+        None => rs_msg_len * rs_code_len,
+    };
+    nnz + rs_muls
+}
+
+/// Parameters of a t-dimensional tensor commitment to coef_no coefficients with `bits` of soundness and challenges
+/// drawn from E, None if E is too small for t
+pub fn tensor_params<F, E, C, D>(coef_no: usize, dim: usize, bits: f64) -> Option<SelectedParams>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+    D: Digest,
+{
+    assert!(dim >= 2);
+    assert!(coef_no >= 1);
+
+    // smallest m with m^t >= coef_no
+    let mut msg_len = (coef_no as f64).powf(1f64 / dim as f64).ceil() as usize;
+    while msg_len > 1 && (msg_len - 1).checked_pow(dim as u32).is_some_and(|n| n >= coef_no) {
+        msg_len -= 1;
+    }
+    while msg_len.checked_pow(dim as u32).is_some_and(|n| n < coef_no) {
+        msg_len += 1;
+    }
+
+    let (precodes, postcodes) = generate::<F, C>(msg_len, 0);
+    let code_len = codeword_length(&precodes, &postcodes);
+
+    let field_size = extension_size::<F, E>();
+    let target = 2f64.powf(-bits);
+    let test_no = min_queries(target, |q| soundness_error(C::dist(), q, dim, code_len, field_size))?;
+    let soundness_bits = -soundness_error(C::dist(), test_no, dim, code_len, field_size).log2();
+
+    // TensorProof as written by `serialize::to_bytes`, the rows of M1, ..., M(t-1) are over E
+    let field_bytes = F::Repr::default().as_ref().len();
+    let ext_bytes = E::DEGREE * field_bytes;
+    let digest_bytes = <D as Digest>::output_size();
+    let len_bytes = 8;
+    // the merkle tree of M(k) has N^(t-1-k) leaves
+    let depths = (0..(dim - 1))
+        .map(|k| next_pow_2(code_len.pow((dim - 1 - k) as u32)).trailing_zeros() as usize)
+        .collect::<Vec<_>>();
+    let query_bytes = len_bytes
+        + (len_bytes + msg_len * field_bytes)
+        + (dim - 2) * (len_bytes + msg_len * ext_bytes)
+        + len_bytes
+        + depths.iter().map(|depth| len_bytes + depth * digest_bytes).sum::<usize>();
+    let proof_bytes = 1
+        + len_bytes + (dim - 2) * digest_bytes
+        + len_bytes + msg_len * ext_bytes
+        + len_bytes + test_no * query_bytes;
+
+    // per query and level: hash the row, walk the path, combine the row and encode the lower row, which is
+    // E::DEGREE rows over F
+    let verifier_hashes = test_no * depths.iter().map(|depth| 1 + depth).sum::<usize>();
+    let verifier_muls = test_no * (dim - 1) * E::DEGREE * (msg_len + encode_muls(&precodes, &postcodes));
+
+    Some(SelectedParams { dim, msg_len, code_len, test_no, soundness_bits, proof_bytes, verifier_hashes, verifier_muls })
+}
+
+/// Parameters for t = 2, ..., max_dim with `bits` of soundness, picking the t with the smallest proof
+pub fn select_params<F, E, C, D>(coef_no: usize, bits: f64, max_dim: usize) -> Option<SelectedParams>
+where
+    F: PrimeField,
+    E: ExtensionField<F>,
+    C: CodeSpecification,
+    D: Digest,
+{
+    (2..=max_dim)
+        .filter_map(|dim| tensor_params::<F, E, C, D>(coef_no, dim, bits))
+        .min_by_key(|params| params.proof_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use crate::codespec::Code6;
+    use crate::commit::TensorCommitment;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext2;
+    use crate::fieldspec::ft32::Ft32Ext4;
+    use crate::fieldspec::ft64::Ft64;
+    use crate::fieldspec::ft64::Ft64Ext2;
+    use crate::fieldspec::ft127::Ft127;
+    use crate::fieldspec::ft255::Ft255;
+    use crate::pcs::PolynomialCommitment;
+    use crate::pcs::random_coefs;
+    use crate::serialize::to_bytes;
+
+    #[test]
+    fn matches_test_py() {
+        let fs = 46242760681095663677370860714659204618859642560429202607213929836750194081793f64;
+        assert!((field_size::<Ft255>() / fs - 1f64).abs() < 1e-12);
+        // soundness_error(0.07, 100, 2, 1762, fs) and soundness_error(0.20, 505, 3, 174, fs)
+        assert!((soundness_error(0.07, 100, 2, 1762, fs) / 1.6671087526010784 - 1f64).abs() < 1e-9);
+        assert!((soundness_error(0.20, 505, 3, 174, fs) / 0.49597000385119694 - 1f64).abs() < 1e-9);
+        // lwe_se(2147483647, 0.035, 1024)
+        let se = lwe_soundness_error(2147483647f64, 0.035, 1024);
+        assert!((se[2] / 9.313230689965098e-10 - 1f64).abs() < 1e-9);
+        assert!((se[3] / 9.305255451884036e-12 - 1f64).abs() < 1e-9);
+    }
+
+    #[test]
+    fn predicted_proof_size() {
+        // the number of queries grows quickly with t, keep the proofs small
+        for (dim, bits) in [(2, 20f64), (3, 4f64)] {
+            let selected = tensor_params::<Ft255, Ft255, Code6, Blake3>(4096, dim, bits).unwrap();
+            assert!(selected.soundness_bits >= bits);
+            assert!(selected.msg_len.pow(dim as u32) >= 4096);
+
+            type P = TensorCommitment<Ft255, Code6, Blake3>;
+            let params = P::setup(dim, selected.msg_len, 0, selected.test_no);
+            assert_eq!(params.code_lens, vec![selected.code_len; dim]);
+            let (_, state) = P::commit_coefs(&params, &random_coefs::<Ft255>(4096));
+            let proof = P::open(&params, &state);
+            assert_eq!(to_bytes(&proof).len(), selected.proof_bytes);
+        }
+
+        // the folded rows and M(t-1) grow with the degree of the extension
+        let selected = tensor_params::<Ft32, Ft32Ext4, Code6, Blake3>(4096, 3, 4f64).unwrap();
+        type P = TensorCommitment<Ft32, Code6, Blake3, Ft32Ext4>;
+        let params = P::setup(3, selected.msg_len, 0, selected.test_no);
+        let (_, state) = P::commit_coefs(&params, &random_coefs::<Ft32>(4096));
+        let proof = P::open(&params, &state);
+        assert_eq!(proof.m_last.len(), 4 * selected.msg_len);
+        assert_eq!(to_bytes(&proof).len(), selected.proof_bytes);
+
+        // Goldilocks elements take one 8 byte limb, half of Ft127's
+        assert_eq!(field_size::<Ft64>(), 18446744069414584321f64);
+        let selected = tensor_params::<Ft64, Ft64Ext2, Code6, Blake3>(4096, 2, 40f64).unwrap();
+        type P64 = TensorCommitment<Ft64, Code6, Blake3, Ft64Ext2>;
+        let params = P64::setup(2, selected.msg_len, 0, selected.test_no);
+        let (_, state) = P64::commit_coefs(&params, &random_coefs::<Ft64>(4096));
+        let proof = P64::open(&params, &state);
+        assert_eq!(to_bytes(&proof).len(), selected.proof_bytes);
+        let wide = tensor_params::<Ft127, Ft127, Code6, Blake3>(4096, 2, 40f64).unwrap();
+        assert!(selected.proof_bytes < wide.proof_bytes);
+    }
+
+    #[test]
+    fn extension_soundness() {
+        // the first term of the tensor test's error is bounded by |E|, about 31 bits for Ft32 on its own
+        assert_eq!(extension_size::<Ft32, Ft32Ext2>(), field_size::<Ft32>().powi(2));
+        assert!(tensor_params::<Ft32, Ft32, Code6, Blake3>(4096, 2, 40f64).is_none());
+        assert!(tensor_params::<Ft32, Ft32Ext2, Code6, Blake3>(4096, 2, 40f64).is_some());
+        assert!(lwe_test_no::<Ft32, Ft32, Code6>(40f64).is_none());
+        assert!(lwe_test_no::<Ft32, Ft32Ext2, Code6>(40f64).is_some());
+        assert!(lwe_test_no::<Ft64, Ft64Ext2, Code6>(100f64).is_some());
+    }
+}
@@ -0,0 +1,347 @@
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+use ff::Field;
+use ff::PrimeField;
+use ndarray::ArrayD;
+use ndarray::IxDyn;
+use ndarray::parallel::prelude::*;
+use ndarray::ShapeBuilder;
+use num_traits::Num;
+use crate::codespec::CodeParams;
+
+/// Reasons a proof is rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// the opened data of query `index` does not hash to the committed root
+    MerklePathMismatch { index: usize },
+    /// folding tensor axis `axis` does not match the encoded row of the next level at query `index`
+    LinearCombinationMismatch { axis: usize, index: usize },
+    /// the LWE relation does not hold at query `index`
+    LweRelationMismatch { index: usize },
+    /// the claimed value does not match the folded coefficients
+    EvaluationMismatch,
+    /// the proof does not have the dimensions implied by the parameters
+    ShapeMismatch,
+    /// the transcript yields a zero challenge, which the protocol has to divide by
+    ZeroChallenge,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VerifyError::MerklePathMismatch { index } => write!(f, "merkle path mismatch at query {}", index),
+            VerifyError::LinearCombinationMismatch { axis, index } => write!(f, "linear combination mismatch on axis {} at query {}", axis, index),
+            VerifyError::LweRelationMismatch { index } => write!(f, "lwe relation mismatch at query {}", index),
+            VerifyError::EvaluationMismatch => write!(f, "evaluation mismatch"),
+            VerifyError::ShapeMismatch => write!(f, "proof shape mismatch"),
+            VerifyError::ZeroChallenge => write!(f, "zero challenge"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// A polynomial commitment scheme, split into the steps run by prover and verifier
+pub trait PolynomialCommitment<F>
+where
+    F: PrimeField,
+{
+    /// public parameters known to both prover and verifier
+    type Params;
+    /// what the prover sends when committing
+    type Commitment;
+    /// what the prover keeps between commit and open
+    type ProverState;
+    /// what the prover sends when opening
+    type Proof;
+
+    /// generate parameters for a `dim`-dimensional tensor with `msg_len` coefficients per axis
+    fn setup(dim: usize, msg_len: usize, seed: u64, test_no: usize) -> Self::Params;
+
+    /// like `setup`, with a code specification chosen at runtime instead of the scheme's `CodeSpecification`
+    fn setup_with_code(code: &CodeParams, dim: usize, msg_len: usize, seed: u64, test_no: usize) -> Self::Params {
+        Self::setup_with_shape(code, &vec![msg_len; dim], seed, test_no)
+    }
+
+    /// parameters for a m0 * ... * m(t-1) tensor with `msg_lens[a]` coefficients along axis a, see `fit_shape`
+    fn setup_with_shape(code: &CodeParams, msg_lens: &[usize], seed: u64, test_no: usize) -> Self::Params;
+
+    /// message lengths m0, ..., m(t-1) of the parameters
+    fn shape(params: &Self::Params) -> Vec<usize>;
+
+    /// commit to a tensor of coefficients with shape `shape(params)`
+    fn commit(params: &Self::Params, coefs: &ArrayD<F>) -> (Self::Commitment, Self::ProverState);
+
+    /// commit to at most m0 * ... * m(t-1) coefficients c0, c1, ..., zero-padded and laid out by `coefs_to_tensor`
+    fn commit_coefs(params: &Self::Params, coefs: &[F]) -> (Self::Commitment, Self::ProverState) {
+        Self::commit(params, &coefs_to_tensor(coefs, &Self::shape(params)))
+    }
+
+    /// open a commitment
+    fn open(params: &Self::Params, state: &Self::ProverState) -> Self::Proof;
+
+    /// check an opening against a commitment
+    fn verify(params: &Self::Params, commitment: &Self::Commitment, proof: &Self::Proof) -> Result<(), VerifyError>;
+}
+
+/// A polynomial commitment that can also prove evaluations p(z) = y
+pub trait EvaluationCommitment<F>: PolynomialCommitment<F>
+where
+    F: PrimeField,
+{
+    /// what the prover sends for an evaluation
+    type EvalProof;
+
+    /// prove y = <q0 * ... * q(t-1), coefs> for the query vectors of an evaluation point, see
+    /// `multilinear_query` and `univariate_query`
+    fn open_eval(params: &Self::Params, state: &Self::ProverState, q: &[Vec<F>]) -> (F, Self::EvalProof);
+
+    /// check an evaluation against a commitment
+    fn verify_eval(params: &Self::Params, commitment: &Self::Commitment, q: &[Vec<F>], y: &F, proof: &Self::EvalProof) -> Result<(), VerifyError>;
+}
+
+/// Lay out coefficients c0, c1, ... in a m0 * ... * m(t-1) tensor: ci is stored at (j0, ..., j(t-1))
+/// with i = j0 + j1 * m0 + ... + j(t-1) * m0 * ... * m(t-2), the tail is padded with zeros
+pub fn coefs_to_tensor<F>(coefs: &[F], shape: &[usize]) -> ArrayD<F>
+where
+    F: PrimeField,
+{
+    let coef_no = shape.iter().product::<usize>();
+    assert!(coefs.len() <= coef_no, "{} coefficients do not fit into {:?}", coefs.len(), shape);
+
+    let mut padded = coefs.to_vec();
+    padded.resize(coef_no, <F as Field>::zero());
+    // j0 is the fastest changing index, i.e. column-major order
+    let tensor = ArrayD::from_shape_vec(IxDyn(shape).f(), padded).unwrap();
+    return tensor.as_standard_layout().into_owned();
+}
+
+/// Message lengths of a `dim`-dimensional tensor holding coef_no coefficients with little padding: every axis
+/// gets the smallest length whose power covers what is left over for it and the remaining axes, so only the
+/// last axis is shortened and at most m0 * ... * m(t-2) - 1 coefficients are padding, instead of up to
+/// m^t - (m - 1)^t for the smallest cube m^t
+pub fn fit_shape(coef_no: usize, dim: usize) -> Vec<usize> {
+    assert!(coef_no >= 1 && dim >= 1);
+    let mut shape = Vec::<usize>::with_capacity(dim);
+    let mut prod = 1usize;
+    for a in 0..dim {
+        let rest = coef_no.div_ceil(prod);
+        let axes = (dim - a) as u32;
+        // smallest m with m^axes >= rest, starting from the floating point estimate
+        let mut m = ((rest as f64).powf(1.0 / axes as f64).round() as usize).max(1);
+        while m > 1 && (m - 1).checked_pow(axes).is_none_or(|x| x >= rest) {
+            m -= 1;
+        }
+        while m.checked_pow(axes).is_some_and(|x| x < rest) {
+            m += 1;
+        }
+        shape.push(m);
+        prod *= m;
+    }
+    return shape;
+}
+
+/// Query vectors of the multilinear polynomial in log2(m0) + ... + log2(m(t-1)) variables whose evaluation at
+/// the point with bits i = j0 + j1 * m0 + ... + j(t-1) * m0 * ... * m(t-2) is coefs[j0, ..., j(t-1)];
+/// the variables of axis a are the next log2(ma) entries of z
+pub fn multilinear_query<F>(z: &[F], shape: &[usize]) -> Vec<Vec<F>>
+where
+    F: PrimeField,
+{
+    assert!(shape.iter().all(|m| m.is_power_of_two()));
+    let ks = shape.iter().map(|m| m.trailing_zeros() as usize).collect::<Vec<_>>();
+    assert_eq!(z.len(), ks.iter().sum::<usize>());
+
+    let one = <F as Field>::one();
+    let mut offset = 0;
+    shape
+        .iter()
+        .zip(ks.iter())
+        .map(|(&msg_len, &k)| {
+            let za = &z[offset..(offset + k)];
+            offset += k;
+            (0..msg_len)
+                .map(|j| {
+                    let mut x = one;
+                    for b in 0..k {
+                        x = x.mul(if (j >> b) & 1 == 1 { za[b] } else { one.sub(za[b]) });
+                    }
+                    x
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Query vectors of the univariate polynomial sum_i coefs[j0, ..., j(t-1)] * x^i
+/// with i = j0 + j1 * m0 + ... + j(t-1) * m0 * ... * m(t-2)
+pub fn univariate_query<F>(x: F, shape: &[usize]) -> Vec<Vec<F>>
+where
+    F: PrimeField,
+{
+    let mut q = Vec::<Vec<F>>::with_capacity(shape.len());
+    // x^(m0 * ... * m(a-1))
+    let mut base = x;
+    for &msg_len in shape.iter() {
+        let mut qa = Vec::<F>::with_capacity(msg_len);
+        let mut cur = <F as Field>::one();
+        for _ in 0..msg_len {
+            qa.push(cur);
+            cur = cur.mul(base);
+        }
+        q.push(qa);
+        base = cur;
+    }
+    return q;
+}
+
+/// Wall-clock time of committing and opening, and of verifying
+pub struct Timings {
+    pub commit: Duration,
+    pub verify: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.commit + self.verify
+    }
+}
+
+/// coef_no uniformly random field elements
+pub fn random_coefs<F>(coef_no: usize) -> Vec<F>
+where
+    F: PrimeField,
+{
+    let mut coefs = vec![<F as Field>::zero(); coef_no];
+    coefs.par_iter_mut().for_each(|x| {
+        let mut rng = rand::thread_rng();
+        *x = F::random(&mut rng);
+    });
+    return coefs;
+}
+
+/// commit to coefs, open and verify, returning the commitment, the proof and the timings
+pub fn run<F, P>(
+    params: &P::Params,
+    coefs: &[F],
+) -> Result<(P::Commitment, P::Proof, Timings), VerifyError>
+where
+    F: PrimeField,
+    P: PolynomialCommitment<F>,
+{
+    let start_time = Instant::now();
+
+    let (commitment, state) = P::commit_coefs(params, coefs);
+    let proof = P::open(params, &state);
+
+    let committed_time = Instant::now();
+
+    P::verify(params, &commitment, &proof)?;
+
+    let verified_time = Instant::now();
+
+    let timings = Timings {
+        commit: committed_time.duration_since(start_time),
+        verify: verified_time.duration_since(committed_time),
+    };
+    Ok((commitment, proof, timings))
+}
+
+/// commit to coef_no random coefficients, open, verify and print the timings under `header`
+pub fn bench<F, P>(
+    params: &P::Params,
+    coef_no: usize,
+    header: &str,
+)
+where
+    F: PrimeField + Num,
+    P: PolynomialCommitment<F>,
+{
+    let coefs = random_coefs::<F>(coef_no);
+    let timings = match run::<F, P>(params, &coefs) {
+        Ok((_, _, timings)) => timings,
+        Err(e) => panic!("verification failed: {}", e),
+    };
+
+    println!("{}", header);
+    println!("commit_time: {} ms", timings.commit.as_millis());
+    println!("verify_time: {} ms", timings.verify.as_millis());
+    println!("total_time: {} ms\n", timings.total().as_millis());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use crate::codespec::Code6;
+    use crate::commit::TensorCommitment;
+    use crate::commit_zk::ZkTensorCommitment;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::simple_zk::SimpleZkTensorCommitment;
+
+    // the verifier sets up its own parameters and sees nothing of the prover but the commitment and the proof
+    fn standalone_proofs<P>()
+    where
+        P: PolynomialCommitment<Ft32>,
+    {
+        let prover_params = P::setup(2, 64, 0, 4);
+        let (commitment, state) = P::commit_coefs(&prover_params, &random_coefs(4096));
+        let (other, _) = P::commit_coefs(&prover_params, &random_coefs(4096));
+        let proof = P::open(&prover_params, &state);
+        drop(state);
+
+        let params = P::setup(2, 64, 0, 4);
+        assert_eq!(P::verify(&params, &commitment, &proof), Ok(()));
+        assert!(P::verify(&params, &other, &proof).is_err());
+    }
+
+    #[test]
+    fn replayed_proofs() {
+        standalone_proofs::<TensorCommitment<Ft32, Code6, Blake3>>();
+        standalone_proofs::<ZkTensorCommitment<Ft32, Code6, Blake3>>();
+        standalone_proofs::<SimpleZkTensorCommitment<Ft32, Code6, Blake3>>();
+    }
+
+    #[test]
+    fn fitted_shapes() {
+        for dim in 2..=4 {
+            for coef_no in (1..2000).step_by(7).chain([4096, 1 << 20, 1000, 1001, 999_999]) {
+                let shape = fit_shape(coef_no, dim);
+                assert_eq!(shape.len(), dim);
+                let prod = shape.iter().product::<usize>();
+                assert!(prod >= coef_no);
+                // the padding fits into the last axis
+                assert!(prod - coef_no < prod / shape[dim - 1]);
+            }
+        }
+        assert_eq!(fit_shape(1000, 3), vec![10, 10, 10]);
+        assert_eq!(fit_shape(1001, 3), vec![11, 10, 10]);
+        assert_eq!(fit_shape(4096, 2), vec![64, 64]);
+    }
+
+    #[test]
+    fn rectangular_queries() {
+        let shape = [4, 2, 8];
+        let x = Ft32::from(3);
+        let coefs = (0..60).map(|i| Ft32::from(i * i + 1)).collect::<Vec<_>>();
+        let tensor = coefs_to_tensor(&coefs, &shape);
+        let q = univariate_query(x, &shape);
+        let mut y = <Ft32 as Field>::zero();
+        for (pos, c) in tensor.indexed_iter() {
+            y += q[0][pos[0]] * q[1][pos[1]] * q[2][pos[2]] * c;
+        }
+        let horner = coefs.iter().rev().fold(<Ft32 as Field>::zero(), |acc, c| acc * x + c);
+        assert_eq!(y, horner);
+
+        // at a boolean point the multilinear query picks the coefficient with those bits
+        let i = 45usize;
+        let z = (0..6).map(|b| Ft32::from(((i >> b) & 1) as u64)).collect::<Vec<_>>();
+        let q = multilinear_query(&z, &shape);
+        let mut y = <Ft32 as Field>::zero();
+        for (pos, c) in tensor.indexed_iter() {
+            y += q[0][pos[0]] * q[1][pos[1]] * q[2][pos[2]] * c;
+        }
+        assert_eq!(y, coefs[i]);
+    }
+}
@@ -0,0 +1,629 @@
+use std::fmt;
+use digest::Digest;
+use digest::generic_array::ArrayLength;
+use digest::generic_array::GenericArray;
+use ff::PrimeField;
+use crate::commit::TensorEvalProof;
+use crate::commit::TensorProof;
+use crate::commit::TensorQuery;
+use crate::lwe::LweProof;
+use crate::lwe::LweQuery;
+use crate::simple_zk::SimpleZkProof;
+
+/// version byte written in front of every encoding
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Reasons a byte string is not a valid encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// the input ended in the middle of a value
+    UnexpectedEnd,
+    /// the input was written by an unknown version of the format
+    UnsupportedVersion(u8),
+    /// a field element repr is not the canonical repr of any element
+    NonCanonicalField,
+    /// a length prefix does not fit into the remaining input
+    InvalidLength(u64),
+    /// the input continues after the encoded value
+    TrailingBytes(usize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::NonCanonicalField => write!(f, "non-canonical field element"),
+            DecodeError::InvalidLength(len) => write!(f, "invalid length {}", len),
+            DecodeError::TrailingBytes(len) => write!(f, "{} trailing bytes", len),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Cursor over the bytes being decoded
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        if len > self.bytes.len() {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        let mut buf = [0u8; 8];
+        buf.copy_from_slice(self.read_bytes(8)?);
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// read a length prefix of a sequence whose items take at least `min_item_len` bytes each
+    pub fn read_len(&mut self, min_item_len: usize) -> Result<usize, DecodeError> {
+        let len = self.read_u64()?;
+        match usize::try_from(len) {
+            Ok(l) if l.saturating_mul(min_item_len.max(1)) <= self.remaining() => Ok(l),
+            _ => Err(DecodeError::InvalidLength(len)),
+        }
+    }
+}
+
+/// Canonical binary encoding
+pub trait Encode {
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// Decoding of the canonical binary encoding
+pub trait Decode: Sized {
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError>;
+}
+
+/// encode `value` behind the format version
+pub fn to_bytes<T>(value: &T) -> Vec<u8>
+where
+    T: Encode,
+{
+    let mut out = vec![FORMAT_VERSION];
+    value.encode(&mut out);
+    return out;
+}
+
+/// decode a value written by `to_bytes`, the whole input has to be consumed
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<T, DecodeError>
+where
+    T: Decode,
+{
+    let mut reader = Reader::new(bytes);
+    let version = reader.read_bytes(1)?[0];
+    if version != FORMAT_VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let value = T::decode(&mut reader)?;
+    if reader.remaining() != 0 {
+        return Err(DecodeError::TrailingBytes(reader.remaining()));
+    }
+    Ok(value)
+}
+
+pub fn write_len(out: &mut Vec<u8>, len: usize) {
+    out.extend_from_slice(&(len as u64).to_le_bytes());
+}
+
+// number of bytes of the repr of F
+fn repr_len<F>() -> usize
+where
+    F: PrimeField,
+{
+    F::Repr::default().as_ref().len()
+}
+
+pub fn write_field<F>(out: &mut Vec<u8>, x: &F)
+where
+    F: PrimeField,
+{
+    out.extend_from_slice(x.to_repr().as_ref());
+}
+
+pub fn read_field<F>(reader: &mut Reader) -> Result<F, DecodeError>
+where
+    F: PrimeField,
+{
+    let mut repr = F::Repr::default();
+    let len = repr.as_ref().len();
+    repr.as_mut().copy_from_slice(reader.read_bytes(len)?);
+    Option::from(F::from_repr(repr)).ok_or(DecodeError::NonCanonicalField)
+}
+
+pub fn write_fields<F>(out: &mut Vec<u8>, xs: &[F])
+where
+    F: PrimeField,
+{
+    write_len(out, xs.len());
+    for x in xs.iter() {
+        write_field(out, x);
+    }
+}
+
+pub fn read_fields<F>(reader: &mut Reader) -> Result<Vec<F>, DecodeError>
+where
+    F: PrimeField,
+{
+    let len = reader.read_len(repr_len::<F>())?;
+    (0..len).map(|_| read_field::<F>(reader)).collect()
+}
+
+pub fn write_vec<T>(out: &mut Vec<u8>, xs: &[T])
+where
+    T: Encode,
+{
+    write_len(out, xs.len());
+    for x in xs.iter() {
+        x.encode(out);
+    }
+}
+
+pub fn read_vec<T>(reader: &mut Reader) -> Result<Vec<T>, DecodeError>
+where
+    T: Decode,
+{
+    let len = reader.read_len(1)?;
+    (0..len).map(|_| T::decode(reader)).collect()
+}
+
+// merkle roots and authentication path entries, i.e. `Output<D>`
+impl<N> Encode for GenericArray<u8, N>
+where
+    N: ArrayLength<u8>,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<N> Decode for GenericArray<u8, N>
+where
+    N: ArrayLength<u8>,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(GenericArray::clone_from_slice(reader.read_bytes(N::to_usize())?))
+    }
+}
+
+// the commitment of the simple zk scheme
+impl<A, B> Encode for (A, B)
+where
+    A: Encode,
+    B: Encode,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+}
+
+impl<A, B> Decode for (A, B)
+where
+    A: Decode,
+    B: Decode,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        Ok((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+// authentication paths
+impl<T> Encode for Vec<T>
+where
+    T: Encode,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_vec(out, self);
+    }
+}
+
+impl<T> Decode for Vec<T>
+where
+    T: Decode,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        read_vec(reader)
+    }
+}
+
+// opened rows
+fn write_rows<F>(out: &mut Vec<u8>, rows: &[Vec<F>])
+where
+    F: PrimeField,
+{
+    write_len(out, rows.len());
+    for row in rows.iter() {
+        write_fields(out, row);
+    }
+}
+
+fn read_rows<F>(reader: &mut Reader) -> Result<Vec<Vec<F>>, DecodeError>
+where
+    F: PrimeField,
+{
+    let len = reader.read_len(8)?;
+    (0..len).map(|_| read_fields::<F>(reader)).collect()
+}
+
+impl<F, D> Encode for TensorQuery<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_rows(out, &self.rows);
+        self.paths.encode(out);
+    }
+}
+
+impl<F, D> Decode for TensorQuery<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let rows = read_rows::<F>(reader)?;
+        let paths = Decode::decode(reader)?;
+        Ok(TensorQuery { rows, paths })
+    }
+}
+
+impl<F, D> Encode for TensorProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.roots.encode(out);
+        write_fields(out, &self.m_last);
+        self.queries.encode(out);
+    }
+}
+
+impl<F, D> Decode for TensorProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let roots = Decode::decode(reader)?;
+        let m_last = read_fields::<F>(reader)?;
+        let queries = Decode::decode(reader)?;
+        Ok(TensorProof { roots, m_last, queries })
+    }
+}
+
+impl<F, D> Encode for TensorEvalProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.proximity.encode(out);
+        self.roots.encode(out);
+        write_fields(out, &self.e_last);
+        self.queries.encode(out);
+    }
+}
+
+impl<F, D> Decode for TensorEvalProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let proximity = Decode::decode(reader)?;
+        let roots = Decode::decode(reader)?;
+        let e_last = read_fields::<F>(reader)?;
+        let queries = Decode::decode(reader)?;
+        Ok(TensorEvalProof { proximity, roots, e_last, queries })
+    }
+}
+
+// the rows of M0 and M0_pad opened at one position are written next to each other
+impl<F, D> Encode for SimpleZkProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        assert_eq!(self.proof.queries.len(), self.pad.queries.len());
+        self.proof.roots.encode(out);
+        self.pad.roots.encode(out);
+        write_fields(out, &self.proof.m_last);
+        write_fields(out, &self.pad.m_last);
+        write_len(out, self.proof.queries.len());
+        for (query, pad_query) in self.proof.queries.iter().zip(self.pad.queries.iter()) {
+            query.encode(out);
+            pad_query.encode(out);
+        }
+    }
+}
+
+impl<F, D> Decode for SimpleZkProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let roots = Decode::decode(reader)?;
+        let pad_roots = Decode::decode(reader)?;
+        let m_last = read_fields::<F>(reader)?;
+        let m_last_pad = read_fields::<F>(reader)?;
+        let len = reader.read_len(1)?;
+        let (queries, pad_queries) = (0..len)
+            .map(|_| Ok((TensorQuery::decode(reader)?, TensorQuery::decode(reader)?)))
+            .collect::<Result<Vec<_>, DecodeError>>()?
+            .into_iter()
+            .unzip();
+        Ok(SimpleZkProof {
+            proof: TensorProof { roots, m_last, queries },
+            pad: TensorProof { roots: pad_roots, m_last: m_last_pad, queries: pad_queries },
+        })
+    }
+}
+
+impl<F, D> Encode for LweQuery<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        write_field(out, &self.h2);
+        write_field(out, &self.h1);
+        write_field(out, &self.h0);
+        self.path.encode(out);
+    }
+}
+
+impl<F, D> Decode for LweQuery<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let h2 = read_field::<F>(reader)?;
+        let h1 = read_field::<F>(reader)?;
+        let h0 = read_field::<F>(reader)?;
+        let path = Decode::decode(reader)?;
+        Ok(LweQuery { h2, h1, h0, path })
+    }
+}
+
+impl<F, D> Encode for LweProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.root.encode(out);
+        write_fields(out, &self.fx);
+        self.queries.encode(out);
+    }
+}
+
+impl<F, D> Decode for LweProof<F, D>
+where
+    F: PrimeField,
+    D: Digest,
+{
+    fn decode(reader: &mut Reader) -> Result<Self, DecodeError> {
+        let root = Decode::decode(reader)?;
+        let fx = read_fields::<F>(reader)?;
+        let queries = Decode::decode(reader)?;
+        Ok(LweProof { root, fx, queries })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use digest::Output;
+    use ndarray::ArrayD;
+    use ndarray::IxDyn;
+    use num_traits::Num;
+    use sprs::MulAcc;
+    use crate::codespec::Code6;
+    use crate::codespec::CodeSpecification;
+    use crate::commit::TensorCommitment;
+    use crate::extension::ExtensionField;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext2;
+    use crate::fieldspec::ft32::Ft32Ext4;
+    use crate::fieldspec::ft64::Ft64;
+    use crate::fieldspec::ft64::Ft64Ext2;
+    use crate::fieldspec::ft64::Ft64Ext4;
+    use crate::fieldspec::ft127::Ft127;
+    use crate::fieldspec::ft255::Ft255;
+    use crate::pcs::EvaluationCommitment;
+    use crate::pcs::PolynomialCommitment;
+    use crate::pcs::VerifyError;
+    use crate::pcs::univariate_query;
+    use crate::simple_zk::SimpleZkTensorCommitment;
+
+    fn random_coefs<F>(shape: &[usize]) -> ArrayD<F>
+    where
+        F: PrimeField + Num,
+    {
+        let mut rng = rand::thread_rng();
+        let mut coefs = ArrayD::<F>::zeros(IxDyn(shape));
+        coefs.iter_mut().for_each(|x| *x = F::random(&mut rng));
+        coefs
+    }
+
+    fn round_trip<F, P>(shape: &[usize])
+    where
+        F: PrimeField + Num,
+        P: PolynomialCommitment<F>,
+        P::Commitment: Encode + Decode,
+        P::Proof: Encode + Decode,
+    {
+        let params = P::setup_with_shape(&Code6::code_params(), shape, 0, 4);
+        let (commitment, state) = P::commit(&params, &random_coefs::<F>(shape));
+        let proof = P::open(&params, &state);
+
+        let commitment_bytes = to_bytes(&commitment);
+        let proof_bytes = to_bytes(&proof);
+        let commitment = from_bytes::<P::Commitment>(&commitment_bytes).unwrap();
+        let proof = from_bytes::<P::Proof>(&proof_bytes).unwrap();
+        assert_eq!(to_bytes(&commitment), commitment_bytes);
+        assert_eq!(to_bytes(&proof), proof_bytes);
+        assert_eq!(P::verify(&params, &commitment, &proof), Ok(()));
+
+        // truncated input is rejected
+        for len in (0..proof_bytes.len()).step_by(31).chain([proof_bytes.len() - 1]) {
+            assert!(from_bytes::<P::Proof>(&proof_bytes[..len]).is_err());
+        }
+        let mut longer = proof_bytes.clone();
+        longer.push(0);
+        assert_eq!(from_bytes::<P::Proof>(&longer).err(), Some(DecodeError::TrailingBytes(1)));
+        let mut newer = proof_bytes;
+        newer[0] = FORMAT_VERSION + 1;
+        assert_eq!(from_bytes::<P::Proof>(&newer).err(), Some(DecodeError::UnsupportedVersion(FORMAT_VERSION + 1)));
+    }
+
+    fn eval_round_trip<F>(shape: &[usize])
+    where
+        F: PrimeField + Num + MulAcc,
+    {
+        type P<F> = TensorCommitment<F, Code6, Blake3>;
+        let params = P::<F>::setup_with_shape(&Code6::code_params(), shape, 0, 4);
+        let (commitment, state) = P::<F>::commit(&params, &random_coefs::<F>(shape));
+        let q = univariate_query(F::random(rand::thread_rng()), shape);
+        let (y, proof) = P::<F>::open_eval(&params, &state, &q);
+
+        let bytes = to_bytes(&proof);
+        let proof = from_bytes::<TensorEvalProof<F, Blake3>>(&bytes).unwrap();
+        assert_eq!(to_bytes(&proof), bytes);
+        assert_eq!(P::<F>::verify_eval(&params, &commitment, &q, &y, &proof), Ok(()));
+        for len in (0..bytes.len()).step_by(97) {
+            assert!(from_bytes::<TensorEvalProof<F, Blake3>>(&bytes[..len]).is_err());
+        }
+    }
+
+    fn lwe_round_trip<F>()
+    where
+        F: PrimeField,
+    {
+        let mut rng = rand::thread_rng();
+        let mut root = Output::<Blake3>::default();
+        root.iter_mut().enumerate().for_each(|(i, x)| *x = i as u8);
+        let queries = (0..3)
+            .map(|_| LweQuery::<F, Blake3> {
+                h2: F::random(&mut rng),
+                h1: F::random(&mut rng),
+                h0: F::random(&mut rng),
+                path: vec![root; 5],
+            })
+            .collect();
+        let fx = (0..7).map(|_| F::random(&mut rng)).collect();
+        let proof = LweProof::<F, Blake3> { root, fx, queries };
+
+        let bytes = to_bytes(&proof);
+        let decoded = from_bytes::<LweProof<F, Blake3>>(&bytes).unwrap();
+        assert_eq!(decoded.root, proof.root);
+        assert_eq!(decoded.fx, proof.fx);
+        assert_eq!(to_bytes(&decoded), bytes);
+    }
+
+    fn rejects_non_canonical<F>()
+    where
+        F: PrimeField,
+    {
+        let mut bytes = vec![FORMAT_VERSION];
+        write_len(&mut bytes, 1);
+        bytes.extend(std::iter::repeat_n(0xff, repr_len::<F>()));
+        let mut reader = Reader::new(&bytes[1..]);
+        assert_eq!(read_fields::<F>(&mut reader).err(), Some(DecodeError::NonCanonicalField));
+    }
+
+    fn all<F>()
+    where
+        F: PrimeField + Num + MulAcc,
+    {
+        round_trip::<F, TensorCommitment<F, Code6, Blake3>>(&[16, 16]);
+        round_trip::<F, TensorCommitment<F, Code6, Blake3>>(&[16, 16, 16]);
+        round_trip::<F, TensorCommitment<F, Code6, Blake3>>(&[40, 9, 23]);
+        round_trip::<F, SimpleZkTensorCommitment<F, Code6, Blake3>>(&[16, 16]);
+        round_trip::<F, SimpleZkTensorCommitment<F, Code6, Blake3>>(&[16, 16, 16]);
+        round_trip::<F, SimpleZkTensorCommitment<F, Code6, Blake3>>(&[7, 40, 12]);
+        eval_round_trip::<F>(&[30, 8, 17, 5]);
+        lwe_round_trip::<F>();
+        rejects_non_canonical::<F>();
+    }
+
+    #[test]
+    fn round_trip_ft32() {
+        all::<Ft32>();
+    }
+
+    #[test]
+    fn round_trip_ft64() {
+        assert_eq!(repr_len::<Ft64>(), 8);
+        all::<Ft64>();
+    }
+
+    // challenges from an extension, the folded rows hold E::DEGREE coefficients per symbol
+    fn extension<F, E>()
+    where
+        F: PrimeField + Num + MulAcc,
+        E: ExtensionField<F>,
+    {
+        round_trip::<F, TensorCommitment<F, Code6, Blake3, E>>(&[16, 16]);
+        round_trip::<F, TensorCommitment<F, Code6, Blake3, E>>(&[40, 9, 23]);
+        round_trip::<F, SimpleZkTensorCommitment<F, Code6, Blake3, E>>(&[16, 16]);
+        round_trip::<F, SimpleZkTensorCommitment<F, Code6, Blake3, E>>(&[7, 40, 12]);
+
+        // a proof for other challenges does not verify
+        type P<F> = TensorCommitment<F, Code6, Blake3>;
+        let params = P::<F>::setup_with_shape(&Code6::code_params(), &[16, 16, 16], 0, 4);
+        let (commitment, state) = P::<F>::commit(&params, &random_coefs::<F>(&[16, 16, 16]));
+        let proof = TensorCommitment::<F, Code6, Blake3, E>::open(&params, &state);
+        assert_eq!(proof.m_last.len(), 16 * E::DEGREE);
+        assert_eq!(TensorCommitment::<F, Code6, Blake3, E>::verify(&params, &commitment, &proof), Ok(()));
+        assert_eq!(P::<F>::verify(&params, &commitment, &proof), Err(VerifyError::ShapeMismatch));
+    }
+
+    #[test]
+    fn round_trip_extensions() {
+        extension::<Ft32, Ft32Ext2>();
+        extension::<Ft32, Ft32Ext4>();
+        extension::<Ft64, Ft64Ext2>();
+        extension::<Ft64, Ft64Ext4>();
+    }
+
+    #[test]
+    fn round_trip_ft127() {
+        all::<Ft127>();
+    }
+
+    #[test]
+    fn round_trip_ft255() {
+        all::<Ft255>();
+    }
+
+    #[test]
+    fn rejects_huge_length() {
+        let mut bytes = vec![FORMAT_VERSION];
+        write_len(&mut bytes, usize::MAX);
+        assert_eq!(from_bytes::<Vec<Output<Blake3>>>(&bytes).err(), Some(DecodeError::InvalidLength(u64::MAX)));
+    }
+}
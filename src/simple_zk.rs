@@ -1,458 +1,129 @@
-use std::collections::HashMap;
-use std::time::Instant;
-use std::sync::RwLock;
-use rand::Rng;
-use ff::Field;
+use std::marker::PhantomData;
 use ff::PrimeField;
-use ndarray::Array;
-use ndarray::Axis;
-use ndarray::Dim;
+use ndarray::ArrayD;
+use ndarray::Zip;
 use ndarray::parallel::prelude::*;
 use num_traits::Num;
 use sprs::MulAcc;
 use digest::Digest;
 use digest::Output;
-use sprs::CsMat;
-use crate::helper::next_pow_2;
-use crate::helper::linear_combination_2_1;
-use crate::helper::linear_combination_3_2;
-use crate::helper::linear_combination_4_3;
-use crate::merkle::check_merkle_path;
-use crate::merkle::merkle_tree_commit_2d;
-use crate::merkle::merkle_tree_commit_3d;
-use crate::merkle::merkle_tree_commit_4d;
+use crate::extension::ExtensionField;
+use crate::helper::encode_tensor;
+use crate::merkle::merkle_tree_commit;
+use crate::codespec::CodeParams;
 use crate::codespec::CodeSpecification;
-use crate::codegen::generate;
-use crate::encode::encode;
-
-pub fn check_linear_combination_2_1_simple_zk<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    m_1d: &Array<F, Dim<[usize; 1]>>,
-    m_1d_pad: &Array<F, Dim<[usize; 1]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
-) -> bool
+use crate::commit::TensorParams;
+use crate::commit::TensorProof;
+use crate::commit::open_padded_tensor;
+use crate::commit::verify_padded_tensor;
+use crate::pcs::PolynomialCommitment;
+use crate::pcs::VerifyError;
+use crate::pcs::bench;
+
+/// Prover state of a simple zk tensor commitment
+pub struct SimpleZkState<F, D>
 where
-    F: PrimeField + Num + MulAcc,
+    D: Digest,
 {
-    assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-    assert_eq!(m_1d.shape(), &[msg_len]);
-    assert_eq!(m_1d_pad.shape(), &[code_len]);
-    assert_eq!(r.len(), msg_len);
-
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i1 in 0..msg_len {
-        msg.push(m_1d[[i1]].sub(m_1d_pad[[i1]]));
-    }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, precodes, postcodes);
-    let mut s = <F as Field>::zero();
-    for i2 in 0..msg_len {
-        s = s.add(r[i2].mul(m_2d[[i1, i2]]));
-    }
-    return s == msg[i1].add(m_1d_pad[[i1]]);
+    /// M0: N0 * ... * N(t-2) * m(t-1), encoded and padded
+    pub m0: ArrayD<F>,
+    pub m0_pad: ArrayD<F>,
+    pub hashes_m0: Vec<Output<D>>,
+    pub hashes_m0_pad: Vec<Output<D>>,
 }
 
-pub fn check_linear_combination_3_2_simple_zk<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_3d: &Array<F, Dim<[usize; 3]>>,
-    m_2d: &Array<F, Dim<[usize; 2]>>,
-    m_2d_pad: &Array<F, Dim<[usize; 2]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
-    i2: usize,
-) -> bool
+/// Opening of a simple zk tensor commitment: everything the prover sends after the commitment
+pub struct SimpleZkProof<F, D>
 where
-    F: PrimeField + Num + MulAcc,
+    D: Digest,
 {
-    assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-    assert_eq!(m_2d.shape(), &[code_len, msg_len]);
-    assert_eq!(m_2d_pad.shape(), &[code_len, code_len]);
-    assert_eq!(r.len(), msg_len);
-
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i2 in 0..msg_len {
-        msg.push(m_2d[[i1, i2]].sub(m_2d_pad[[i1, i2]]));
-    }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, &precodes, &postcodes);
-    let mut s = <F as Field>::zero();
-    for i3 in 0..msg_len {
-        s = s.add(r[i3].mul(m_3d[[i1, i2, i3]]));
-    }
-    return s == msg[i2].add(m_2d_pad[[i1, i2]]);
+    /// opening of M0, its M(t-1) has m0 symbols over the extension the challenges are drawn from
+    pub proof: TensorProof<F, D>,
+    /// opening of M0_pad at the same positions, folded over full codewords: its M(t-1) has N0 symbols
+    pub pad: TensorProof<F, D>,
 }
 
-pub fn check_linear_combination_4_3_simple_zk<F>(
-    msg_len: usize, 
-    code_len: usize,
-    m_4d: &Array<F, Dim<[usize; 4]>>,
-    m_3d: &Array<F, Dim<[usize; 3]>>,
-    m_3d_pad: &Array<F, Dim<[usize; 3]>>,
-    r: &Vec<F>,
-    precodes: &Vec<CsMat<F>>,
-    postcodes: &Vec<CsMat<F>>,
-    i1: usize,
-    i2: usize,
-    i3: usize,
-) -> bool
-where
-    F: PrimeField + Num + MulAcc,
-{
-    assert_eq!(m_4d.shape(), &[code_len, code_len, code_len, msg_len]);
-    assert_eq!(m_3d.shape(), &[code_len, code_len, msg_len]);
-    assert_eq!(m_3d_pad.shape(), &[code_len, code_len, code_len]);
-    assert_eq!(r.len(), msg_len);
-
-    let mut msg = Vec::<F>::with_capacity(code_len);
-    for i3 in 0..msg_len {
-        msg.push(m_3d[[i1, i2, i3]].sub(m_3d_pad[[i1, i2, i3]]));
-    }
-    msg.resize(code_len, <F as Field>::zero());
-    encode(&mut msg, &precodes, &postcodes);
-    let mut s = <F as Field>::zero();
-    for i4 in 0..msg_len {
-        s = s.add(r[i4].mul(m_4d[[i1, i2, i3, i4]]));
-    }
-    return s == msg[i3].add(m_3d_pad[[i1, i2, i3]]);
+/// Tensor commitment hiding every committed row behind a random pad, with the random linear combinations drawn
+/// from the extension E of F
+pub struct SimpleZkTensorCommitment<F, C, D, E = F> {
+    _p: PhantomData<(F, C, D, E)>,
 }
 
-pub fn commit_2_dim_simple_zk<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
-    seed: u64,
-    test_no: usize,
-)
+impl<F, C, D, E> PolynomialCommitment<F> for SimpleZkTensorCommitment<F, C, D, E>
 where
     F: PrimeField + Num + MulAcc,
     C: CodeSpecification,
     D: Digest,
+    E: ExtensionField<F>,
 {
-    let mut rng = rand::thread_rng();
-
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-    // m0: N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, msg_len));
-    // generate random coefficient: m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-    
-    let start_time = Instant::now();
-    
-    // m0_pad: N * m
-    let mut m0_pad = Array::<F, _>::zeros((code_len, msg_len));
-    // generate random pad: N * m
-    m0_pad.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(1))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i2, mut x)| {
-            let mut msg = x.to_vec();
-            msg.resize(code_len, <F as Field>::zero());
-            encode(&mut msg, &precodes, &postcodes);
-            for i1 in 0..code_len {
-                x[i1] = msg[i1].add(m0_pad[[i1, i2]]);
-            }
-        });
-
-    // m1: m
-    let m1 = linear_combination_2_1::<F>(msg_len, code_len, &m0, &r1, msg_len);
-    // m1_pad: N
-    let m1_pad = linear_combination_2_1::<F>(msg_len, code_len, &m0_pad, &r1, code_len);
-
-    // commit to m0, m0_pad
-    let hashes_m0 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m0);
-    let hashes_m0_pad = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m0_pad);
-    
-    let committed_time = Instant::now();
-    
-    // verifier has access to r1, m1, m1_pad, m0.root, m0_pad.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    let mut m0_pad_map = HashMap::<usize, Output<D>>::new();
-    m0_pad_map.insert(0, hashes_m0_pad[0].clone());
+    type Params = TensorParams<F>;
+    type Commitment = (Output<D>, Output<D>);
+    type ProverState = SimpleZkState<F, D>;
+    type Proof = SimpleZkProof<F, D>;
 
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        
-        assert!(
-            check_linear_combination_2_1_simple_zk::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &m1_pad, &r1, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-        
-    });
-    
-    let m0_map_rwlock = RwLock::new(m0_map);
-    let m0_pad_map_rwlock = RwLock::new(m0_pad_map);
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m0[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-        // verify the merkle path for m0_pad
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m0_pad[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_pad_map_rwlock, &hashes_m0_pad));
-    });
-
-    let verified_time = Instant::now();
-
-    println!("simple zk t:2 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
-}
+    fn setup(dim: usize, msg_len: usize, seed: u64, test_no: usize) -> TensorParams<F> {
+        TensorParams::new::<C>(dim, msg_len, seed, test_no)
+    }
 
-pub fn commit_3_dim_simple_zk<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
-    seed: u64,
-    test_no: usize,
-)
-where
-    F: PrimeField + Num + MulAcc,
-    C: CodeSpecification,
-    D: Digest,
-{
-    let mut rng = rand::thread_rng();
+    fn setup_with_shape(code: &CodeParams, msg_lens: &[usize], seed: u64, test_no: usize) -> TensorParams<F> {
+        TensorParams::with_shape(code, msg_lens, seed, test_no)
+    }
 
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
+    fn shape(params: &TensorParams<F>) -> Vec<usize> {
+        params.msg_lens.clone()
+    }
 
-    // m0: N * N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, code_len, msg_len));
-    // generate random coefficient: m * m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-    
-    let start_time = Instant::now();
-    
-    // m0_pad: N * N * m
-    let mut m0_pad = Array::<F, _>::zeros((code_len, code_len, msg_len));
-    // generate random pad: N * N * m
-    m0_pad.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
+    fn commit(params: &TensorParams<F>, coefs: &ArrayD<F>) -> ((Output<D>, Output<D>), SimpleZkState<F, D>) {
+        assert_eq!(coefs.ndim(), params.dim);
+        let row_len = params.msg_lens[params.dim - 1];
 
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-    let mut r2 = Vec::<F>::new();
-    r2.resize_with(msg_len, || F::random(&mut rng));
+        let enc = |a: usize, block: &mut [F], width: usize, scratch: &mut Vec<F>| params.codes[a].encode_batch(block, width, scratch);
+        let mut m0 = encode_tensor(&params.msg_lens, &params.code_lens, coefs, &enc);
 
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i3, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i2, mut x)| {
-                    let mut msg = x.to_vec();
-                    msg.resize(code_len, <F as Field>::zero());
-                    encode(&mut msg, &precodes, &postcodes);
-                    for i1 in 0..code_len {
-                        x[i1] = msg[i1];
-                    }
-                });
-        });
-    // encode for axis 1
-    m0
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i3, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(0))
-                .enumerate()
-                .for_each(|(_i1, mut x)| {
-                    let mut msg = x.to_vec();
-                    msg.resize(code_len, <F as Field>::zero());
-                    encode(&mut msg, &precodes, &postcodes);
-                    for i2 in msg_len..code_len {
-                        x[i2] = msg[i2];
-                    }
-                });
+        // m0_pad: N0 * ... * N(t-2) * m(t-1)
+        let mut m0_pad = ArrayD::<F>::zeros(m0.raw_dim());
+        // generate random pad: N0 * ... * N(t-2) * m(t-1)
+        m0_pad.par_iter_mut().for_each(|x| {
+            let mut rng = rand::thread_rng();
+            *x = F::random(&mut rng);
         });
-    // add random pad
-    m0
-        .axis_iter_mut(Axis(2))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i3, mut xx)| {
-            xx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .for_each(|(i2, mut x)| {
-                    for i1 in 0..code_len {
-                        x[i1] = x[i1].add(m0_pad[[i1, i2, i3]]);
-                    }
-                });
-        });
-
-    // m1: N * m
-    let m1 = linear_combination_3_2::<F>(msg_len, code_len, &m0, &r1, msg_len);
-    // m2: m
-    let m2 = linear_combination_2_1::<F>(msg_len, code_len, &m1, &r2, msg_len);
-    
-    // m1_pad: N * N
-    let m1_pad = linear_combination_3_2::<F>(msg_len, code_len, &m0_pad, &r1, code_len);
-    // m2_pad: N
-    let m2_pad = linear_combination_2_1::<F>(msg_len, code_len, &m1_pad, &r2, code_len);
-
-
-    // commit to m0, m0_pad
-    let hashes_m0 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m0);
-    let hashes_m0_pad = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m0_pad);
-    // commit to m1, m1_pad
-    let hashes_m1 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m1);
-    let hashes_m1_pad = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m1_pad);
-
-    let committed_time = Instant::now();
-
-    // verifier has access to r1, r2, m2, m0.root, m1.root, m0_pad.root, m1_pad.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    let mut m1_map = HashMap::<usize, Output<D>>::new();
-    m1_map.insert(0, hashes_m1[0].clone());
-
-    let mut m0_pad_map = HashMap::<usize, Output<D>>::new();
-    m0_pad_map.insert(0, hashes_m0_pad[0].clone());
-    let mut m1_pad_map = HashMap::<usize, Output<D>>::new();
-    m1_pad_map.insert(0, hashes_m1_pad[0].clone());
-    
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_2 = Vec::<usize>::new();
-    idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-
-        assert!(
-            check_linear_combination_3_2_simple_zk::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &m1_pad, &r1, 
-                &precodes, &postcodes, 
-                i1, i2
-            )
-        );
-        assert!(
-            check_linear_combination_2_1_simple_zk::<F>(
-                msg_len, code_len, 
-                &m1, &m2, &m2_pad, &r2, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-    });
-    
-    let m0_map_rwlock = RwLock::new(m0_map);
-    let m1_map_rwlock = RwLock::new(m1_map);
-    let m0_pad_map_rwlock = RwLock::new(m0_pad_map);
-    let m1_pad_map_rwlock = RwLock::new(m1_pad_map);
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-
-        let item_no = code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + np2 - 1;
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m0[[i1, i2, i3]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-        // verify the merkle path for m0_pad
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m0_pad[[i1, i2, i3]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_pad_map_rwlock, &hashes_m0_pad));
-    
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        // verify the merkle path for m1
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m1[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-        // verify the merkle path for m1_pad
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m1_pad[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_pad_map_rwlock, &hashes_m1_pad));
-
-    });
+        // add random pad
+        Zip::from(&mut m0)
+            .and(&m0_pad)
+            .par_apply(|x, &pad| {
+                *x = x.add(pad);
+            });
+
+        // commit to m0, m0_pad
+        let hashes_m0 = merkle_tree_commit::<F, D>(row_len, &m0.view());
+        let hashes_m0_pad = merkle_tree_commit::<F, D>(row_len, &m0_pad.view());
+        let commitment = (hashes_m0[0].clone(), hashes_m0_pad[0].clone());
+        (commitment, SimpleZkState { m0, m0_pad, hashes_m0, hashes_m0_pad })
+    }
 
-    let verified_time = Instant::now();
+    fn open(params: &TensorParams<F>, state: &SimpleZkState<F, D>) -> SimpleZkProof<F, D> {
+        let mut transcript = params.transcript::<D>(b"tensor simple zk");
+        transcript.absorb_digest(&state.hashes_m0[0]);
+        transcript.absorb_digest(&state.hashes_m0_pad[0]);
+        let (proof, pad) = open_padded_tensor::<F, E, D>(params, &state.m0, &state.hashes_m0, &state.m0_pad, &state.hashes_m0_pad, &mut transcript);
+        SimpleZkProof { proof, pad }
+    }
 
-    println!("simple zk t:3 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
+    fn verify(params: &TensorParams<F>, commitment: &(Output<D>, Output<D>), proof: &SimpleZkProof<F, D>) -> Result<(), VerifyError> {
+        let mut transcript = params.transcript::<D>(b"tensor simple zk");
+        transcript.absorb_digest(&commitment.0);
+        transcript.absorb_digest(&commitment.1);
+        let enc = |a: usize, block: &mut [F], width: usize, scratch: &mut Vec<F>| params.codes[a].encode_batch(block, width, scratch);
+        verify_padded_tensor::<F, E, D, _>(params, &commitment.0, &proof.proof, &commitment.1, &proof.pad, &enc, &mut transcript)
+    }
 }
 
-pub fn commit_4_dim_simple_zk<F, C, D>(
-    coef_no: usize, 
-    msg_len: usize, 
-    code_len: usize, 
+/// commit to random coefficients of a t-dimensional tensor with the simple zk scheme, open, verify and print the timings
+pub fn commit_t_dim_simple_zk<F, C, D>(
+    dim: usize,
+    coef_no: usize,
+    msg_len: usize,
+    code_len: usize,
     seed: u64,
     test_no: usize,
 )
@@ -461,280 +132,8 @@ where
     C: CodeSpecification,
     D: Digest,
 {
-    let mut rng = rand::thread_rng();
-
-    // generate codes
-    let (precodes, postcodes) = generate::<F, C>(msg_len, seed);
-
-    // m0: N * N * N * m
-    let mut m0 = Array::<F, _>::zeros((code_len, code_len, code_len, msg_len));
-    // generate random coefficient: m * m * m * m
-    m0.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-    
-    let start_time = Instant::now();
-    
-    // m0_pad: N * N * N * m
-    let mut m0_pad = Array::<F, _>::zeros((code_len, code_len, code_len, msg_len));
-    // generate random pad: N * N * N * m
-    m0_pad.par_iter_mut().for_each(|x| {
-        let mut rng = rand::thread_rng();
-        *x = F::random(&mut rng);
-    });
-
-    // random linear combination
-    let mut r1 = Vec::<F>::new();
-    r1.resize_with(msg_len, || F::random(&mut rng));
-    let mut r2 = Vec::<F>::new();
-    r2.resize_with(msg_len, || F::random(&mut rng));
-    let mut r3 = Vec::<F>::new();
-    r3.resize_with(msg_len, || F::random(&mut rng));
-
-    // encode for axis 0
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(2))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i3, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(1))
-                        .enumerate()
-                        .filter(|(i, _)| i < &(msg_len))
-                        .for_each(|(_i2, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i1 in 0..code_len {
-                                x[i1] = msg[i1];
-                            }
-                        });
-                });
-        });
-    // encode for axis 1
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(2))
-                .enumerate()
-                .filter(|(i, _)| i < &(msg_len))
-                .for_each(|(_i3, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(0))
-                        .enumerate()
-                        .for_each(|(_i1, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i2 in msg_len..code_len {
-                                x[i2] = msg[i2];
-                            }
-                        });
-                });
-        });
-    // encode for axis 2
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(_i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(1))
-                .enumerate()
-                .for_each(|(_i2, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(0))
-                        .enumerate()
-                        .for_each(|(_i1, mut x)| {
-                            let mut msg = x.to_vec();
-                            msg.resize(code_len, <F as Field>::zero());
-                            encode(&mut msg, &precodes, &postcodes);
-                            for i3 in msg_len..code_len {
-                                x[i3] = msg[i3];
-                            }
-                        });
-                });
-        });
-    // add random pad
-    m0
-        .axis_iter_mut(Axis(3))
-        .into_par_iter()
-        .enumerate()
-        .for_each(|(i4, mut xxx)| {
-            xxx
-                .axis_iter_mut(Axis(2))
-                .enumerate()
-                .for_each(|(i3, mut xx)| {
-                    xx
-                        .axis_iter_mut(Axis(1))
-                        .enumerate()
-                        .for_each(|(i2, mut x)| {
-                            for i1 in 0..code_len {
-                                x[i1] = x[i1].add(m0_pad[[i1, i2, i3, i4]]);
-                            }
-                        });
-                });
-        });
-
-    // m1: N * N * m
-    let m1 = linear_combination_4_3::<F>(msg_len, code_len, &m0, &r1, msg_len);
-    // m2: N * m
-    let m2 = linear_combination_3_2::<F>(msg_len, code_len, &m1, &r2, msg_len);
-    // m3: m
-    let m3 = linear_combination_2_1::<F>(msg_len, code_len, &m2, &r3, msg_len);
-
-    // m1_pad: N * N * N
-    let m1_pad = linear_combination_4_3::<F>(msg_len, code_len, &m0_pad, &r1, code_len);
-    // m2_pad: N * N
-    let m2_pad = linear_combination_3_2::<F>(msg_len, code_len, &m1_pad, &r2, code_len);
-    // m3_pad: N
-    let m3_pad = linear_combination_2_1::<F>(msg_len, code_len, &m2_pad, &r3, code_len);
-
-
-    // commit to m0, m0_pad
-    let hashes_m0 = merkle_tree_commit_4d::<F, D>(msg_len, code_len, &m0);
-    let hashes_m0_pad = merkle_tree_commit_4d::<F, D>(msg_len, code_len, &m0_pad);
-    // commit to m1, m1_pad
-    let hashes_m1 = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m1);
-    let hashes_m1_pad = merkle_tree_commit_3d::<F, D>(msg_len, code_len, &m1_pad);
-    // commit to m2, m2_pad
-    let hashes_m2 = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m2);
-    let hashes_m2_pad = merkle_tree_commit_2d::<F, D>(msg_len, code_len, &m2_pad);
-
-    let committed_time = Instant::now();
-
-    // verifier has access to r1, r2, r3, m3, m0.root, m1.root, m2.root, m0_pad.root, m1_pad.root, m2_pad.root
-    let mut m0_map = HashMap::<usize, Output<D>>::new();
-    m0_map.insert(0, hashes_m0[0].clone());
-    let mut m1_map = HashMap::<usize, Output<D>>::new();
-    m1_map.insert(0, hashes_m1[0].clone());
-    let mut m2_map = HashMap::<usize, Output<D>>::new();
-    m2_map.insert(0, hashes_m2[0].clone());
-
-    let mut m0_pad_map = HashMap::<usize, Output<D>>::new();
-    m0_pad_map.insert(0, hashes_m0_pad[0].clone());
-    let mut m1_pad_map = HashMap::<usize, Output<D>>::new();
-    m1_pad_map.insert(0, hashes_m1_pad[0].clone());
-    let mut m2_pad_map = HashMap::<usize, Output<D>>::new();
-    m2_pad_map.insert(0, hashes_m2_pad[0].clone());
-    
-    // sample idx
-    let mut idx_1 = Vec::<usize>::new();
-    idx_1.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_2 = Vec::<usize>::new();
-    idx_2.resize_with(test_no, || rng.gen_range(0..code_len));
-    let mut idx_3 = Vec::<usize>::new();
-    idx_3.resize_with(test_no, || rng.gen_range(0..code_len));
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-        let i3 = idx_3[i];
-
-        assert!(
-            check_linear_combination_4_3_simple_zk::<F>(
-                msg_len, code_len, 
-                &m0, &m1, &m1_pad, &r1, 
-                &precodes, &postcodes, 
-                i1, i2, i3
-            )
-        );
-        assert!(
-            check_linear_combination_3_2_simple_zk::<F>(
-                msg_len, code_len, 
-                &m1, &m2, &m2_pad, &r2, 
-                &precodes, &postcodes, 
-                i1, i2
-            )
-        );
-        assert!(
-            check_linear_combination_2_1_simple_zk::<F>(
-                msg_len, code_len, 
-                &m2, &m3, &m3_pad, &r3, 
-                &precodes, &postcodes, 
-                i1
-            )
-        );
-    });
-    
-    let m0_map_rwlock = RwLock::new(m0_map);
-    let m1_map_rwlock = RwLock::new(m1_map);
-    let m2_map_rwlock = RwLock::new(m2_map);
-    let m0_pad_map_rwlock = RwLock::new(m0_pad_map);
-    let m1_pad_map_rwlock = RwLock::new(m1_pad_map);
-    let m2_pad_map_rwlock = RwLock::new(m2_pad_map);
-    (0..test_no).into_par_iter().for_each(|i| {
-        let i1 = idx_1[i];
-        let i2 = idx_2[i];
-        let i3 = idx_3[i];
-
-        let item_no = code_len * code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + i3 * code_len * code_len + np2 - 1;
-        // verify the merkle path for m0
-        let mut digest = D::new();
-        for i4 in 0..msg_len {
-            digest.update(m0[[i1, i2, i3, i4]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_map_rwlock, &hashes_m0));
-        // verify the merkle path for m0_pad
-        let mut digest = D::new();
-        for i4 in 0..msg_len {
-            digest.update(m0_pad[[i1, i2, i3, i4]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m0_pad_map_rwlock, &hashes_m0_pad));
-
-        let item_no = code_len * code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + i2 * code_len + np2 - 1;
-        // verify the merkle path for m1
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m1[[i1, i2, i3]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_map_rwlock, &hashes_m1));
-        // verify the merkle path for m1_pad
-        let mut digest = D::new();
-        for i3 in 0..msg_len {
-            digest.update(m1_pad[[i1, i2, i3]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m1_pad_map_rwlock, &hashes_m1_pad));
-    
-        let item_no = code_len;
-        let np2 = next_pow_2(item_no);
-        let idx = i1 + np2 - 1;
-        // verify the merkle path for m2
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m2[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m2_map_rwlock, &hashes_m2));
-        // verify the merkle path for m2_pad
-        let mut digest = D::new();
-        for i2 in 0..msg_len {
-            digest.update(m2_pad[[i1, i2]].to_repr());
-        }
-        let cur_hash = digest.finalize();
-        assert!(check_merkle_path::<D>(cur_hash, idx, &m2_pad_map_rwlock, &hashes_m2_pad));
-    });
-
-    let verified_time = Instant::now();
-
-    println!("simple zk t:4 coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", coef_no, msg_len, code_len, test_no);
-    println!("commit_time: {} ms", committed_time.duration_since(start_time).as_millis());
-    println!("verify_time: {} ms", verified_time.duration_since(committed_time).as_millis());
-    println!("total_time: {} ms\n", verified_time.duration_since(start_time).as_millis());
-}
\ No newline at end of file
+    let params = SimpleZkTensorCommitment::<F, C, D>::setup(dim, msg_len, seed, test_no);
+    assert_eq!(params.code_lens, vec![code_len; dim]);
+    let header = format!("simple zk t:{} coef_no:{:?} msg_len:{:?} code_len:{:?} test_no:{:?}", dim, coef_no, msg_len, code_len, test_no);
+    bench::<F, SimpleZkTensorCommitment<F, C, D>>(&params, coef_no, &header);
+}
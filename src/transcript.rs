@@ -0,0 +1,179 @@
+use std::marker::PhantomData;
+use digest::Digest;
+use digest::Output;
+use ff::PrimeField;
+use rand::Rng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use crate::extension::ExtensionField;
+
+/// Fiat-Shamir transcript: every challenge is derived from the hash of everything absorbed so far
+pub struct Transcript<D>
+where
+    D: Digest,
+{
+    state: Output<D>,
+    _p: PhantomData<D>,
+}
+
+impl<D> Transcript<D>
+where
+    D: Digest,
+{
+    /// start a transcript for the protocol named `label`
+    pub fn new(label: &[u8]) -> Self {
+        let mut transcript = Transcript { state: Output::<D>::default(), _p: PhantomData };
+        transcript.absorb_bytes(label);
+        transcript
+    }
+
+    /// absorb a length-prefixed byte string
+    pub fn absorb_bytes(&mut self, bytes: &[u8]) {
+        let mut digest = D::new();
+        digest.update(&self.state);
+        digest.update((bytes.len() as u64).to_le_bytes());
+        digest.update(bytes);
+        self.state = digest.finalize();
+    }
+
+    pub fn absorb_usize(&mut self, n: usize) {
+        self.absorb_bytes(&(n as u64).to_le_bytes());
+    }
+
+    /// absorb a merkle root
+    pub fn absorb_digest(&mut self, root: &Output<D>) {
+        self.absorb_bytes(root);
+    }
+
+    pub fn absorb_field<F>(&mut self, x: &F)
+    where
+        F: PrimeField,
+    {
+        self.absorb_bytes(x.to_repr().as_ref());
+    }
+
+    /// absorb a prover message as one entry of the transcript
+    pub fn absorb_fields<F>(&mut self, xs: &[F])
+    where
+        F: PrimeField,
+    {
+        let mut digest = D::new();
+        digest.update(&self.state);
+        digest.update((xs.len() as u64).to_le_bytes());
+        for x in xs.iter() {
+            digest.update(x.to_repr());
+        }
+        self.state = digest.finalize();
+    }
+
+    /// derive a fresh rng from the transcript, all later challenges depend on this squeeze
+    pub fn squeeze_rng(&mut self) -> ChaCha20Rng {
+        let mut seed = [0u8; 32];
+        let mut filled = 0;
+        let mut block = 0u64;
+        while filled < seed.len() {
+            let mut digest = D::new();
+            digest.update(&self.state);
+            digest.update(b"squeeze");
+            digest.update(block.to_le_bytes());
+            let out = digest.finalize();
+            let len = out.len().min(seed.len() - filled);
+            seed[filled..(filled + len)].copy_from_slice(&out[..len]);
+            filled += len;
+            block += 1;
+        }
+        self.absorb_bytes(b"squeezed");
+        ChaCha20Rng::from_seed(seed)
+    }
+
+    pub fn squeeze_field<F>(&mut self) -> F
+    where
+        F: PrimeField,
+    {
+        F::random(&mut self.squeeze_rng())
+    }
+
+    pub fn squeeze_fields<F>(&mut self, len: usize) -> Vec<F>
+    where
+        F: PrimeField,
+    {
+        let mut rng = self.squeeze_rng();
+        let mut res = Vec::<F>::with_capacity(len);
+        res.resize_with(len, || F::random(&mut rng));
+        return res;
+    }
+
+    /// squeeze `len` elements of the extension E of F from their coefficients, the same values as `squeeze_fields`
+    /// for E = F
+    pub fn squeeze_extension<F, E>(&mut self, len: usize) -> Vec<E>
+    where
+        F: PrimeField,
+        E: ExtensionField<F>,
+    {
+        let coeffs = self.squeeze_fields::<F>(len * E::DEGREE);
+        return coeffs.chunks(E::DEGREE).map(E::from_coeffs).collect();
+    }
+
+    /// squeeze `len` indices in 0..bound
+    pub fn squeeze_indices(&mut self, len: usize, bound: usize) -> Vec<usize> {
+        let mut rng = self.squeeze_rng();
+        let mut res = Vec::<usize>::with_capacity(len);
+        res.resize_with(len, || rng.gen_range(0..bound));
+        return res;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blake3::Hasher as Blake3;
+    use ff::Field;
+    use crate::extension::to_coeff_vec;
+    use crate::fieldspec::ft32::Ft32;
+    use crate::fieldspec::ft32::Ft32Ext4;
+
+    fn transcript(bytes: &[u8], x: u64, root: u8) -> Transcript<Blake3> {
+        let mut transcript = Transcript::<Blake3>::new(b"test");
+        transcript.absorb_bytes(bytes);
+        transcript.absorb_field(&Ft32::from(x));
+        transcript.absorb_digest(&Output::<Blake3>::from([root; 32]));
+        transcript
+    }
+
+    #[test]
+    fn squeezed_indices() {
+        let indices = transcript(b"abc", 5, 7).squeeze_indices(64, 1000);
+        assert_eq!(indices.len(), 64);
+        assert!(indices.iter().all(|&i| i < 1000));
+        assert_eq!(transcript(b"abc", 5, 7).squeeze_indices(64, 1000), indices);
+
+        // every absorbed value changes the challenges
+        assert_ne!(transcript(b"abd", 5, 7).squeeze_indices(64, 1000), indices);
+        assert_ne!(transcript(b"abc\0", 5, 7).squeeze_indices(64, 1000), indices);
+        assert_ne!(transcript(b"abc", 6, 7).squeeze_indices(64, 1000), indices);
+        assert_ne!(transcript(b"abc", 5, 8).squeeze_indices(64, 1000), indices);
+
+        // and so does every squeeze
+        let mut t = transcript(b"abc", 5, 7);
+        let first = t.squeeze_indices(64, 1000);
+        assert_ne!(t.squeeze_indices(64, 1000), first);
+
+        for bound in [1, 2, 3, 17, 1 << 20] {
+            assert!(transcript(b"", 0, 0).squeeze_indices(200, bound).iter().all(|&i| i < bound));
+        }
+    }
+
+    #[test]
+    fn squeezed_extension() {
+        let xs = transcript(b"abc", 5, 7).squeeze_extension::<Ft32, Ft32Ext4>(3);
+        assert_eq!(xs.len(), 3);
+        let coeffs = to_coeff_vec::<Ft32, Ft32Ext4>(&xs);
+        assert_eq!(coeffs.len(), 3 * 4);
+        assert_eq!(coeffs, transcript(b"abc", 5, 7).squeeze_fields::<Ft32>(12));
+        assert!(xs.iter().all(|x| !bool::from(x.is_zero())));
+
+        // over the base field itself it is squeeze_fields
+        let ys = transcript(b"abc", 5, 7).squeeze_extension::<Ft32, Ft32>(3);
+        assert_eq!(ys, transcript(b"abc", 5, 7).squeeze_fields::<Ft32>(3));
+    }
+}
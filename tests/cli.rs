@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::Output;
+
+// a fresh directory for the files of one test
+fn work_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("thesis-cli-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn thesis(args: &[&str], dir: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_thesis"))
+        .args(args)
+        .args(["--field", "ft32", "--shape", "16,8", "--tests", "4"])
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn commit_prove_verify() {
+    let dir = work_dir("plain");
+    let coefs = (0..120).map(|i| (i * i + 7).to_string()).collect::<Vec<_>>();
+    fs::write(dir.join("coefs.txt"), coefs.join(" ")).unwrap();
+
+    let commit = thesis(&["commit", "--coefs", "coefs.txt", "--commitment", "committed.bin"], &dir);
+    assert!(commit.status.success(), "{}", String::from_utf8_lossy(&commit.stderr));
+    let prove = thesis(&["prove", "--coefs", "coefs.txt", "--commitment", "commitment.bin", "--proof", "proof.bin"], &dir);
+    assert!(prove.status.success(), "{}", String::from_utf8_lossy(&prove.stderr));
+    // prove commits again, to the same commitment
+    assert_eq!(fs::read(dir.join("committed.bin")).unwrap(), fs::read(dir.join("commitment.bin")).unwrap());
+
+    let verify = thesis(&["verify", "--commitment", "committed.bin", "--proof", "proof.bin"], &dir);
+    assert_eq!(verify.status.code(), Some(0), "{}", String::from_utf8_lossy(&verify.stderr));
+
+    // the last bytes of the proof are a merkle path entry, changing one is a rejection rather than a decode error
+    let mut proof = fs::read(dir.join("proof.bin")).unwrap();
+    *proof.last_mut().unwrap() ^= 1;
+    fs::write(dir.join("tampered.bin"), proof).unwrap();
+    let verify = thesis(&["verify", "--commitment", "committed.bin", "--proof", "tampered.bin"], &dir);
+    assert_eq!(verify.status.code(), Some(1));
+
+    // a truncated proof does not decode
+    let proof = fs::read(dir.join("proof.bin")).unwrap();
+    fs::write(dir.join("truncated.bin"), &proof[..(proof.len() / 2)]).unwrap();
+    let verify = thesis(&["verify", "--commitment", "committed.bin", "--proof", "truncated.bin"], &dir);
+    assert_eq!(verify.status.code(), Some(2));
+
+    fs::remove_dir_all(&dir).unwrap();
+}